@@ -0,0 +1,173 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::hand_score::LimitHand;
+use crate::user_statistics::{AnswerRecord, UserStatistics};
+use serde::{Deserialize, Serialize};
+
+const SECONDS_IN_DAY: i64 = 24 * 60 * 60;
+// how many kiriage hands should be answered in one day to count as a perfect day
+const KIRIAGE_DAY_ANSWERS_COUNT: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Achievement {
+    HundredCorrectAnswers,
+    ThousandCorrectAnswers,
+    TenInARow,
+    FiftyInARow,
+    AllLimitHands,
+    PerfectKiriageDay,
+}
+
+pub const ALL_ACHIEVEMENTS: [Achievement; 6] = [
+    Achievement::HundredCorrectAnswers,
+    Achievement::ThousandCorrectAnswers,
+    Achievement::TenInARow,
+    Achievement::FiftyInARow,
+    Achievement::AllLimitHands,
+    Achievement::PerfectKiriageDay,
+];
+
+impl Achievement {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Achievement::HundredCorrectAnswers => "First hundred",
+            Achievement::ThousandCorrectAnswers => "Thousand hands",
+            Achievement::TenInARow => "Ten in a row",
+            Achievement::FiftyInARow => "Fifty in a row",
+            Achievement::AllLimitHands => "Limit collector",
+            Achievement::PerfectKiriageDay => "Perfect kiriage day",
+        }
+    }
+
+    pub fn get_description(&self) -> String {
+        match self {
+            Achievement::HundredCorrectAnswers => "answer 100 hands correctly".to_string(),
+            Achievement::ThousandCorrectAnswers => "answer 1000 hands correctly".to_string(),
+            Achievement::TenInARow => "answer 10 hands in a row correctly".to_string(),
+            Achievement::FiftyInARow => "answer 50 hands in a row correctly".to_string(),
+            Achievement::AllLimitHands => {
+                "answer mangan, haneman, baiman, sanbaiman and yakuman hands correctly".to_string()
+            }
+            Achievement::PerfectKiriageDay => format!(
+                "answer at least {} hands with 4 han 30 fu or 3 han 60 fu in one day without mistakes",
+                KIRIAGE_DAY_ANSWERS_COUNT
+            ),
+        }
+    }
+
+    fn is_earned(&self, statistics: &UserStatistics, time: i64) -> bool {
+        match self {
+            Achievement::HundredCorrectAnswers => statistics.correct_answers_count >= 100,
+            Achievement::ThousandCorrectAnswers => statistics.correct_answers_count >= 1000,
+            Achievement::TenInARow => statistics.best_streak >= 10,
+            Achievement::FiftyInARow => statistics.best_streak >= 50,
+            Achievement::AllLimitHands => {
+                let has_limit = |limit: LimitHand| statistics.correct_limits.contains(&limit);
+                has_limit(LimitHand::Mangan)
+                    && has_limit(LimitHand::Haneman)
+                    && has_limit(LimitHand::Baiman)
+                    && has_limit(LimitHand::Sanbaiman)
+                    && statistics
+                        .correct_limits
+                        .iter()
+                        .any(|limit| matches!(limit, LimitHand::Yakuman(_)))
+            }
+            Achievement::PerfectKiriageDay => is_perfect_kiriage_day(statistics, time),
+        }
+    }
+}
+
+// days are counted in UTC
+fn is_perfect_kiriage_day(statistics: &UserStatistics, time: i64) -> bool {
+    let day = time.div_euclid(SECONDS_IN_DAY);
+    let kiriage_answers: Vec<&AnswerRecord> = statistics
+        .answers
+        .iter()
+        .filter(|record| record.time.div_euclid(SECONDS_IN_DAY) == day)
+        .filter(|record| {
+            let combination = record.combination;
+            combination.han == 4 && combination.fu == 30
+                || combination.han == 3 && combination.fu == 60
+        })
+        .collect();
+
+    kiriage_answers.len() >= KIRIAGE_DAY_ANSWERS_COUNT
+        && kiriage_answers.iter().all(|record| record.is_correct)
+}
+
+// returns the achievements that were earned for the first time and remembers them
+pub fn award_new_achievements(statistics: &mut UserStatistics, time: i64) -> Vec<Achievement> {
+    let new_achievements: Vec<Achievement> = ALL_ACHIEVEMENTS
+        .iter()
+        .copied()
+        .filter(|achievement| !statistics.achievements.contains(achievement))
+        .filter(|achievement| achievement.is_earned(statistics, time))
+        .collect();
+    statistics
+        .achievements
+        .extend(new_achievements.iter().copied());
+    new_achievements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::review_schedule::HandCombination;
+    use crate::user_statistics::get_default_statistics;
+
+    fn record(han: u8, fu: u8, is_correct: bool, time: i64) -> AnswerRecord {
+        AnswerRecord {
+            combination: HandCombination {
+                han,
+                fu,
+                is_dealer: false,
+                ron: true,
+            },
+            honba: 0,
+            is_correct,
+            mistake: None,
+            limit: None,
+            answer_seconds: None,
+            time,
+        }
+    }
+
+    #[test]
+    fn test_streak_achievements_are_awarded_once() {
+        let mut statistics = get_default_statistics();
+        for index in 0..9 {
+            statistics.record_answer(record(1, 30, true, index));
+            assert!(award_new_achievements(&mut statistics, index).is_empty());
+        }
+
+        statistics.record_answer(record(1, 30, true, 9));
+        assert_eq!(
+            award_new_achievements(&mut statistics, 9),
+            vec![Achievement::TenInARow]
+        );
+
+        statistics.record_answer(record(1, 30, false, 10));
+        statistics.record_answer(record(1, 30, true, 11));
+        assert_eq!(statistics.current_streak, 1);
+        assert_eq!(statistics.best_streak, 10);
+        assert!(award_new_achievements(&mut statistics, 11).is_empty());
+    }
+
+    #[test]
+    fn test_perfect_kiriage_day() {
+        let mut statistics = get_default_statistics();
+        let day_start = SECONDS_IN_DAY * 10;
+
+        // a mistake on the previous day doesn't count
+        statistics.record_answer(record(4, 30, false, day_start - 1));
+        for index in 0..KIRIAGE_DAY_ANSWERS_COUNT as i64 {
+            statistics.record_answer(record(3, 60, true, day_start + index));
+            statistics.record_answer(record(2, 30, false, day_start + index));
+        }
+        assert!(is_perfect_kiriage_day(&statistics, day_start + 100));
+
+        statistics.record_answer(record(4, 30, false, day_start + 200));
+        assert!(!is_perfect_kiriage_day(&statistics, day_start + 200));
+    }
+}
@@ -0,0 +1,232 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::generation_filter::{get_default_generation_filter, MAX_GENERATED_HAN};
+use crate::hand_score::ScoringSettings;
+use crate::user_statistics::AnswerRecord;
+use serde::{Deserialize, Serialize};
+
+// the accuracy is checked on this many last answers given at the current level
+pub const ROLLING_ANSWERS_COUNT: u32 = 20;
+// fewer answers are enough to notice that the level is too hard
+const MIN_ANSWERS_TO_FALL_BACK: u32 = 10;
+pub const PROMOTION_PERCENT: u32 = 85;
+pub const FALL_BACK_PERCENT: u32 = 60;
+
+pub struct DifficultyLevel {
+    pub name: &'static str,
+    pub description: &'static str,
+    max_han: u8,
+    max_fu: u8,
+    use_honba: bool,
+}
+
+// each level adds new kinds of hands to the previous one
+pub const DIFFICULTY_LEVELS: [DifficultyLevel; 5] = [
+    DifficultyLevel {
+        name: "Common hands",
+        description: "1-4 han with up to 40 fu, no honba",
+        max_han: 4,
+        max_fu: 40,
+        use_honba: false,
+    },
+    DifficultyLevel {
+        name: "All fu",
+        description: "1-4 han with any fu, no honba",
+        max_han: 4,
+        max_fu: 110,
+        use_honba: false,
+    },
+    DifficultyLevel {
+        name: "Honba",
+        description: "1-4 han with any fu and honba",
+        max_han: 4,
+        max_fu: 110,
+        use_honba: true,
+    },
+    DifficultyLevel {
+        name: "Mangan edge cases",
+        description: "up to 5 han, including hands like 4 han 30 fu and 3 han 60 fu that can be kiriage mangan",
+        max_han: 5,
+        max_fu: 110,
+        use_honba: true,
+    },
+    DifficultyLevel {
+        name: "Limit hands",
+        description: "all hands up to yakuman",
+        max_han: MAX_GENERATED_HAN,
+        max_fu: 110,
+        use_honba: true,
+    },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AdaptiveDifficulty {
+    pub level_index: usize,
+    // answers given since the level was last changed
+    pub answers_at_level: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DifficultyChange {
+    Promoted,
+    FellBack,
+}
+
+impl AdaptiveDifficulty {
+    pub fn get_level(&self) -> &'static DifficultyLevel {
+        &DIFFICULTY_LEVELS[self.level_index.min(DIFFICULTY_LEVELS.len() - 1)]
+    }
+
+    pub fn is_last_level(&self) -> bool {
+        self.level_index + 1 >= DIFFICULTY_LEVELS.len()
+    }
+
+    // keeps the rules of the user, only the generated hands are changed
+    pub fn get_scoring_settings(&self, settings: ScoringSettings) -> ScoringSettings {
+        let level = self.get_level();
+        let mut filter = get_default_generation_filter();
+        filter.max_han = level.max_han;
+        filter.max_fu = level.max_fu;
+        ScoringSettings {
+            generation_filter: filter,
+            use_honba: level.use_honba,
+            ..settings
+        }
+    }
+
+    // percent of correct answers among the last answers given at this level,
+    // None if nothing was answered at this level yet
+    pub fn get_rolling_percent(&self, answers: &[AnswerRecord]) -> Option<u32> {
+        let count = (self.answers_at_level.min(ROLLING_ANSWERS_COUNT) as usize).min(answers.len());
+        if count == 0 {
+            return None;
+        }
+        let correct_count = answers[answers.len() - count..]
+            .iter()
+            .filter(|record| record.is_correct)
+            .count();
+        Some((correct_count * 100 / count) as u32)
+    }
+
+    // the answer should already be added to the answers
+    pub fn record_answer(&mut self, answers: &[AnswerRecord]) -> Option<DifficultyChange> {
+        self.answers_at_level += 1;
+        let percent = self.get_rolling_percent(answers)?;
+
+        let change = if self.answers_at_level >= ROLLING_ANSWERS_COUNT
+            && percent >= PROMOTION_PERCENT
+            && !self.is_last_level()
+        {
+            self.level_index += 1;
+            DifficultyChange::Promoted
+        } else if self.answers_at_level >= MIN_ANSWERS_TO_FALL_BACK
+            && percent < FALL_BACK_PERCENT
+            && self.level_index > 0
+        {
+            self.level_index -= 1;
+            DifficultyChange::FellBack
+        } else {
+            return None;
+        };
+
+        self.answers_at_level = 0;
+        Some(change)
+    }
+}
+
+pub fn get_default_adaptive_difficulty() -> AdaptiveDifficulty {
+    AdaptiveDifficulty {
+        level_index: 0,
+        answers_at_level: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::review_schedule::HandCombination;
+    use crate::user_settings::get_default_settings;
+
+    fn answer(is_correct: bool) -> AnswerRecord {
+        AnswerRecord {
+            combination: HandCombination {
+                han: 2,
+                fu: 30,
+                is_dealer: false,
+                ron: true,
+            },
+            honba: 0,
+            is_correct,
+            mistake: None,
+            limit: None,
+            answer_seconds: None,
+            time: 0,
+        }
+    }
+
+    fn record_answers(
+        difficulty: &mut AdaptiveDifficulty,
+        answers: &mut Vec<AnswerRecord>,
+        results: &[bool],
+    ) -> Vec<DifficultyChange> {
+        let mut changes = Vec::new();
+        for is_correct in results {
+            answers.push(answer(*is_correct));
+            changes.extend(difficulty.record_answer(answers));
+        }
+        changes
+    }
+
+    #[test]
+    fn test_level_rises_with_high_accuracy() {
+        let mut difficulty = get_default_adaptive_difficulty();
+        let mut answers = Vec::new();
+
+        let mut results = vec![true; ROLLING_ANSWERS_COUNT as usize];
+        results[0] = false;
+        let changes = record_answers(&mut difficulty, &mut answers, &results);
+        assert_eq!(changes, vec![DifficultyChange::Promoted]);
+        assert_eq!(difficulty.level_index, 1);
+        assert_eq!(difficulty.answers_at_level, 0);
+
+        // answers from the previous level are not counted
+        assert_eq!(difficulty.get_rolling_percent(&answers), None);
+    }
+
+    #[test]
+    fn test_level_falls_back_with_low_accuracy() {
+        let mut difficulty = get_default_adaptive_difficulty();
+        let mut answers = Vec::new();
+
+        // there is no easier level than the first one
+        let changes = record_answers(&mut difficulty, &mut answers, &[false; 10]);
+        assert!(changes.is_empty());
+        assert_eq!(difficulty.level_index, 0);
+
+        difficulty.level_index = 2;
+        difficulty.answers_at_level = 0;
+        let changes = record_answers(
+            &mut difficulty,
+            &mut answers,
+            &[true, false, true, false, true, false, true, false, true],
+        );
+        assert!(changes.is_empty());
+        let changes = record_answers(&mut difficulty, &mut answers, &[false]);
+        assert_eq!(changes, vec![DifficultyChange::FellBack]);
+        assert_eq!(difficulty.level_index, 1);
+    }
+
+    #[test]
+    fn test_level_changes_only_generated_hands() {
+        let mut settings = get_default_settings().scoring_settings;
+        settings.use_kiriage_mangan = true;
+        let difficulty = get_default_adaptive_difficulty();
+
+        let level_settings = difficulty.get_scoring_settings(settings);
+        assert!(level_settings.use_kiriage_mangan);
+        assert!(!level_settings.use_honba);
+        assert_eq!(level_settings.generation_filter.max_han, 4);
+        assert_eq!(level_settings.generation_filter.max_fu, 40);
+    }
+}
@@ -0,0 +1,425 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::hand_score::{HandScoreData, LimitHand};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParsedAnswer {
+    // one payment, e.g. 7700
+    Single(u32),
+    // two payments in the order they were written, e.g. 1000-2000
+    Split(u32, u32),
+    // payment from every player, e.g. 2000 all
+    All(u32),
+    // name of a limit hand, e.g. mangan
+    Limit(LimitHand),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnswerParseError {
+    Empty,
+    InvalidNumber { text: String },
+    TooManyParts { count: usize },
+    AllWithTwoParts,
+}
+
+const ALL_SUFFIXES: [&str; 3] = ["all", "∀", "オール"];
+
+pub fn normalize_char(c: char) -> char {
+    match c {
+        // full-width digits and ascii symbols are shifted by a constant offset
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFF01 + 0x21).unwrap_or(c),
+        '\u{3000}' => ' ',
+        // different kinds of dashes people type on phones
+        '\u{2010}'..='\u{2015}' | '\u{2212}' => '-',
+        _ => c,
+    }
+}
+
+fn is_separator(c: char) -> bool {
+    c == '/' || c == '-' || c.is_whitespace()
+}
+
+fn parse_number(text: &str) -> Result<u32, AnswerParseError> {
+    let invalid_number = || AnswerParseError::InvalidNumber {
+        text: text.to_string(),
+    };
+
+    let (number_text, multiplier) = if let Some(stripped) = text.strip_suffix('k') {
+        (stripped, 1000)
+    } else {
+        (text, 1)
+    };
+
+    // thousands separators, only allowed in groups of three digits
+    let number_text = if number_text.contains(',') {
+        let mut groups = number_text.split(',');
+        let first_group = groups.next().unwrap_or("");
+        if first_group.is_empty() || first_group.len() > 3 || groups.any(|group| group.len() != 3) {
+            return Err(invalid_number());
+        }
+        number_text.replace(',', "")
+    } else {
+        number_text.to_string()
+    };
+
+    let (integer_part, fraction_part) = match number_text.split_once('.') {
+        Some((integer_part, fraction_part)) => (integer_part, fraction_part),
+        None => (number_text.as_str(), ""),
+    };
+
+    let is_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+    if integer_part.is_empty() || !is_digits(integer_part) || !is_digits(fraction_part) {
+        return Err(invalid_number());
+    }
+
+    // fractions only make sense in shortened notation like 1.3k
+    if number_text.contains('.') && (multiplier == 1 || fraction_part.is_empty()) {
+        return Err(invalid_number());
+    }
+    if fraction_part.len() > 3 {
+        return Err(invalid_number());
+    }
+
+    let integer_value = integer_part.parse::<u32>().map_err(|_| invalid_number())?;
+    let fraction_value = if fraction_part.is_empty() {
+        0
+    } else {
+        // "3" -> 300, "35" -> 350
+        format!("{:0<3}", fraction_part)
+            .parse::<u32>()
+            .map_err(|_| invalid_number())?
+    };
+
+    integer_value
+        .checked_mul(multiplier)
+        .and_then(|value| value.checked_add(fraction_value))
+        .ok_or_else(invalid_number)
+}
+
+pub fn parse_answer(text: &str) -> Result<ParsedAnswer, AnswerParseError> {
+    let normalized: String = text.chars().map(normalize_char).collect();
+    let normalized = normalized.trim().to_lowercase();
+
+    if normalized.is_empty() {
+        return Err(AnswerParseError::Empty);
+    }
+
+    if let Some(limit) = LimitHand::from_name(&normalized) {
+        return Ok(ParsedAnswer::Limit(limit));
+    }
+
+    let (numbers_text, is_all) = match ALL_SUFFIXES
+        .iter()
+        .find_map(|suffix| normalized.strip_suffix(suffix))
+    {
+        Some(stripped) => (stripped.trim_end(), true),
+        None => (normalized.as_str(), false),
+    };
+
+    let parts: Vec<&str> = numbers_text
+        .split(is_separator)
+        .filter(|part| !part.is_empty())
+        .collect();
+
+    let numbers = parts
+        .iter()
+        .map(|part| parse_number(part))
+        .collect::<Result<Vec<u32>, AnswerParseError>>()?;
+
+    match (numbers.as_slice(), is_all) {
+        ([], _) => Err(AnswerParseError::Empty),
+        ([value], false) => Ok(ParsedAnswer::Single(*value)),
+        ([value], true) => Ok(ParsedAnswer::All(*value)),
+        ([first, second], false) => Ok(ParsedAnswer::Split(*first, *second)),
+        ([_, _], true) => Err(AnswerParseError::AllWithTwoParts),
+        (numbers, _) => Err(AnswerParseError::TooManyParts {
+            count: numbers.len(),
+        }),
+    }
+}
+
+// in the official notation only dealer tsumo is called as "X all", and it can't be called with one number
+pub fn is_official_answer_form(answer: &ParsedAnswer, hand_score: &HandScoreData) -> bool {
+    let is_dealer_tsumo = hand_score.is_dealer && !hand_score.ron;
+    match answer {
+        ParsedAnswer::Single(_) => !is_dealer_tsumo,
+        ParsedAnswer::All(_) => is_dealer_tsumo,
+        ParsedAnswer::Split(_, _) | ParsedAnswer::Limit(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_parse_single_score() {
+        assert_eq!(parse_answer("7700"), Ok(ParsedAnswer::Single(7700)));
+        assert_eq!(parse_answer("  7700 \n"), Ok(ParsedAnswer::Single(7700)));
+        assert_eq!(parse_answer("7,700"), Ok(ParsedAnswer::Single(7700)));
+        assert_eq!(parse_answer("12,000"), Ok(ParsedAnswer::Single(12000)));
+        assert_eq!(parse_answer("7.7k"), Ok(ParsedAnswer::Single(7700)));
+        assert_eq!(parse_answer("12k"), Ok(ParsedAnswer::Single(12000)));
+        assert_eq!(parse_answer("７７００"), Ok(ParsedAnswer::Single(7700)));
+    }
+
+    #[test]
+    fn test_parse_split_score() {
+        assert_eq!(
+            parse_answer("1000/2000"),
+            Ok(ParsedAnswer::Split(1000, 2000))
+        );
+        assert_eq!(
+            parse_answer("1000-2000"),
+            Ok(ParsedAnswer::Split(1000, 2000))
+        );
+        assert_eq!(
+            parse_answer("1000 2000"),
+            Ok(ParsedAnswer::Split(1000, 2000))
+        );
+        assert_eq!(
+            parse_answer("1000 / 2000"),
+            Ok(ParsedAnswer::Split(1000, 2000))
+        );
+        assert_eq!(
+            parse_answer("2000/1000"),
+            Ok(ParsedAnswer::Split(2000, 1000))
+        );
+        assert_eq!(
+            parse_answer("1.3k-2.6k"),
+            Ok(ParsedAnswer::Split(1300, 2600))
+        );
+        assert_eq!(
+            parse_answer("１０００／２０００"),
+            Ok(ParsedAnswer::Split(1000, 2000))
+        );
+        assert_eq!(
+            parse_answer("1000–2000"),
+            Ok(ParsedAnswer::Split(1000, 2000))
+        );
+    }
+
+    #[test]
+    fn test_parse_all_score() {
+        assert_eq!(parse_answer("2000 all"), Ok(ParsedAnswer::All(2000)));
+        assert_eq!(parse_answer("2000All"), Ok(ParsedAnswer::All(2000)));
+        assert_eq!(parse_answer("2000∀"), Ok(ParsedAnswer::All(2000)));
+        assert_eq!(parse_answer("4,000 ALL"), Ok(ParsedAnswer::All(4000)));
+        assert_eq!(parse_answer("2000オール"), Ok(ParsedAnswer::All(2000)));
+    }
+
+    #[test]
+    fn test_parse_limit_name() {
+        assert_eq!(
+            parse_answer("Mangan"),
+            Ok(ParsedAnswer::Limit(LimitHand::Mangan))
+        );
+        assert_eq!(
+            parse_answer(" 跳満 "),
+            Ok(ParsedAnswer::Limit(LimitHand::Haneman))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse_answer(""), Err(AnswerParseError::Empty));
+        assert_eq!(parse_answer(" / "), Err(AnswerParseError::Empty));
+        assert_eq!(parse_answer("all"), Err(AnswerParseError::Empty));
+        assert_eq!(
+            parse_answer("1000/2000/3000"),
+            Err(AnswerParseError::TooManyParts { count: 3 })
+        );
+        assert_eq!(
+            parse_answer("1000/2000 all"),
+            Err(AnswerParseError::AllWithTwoParts)
+        );
+        assert_eq!(
+            parse_answer("1000a"),
+            Err(AnswerParseError::InvalidNumber {
+                text: "1000a".to_string()
+            })
+        );
+        assert_eq!(
+            parse_answer("77,00"),
+            Err(AnswerParseError::InvalidNumber {
+                text: "77,00".to_string()
+            })
+        );
+        assert_eq!(
+            parse_answer("7.7"),
+            Err(AnswerParseError::InvalidNumber {
+                text: "7.7".to_string()
+            })
+        );
+        assert_eq!(
+            parse_answer("99999999999"),
+            Err(AnswerParseError::InvalidNumber {
+                text: "99999999999".to_string()
+            })
+        );
+    }
+
+    fn format_random_number<R: Rng>(value: u32, rng: &mut R) -> String {
+        let text = match rng.gen_range(0..3) {
+            0 => value.to_string(),
+            1 if value >= 1000 => format!("{},{:03}", value / 1000, value % 1000),
+            2 if value >= 1000 => {
+                let fraction = format!("{:03}", value % 1000);
+                let fraction = fraction.trim_end_matches('0');
+                if fraction.is_empty() {
+                    format!("{}k", value / 1000)
+                } else {
+                    format!("{}.{}k", value / 1000, fraction)
+                }
+            }
+            _ => value.to_string(),
+        };
+
+        if rng.gen_bool(0.3) {
+            // full-width digits
+            text.chars()
+                .map(|c| {
+                    if c.is_ascii_digit() {
+                        char::from_u32(c as u32 - '0' as u32 + 0xFF10).unwrap()
+                    } else {
+                        c
+                    }
+                })
+                .collect()
+        } else {
+            text
+        }
+    }
+
+    #[test]
+    fn test_fuzz_valid_answers_are_parsed_back() {
+        let mut rng = StdRng::seed_from_u64(42);
+        const SEPARATORS: [&str; 6] = ["/", "-", " ", " / ", "　", "－"];
+        const ALL_NOTATIONS: [&str; 5] = [" all", "all", "∀", " ALL", "オール"];
+
+        for _ in 0..10000 {
+            let first = rng.gen_range(1..1000) * 100;
+            let second = rng.gen_range(1..1000) * 100;
+            let padding = " ".repeat(rng.gen_range(0..3));
+
+            match rng.gen_range(0..3) {
+                0 => {
+                    let text = format!(
+                        "{}{}{}",
+                        padding,
+                        format_random_number(first, &mut rng),
+                        padding
+                    );
+                    assert_eq!(
+                        parse_answer(&text),
+                        Ok(ParsedAnswer::Single(first)),
+                        "{}",
+                        text
+                    );
+                }
+                1 => {
+                    let separator = SEPARATORS[rng.gen_range(0..SEPARATORS.len())];
+                    let text = format!(
+                        "{}{}{}{}",
+                        format_random_number(first, &mut rng),
+                        separator,
+                        format_random_number(second, &mut rng),
+                        padding
+                    );
+                    assert_eq!(
+                        parse_answer(&text),
+                        Ok(ParsedAnswer::Split(first, second)),
+                        "{}",
+                        text
+                    );
+                }
+                _ => {
+                    let suffix = ALL_NOTATIONS[rng.gen_range(0..ALL_NOTATIONS.len())];
+                    let text = format!(
+                        "{}{}{}",
+                        format_random_number(first, &mut rng),
+                        suffix,
+                        padding
+                    );
+                    assert_eq!(
+                        parse_answer(&text),
+                        Ok(ParsedAnswer::All(first)),
+                        "{}",
+                        text
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_fuzz_random_input_does_not_panic() {
+        let mut rng = StdRng::seed_from_u64(7);
+        const ALPHABET: [char; 24] = [
+            '0', '1', '5', '9', '/', '-', ' ', ',', '.', 'k', 'a', 'l', '∀', '０', '９', '／',
+            '　', '満', '貫', 'ー', '\u{FF5E}', '\u{FFFF}', '\n', 'K',
+        ];
+
+        for _ in 0..100000 {
+            let length = rng.gen_range(0..12);
+            let text: String = (0..length)
+                .map(|_| {
+                    if rng.gen_bool(0.1) {
+                        rng.gen::<char>()
+                    } else {
+                        ALPHABET[rng.gen_range(0..ALPHABET.len())]
+                    }
+                })
+                .collect();
+
+            // any result is fine as long as it doesn't panic and numbers are not made up
+            if let Ok(ParsedAnswer::Single(_)) = parse_answer(&text) {
+                assert!(text
+                    .chars()
+                    .any(|c| c.is_ascii_digit() || ('０'..='９').contains(&c)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_official_form_depends_on_the_hand() {
+        let hand = |ron: bool, is_dealer: bool| HandScoreData {
+            han: 3,
+            fu: 30,
+            honba: 0,
+            ron,
+            is_dealer,
+        };
+
+        // a single number is not enough for dealer tsumo
+        assert!(!is_official_answer_form(
+            &ParsedAnswer::Single(2000),
+            &hand(false, true)
+        ));
+        assert!(is_official_answer_form(
+            &ParsedAnswer::All(2000),
+            &hand(false, true)
+        ));
+
+        // "X all" is only for dealer tsumo
+        assert!(!is_official_answer_form(
+            &ParsedAnswer::All(5800),
+            &hand(true, true)
+        ));
+        assert!(!is_official_answer_form(
+            &ParsedAnswer::All(1000),
+            &hand(false, false)
+        ));
+        assert!(is_official_answer_form(
+            &ParsedAnswer::Single(5800),
+            &hand(true, true)
+        ));
+        assert!(is_official_answer_form(
+            &ParsedAnswer::Split(1000, 2000),
+            &hand(false, false)
+        ));
+    }
+}
@@ -0,0 +1,212 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::hand_score::HandScoreData;
+use crate::user_settings::{get_default_settings, UserSettings};
+use dashmap::DashMap;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use teloxide::types::UserId;
+
+pub const DAILY_QUESTIONS_COUNT: usize = 5;
+const SECONDS_IN_DAY: i64 = 24 * 60 * 60;
+// rankings of older days are removed
+const KEPT_DAYS_COUNT: i64 = 7;
+// mixed into the seed, so the daily hands don't match hands generated from small seeds elsewhere
+const DAILY_SEED_SALT: u64 = 0x5eed_da11;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DailyResult {
+    pub user_id: UserId,
+    pub name: String,
+    pub answered_count: u32,
+    pub correct_count: u32,
+    // from the first question to the last answer
+    pub seconds: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DailyRankings {
+    // results of everyone who took the challenge of the day (days since the unix epoch in UTC)
+    pub days: DashMap<i64, Vec<DailyResult>>,
+
+    // set when a result has changed since the rankings were saved last time
+    #[serde(skip)]
+    unsaved: AtomicBool,
+}
+
+// the challenge that the user is currently answering
+#[derive(Debug, Clone)]
+pub struct DailyChallenge {
+    pub day: i64,
+    pub hands: Vec<HandScoreData>,
+    pub answered_count: u32,
+    pub correct_count: u32,
+    pub start_time: i64,
+}
+
+// everyone plays with the same rules and answer format, so the results can be compared
+pub fn get_daily_settings() -> UserSettings {
+    get_default_settings()
+}
+
+pub fn get_day(time: i64) -> i64 {
+    time.div_euclid(SECONDS_IN_DAY)
+}
+
+pub fn generate_daily_hands(day: i64) -> Vec<HandScoreData> {
+    let mut rng = StdRng::seed_from_u64(day as u64 ^ DAILY_SEED_SALT);
+    (0..DAILY_QUESTIONS_COUNT)
+        .map(|_| {
+            HandScoreData::generate_winning_hand(&mut rng, get_daily_settings().scoring_settings)
+        })
+        .collect()
+}
+
+impl DailyChallenge {
+    pub fn new(time: i64) -> DailyChallenge {
+        let day = get_day(time);
+        DailyChallenge {
+            day,
+            hands: generate_daily_hands(day),
+            answered_count: 0,
+            correct_count: 0,
+            start_time: time,
+        }
+    }
+
+    pub fn get_current_hand(&self) -> Option<HandScoreData> {
+        self.hands.get(self.answered_count as usize).copied()
+    }
+
+    pub fn record_answer(&mut self, is_correct: bool) {
+        self.answered_count += 1;
+        if is_correct {
+            self.correct_count += 1;
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.answered_count as usize >= self.hands.len()
+    }
+
+    pub fn get_result(&self, user_id: UserId, name: String, time: i64) -> DailyResult {
+        DailyResult {
+            user_id,
+            name,
+            answered_count: self.answered_count,
+            correct_count: self.correct_count,
+            seconds: (time - self.start_time).max(0) as u32,
+        }
+    }
+}
+
+impl DailyRankings {
+    // replaces the previous result of the same user for that day
+    pub fn record_result(&self, day: i64, result: DailyResult) {
+        self.days
+            .retain(|ranking_day, _| day - *ranking_day < KEPT_DAYS_COUNT);
+
+        let mut results = self.days.entry(day).or_default();
+        match results
+            .iter_mut()
+            .find(|existing| existing.user_id == result.user_id)
+        {
+            Some(existing) => *existing = result,
+            None => results.push(result),
+        }
+        self.unsaved.store(true, Ordering::Release);
+    }
+
+    // returns whether the rankings have changed and resets it
+    pub fn take_unsaved(&self) -> bool {
+        self.unsaved.swap(false, Ordering::AcqRel)
+    }
+
+    pub fn get_result(&self, day: i64, user_id: UserId) -> Option<DailyResult> {
+        self.days.get(&day).and_then(|results| {
+            results
+                .iter()
+                .find(|result| result.user_id == user_id)
+                .cloned()
+        })
+    }
+
+    // more correct answers first, then faster
+    pub fn get_ranking(&self, day: i64) -> Vec<DailyResult> {
+        let mut ranking = self
+            .days
+            .get(&day)
+            .map(|results| results.clone())
+            .unwrap_or_default();
+        ranking.sort_by(|first, second| {
+            second
+                .correct_count
+                .cmp(&first.correct_count)
+                .then(first.seconds.cmp(&second.seconds))
+        });
+        ranking
+    }
+}
+
+pub fn get_default_daily_rankings() -> DailyRankings {
+    DailyRankings {
+        days: DashMap::new(),
+        unsaved: AtomicBool::new(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(user_id: u64, correct_count: u32, seconds: u32) -> DailyResult {
+        DailyResult {
+            user_id: UserId(user_id),
+            name: user_id.to_string(),
+            answered_count: DAILY_QUESTIONS_COUNT as u32,
+            correct_count,
+            seconds,
+        }
+    }
+
+    #[test]
+    fn test_everyone_gets_the_same_hands_on_the_same_day() {
+        let morning = 20000 * SECONDS_IN_DAY + 100;
+        let evening = 20000 * SECONDS_IN_DAY + 80000;
+        assert_eq!(
+            DailyChallenge::new(morning).hands,
+            DailyChallenge::new(evening).hands
+        );
+        assert_ne!(
+            DailyChallenge::new(morning).hands,
+            DailyChallenge::new(morning + SECONDS_IN_DAY).hands
+        );
+    }
+
+    #[test]
+    fn test_ranking_order() {
+        let rankings = get_default_daily_rankings();
+        rankings.record_result(10, result(1, 3, 50));
+        rankings.record_result(10, result(2, 4, 90));
+        rankings.record_result(10, result(3, 4, 60));
+        // the result is updated with each answer
+        rankings.record_result(10, result(1, 5, 70));
+
+        let user_ids: Vec<u64> = rankings
+            .get_ranking(10)
+            .iter()
+            .map(|result| result.user_id.0)
+            .collect();
+        assert_eq!(user_ids, vec![1, 3, 2]);
+
+        rankings.record_result(10 + KEPT_DAYS_COUNT, result(1, 1, 10));
+        assert!(rankings.get_ranking(10).is_empty());
+        assert_eq!(
+            rankings.get_result(10 + KEPT_DAYS_COUNT, UserId(1)),
+            Some(result(1, 1, 10))
+        );
+    }
+}
@@ -0,0 +1,306 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::hand_score::ScoringSettings;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MeldKind {
+    Sequence,
+    Triplet,
+    Quad,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FuMeld {
+    pub kind: MeldKind,
+    pub is_open: bool,
+    pub is_terminal_or_honor: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WaitKind {
+    Ryanmen,
+    Kanchan,
+    Penchan,
+    Shanpon,
+    Tanki,
+}
+
+// everything about a winning hand that affects the fu
+#[derive(Debug, Clone)]
+pub struct FuHandComposition {
+    // four melds without the pair, empty for chiitoitsu
+    pub melds: Vec<FuMeld>,
+    // index of the meld that was completed by the winning tile (if any)
+    pub winning_meld_index: Option<usize>,
+    // 0 for a simple pair, 1 for a dragon or a seat/round wind, 2 for a double wind
+    pub pair_yakuhai_count: u8,
+    pub wait: WaitKind,
+    pub is_ron: bool,
+    pub is_rinshan: bool,
+    pub is_chiitoitsu: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FuSource {
+    Base,
+    Chiitoitsu,
+    ClosedRon,
+    Tsumo,
+    Meld(MeldKind, bool, bool),
+    Pair,
+    Wait(WaitKind),
+    OpenPinfuRon,
+}
+
+#[derive(Debug, Clone)]
+pub struct FuBreakdown {
+    pub parts: Vec<(FuSource, u8)>,
+    pub total: u8,
+}
+
+impl FuHandComposition {
+    fn is_closed(&self) -> bool {
+        self.melds.iter().all(|meld| !meld.is_open)
+    }
+
+    fn is_meld_open(&self, index: usize) -> bool {
+        // a triplet completed by ron on a shanpon wait is counted as open
+        self.melds[index].is_open
+            || (self.is_ron
+                && self.wait == WaitKind::Shanpon
+                && self.winning_meld_index == Some(index))
+    }
+}
+
+fn get_meld_fu(meld: &FuMeld, is_open: bool) -> u8 {
+    let base = match meld.kind {
+        MeldKind::Sequence => return 0,
+        MeldKind::Triplet => 2,
+        MeldKind::Quad => 8,
+    };
+    let base = if is_open { base } else { base * 2 };
+    if meld.is_terminal_or_honor {
+        base * 2
+    } else {
+        base
+    }
+}
+
+pub fn calculate_fu(hand: &FuHandComposition, settings: ScoringSettings) -> FuBreakdown {
+    if hand.is_chiitoitsu {
+        let fu = if settings.use_legacy_chiitoitsu {
+            50
+        } else {
+            25
+        };
+        return FuBreakdown {
+            parts: vec![(FuSource::Chiitoitsu, fu)],
+            total: fu,
+        };
+    }
+
+    let mut parts = vec![(FuSource::Base, 20)];
+
+    let is_closed = hand.is_closed();
+    if is_closed && hand.is_ron {
+        parts.push((FuSource::ClosedRon, 10));
+    }
+
+    for (index, meld) in hand.melds.iter().enumerate() {
+        let fu = get_meld_fu(meld, hand.is_meld_open(index));
+        if fu > 0 {
+            parts.push((
+                FuSource::Meld(
+                    meld.kind,
+                    hand.is_meld_open(index),
+                    meld.is_terminal_or_honor,
+                ),
+                fu,
+            ));
+        }
+    }
+
+    match hand.pair_yakuhai_count {
+        0 => {}
+        1 => parts.push((FuSource::Pair, 2)),
+        _ => parts.push((
+            FuSource::Pair,
+            if settings.use_4_fu_double_wind_pair {
+                4
+            } else {
+                2
+            },
+        )),
+    }
+
+    if matches!(
+        hand.wait,
+        WaitKind::Kanchan | WaitKind::Penchan | WaitKind::Tanki
+    ) {
+        parts.push((FuSource::Wait(hand.wait), 2));
+    }
+
+    let is_pinfu_shape = parts
+        .iter()
+        .all(|(source, _)| matches!(source, FuSource::Base | FuSource::ClosedRon));
+
+    if !hand.is_ron {
+        // closed pinfu tsumo doesn't get tsumo fu
+        let is_pinfu_tsumo = is_closed && is_pinfu_shape;
+        if !is_pinfu_tsumo && (!hand.is_rinshan || settings.use_rinshan_tsumo_fu) {
+            parts.push((FuSource::Tsumo, 2));
+        }
+    }
+
+    let mut unrounded: u8 = parts.iter().map(|(_, fu)| fu).sum();
+
+    if !is_closed && hand.is_ron && is_pinfu_shape && settings.use_30_fu_open_pinfu_ron {
+        parts.push((FuSource::OpenPinfuRon, 10));
+        unrounded += 10;
+    }
+
+    FuBreakdown {
+        parts,
+        // round up to the next 10
+        total: unrounded.div_ceil(10) * 10,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user_settings::get_default_settings;
+
+    fn sequence() -> FuMeld {
+        FuMeld {
+            kind: MeldKind::Sequence,
+            is_open: false,
+            is_terminal_or_honor: false,
+        }
+    }
+
+    fn get_unrounded_fu(breakdown: &FuBreakdown) -> u8 {
+        breakdown.parts.iter().map(|(_, fu)| fu).sum()
+    }
+
+    fn hand(melds: Vec<FuMeld>, wait: WaitKind, is_ron: bool) -> FuHandComposition {
+        FuHandComposition {
+            melds,
+            winning_meld_index: Some(0),
+            pair_yakuhai_count: 0,
+            wait,
+            is_ron,
+            is_rinshan: false,
+            is_chiitoitsu: false,
+        }
+    }
+
+    #[test]
+    fn test_closed_pinfu_tsumo_is_20_fu_and_ron_is_30_fu() {
+        let settings = get_default_settings().scoring_settings;
+        let melds = vec![sequence(); 4];
+
+        assert_eq!(
+            calculate_fu(&hand(melds.clone(), WaitKind::Ryanmen, false), settings).total,
+            20
+        );
+        assert_eq!(
+            calculate_fu(&hand(melds, WaitKind::Ryanmen, true), settings).total,
+            30
+        );
+    }
+
+    #[test]
+    fn test_open_pinfu_ron_minimum_depends_on_the_rule() {
+        let mut settings = get_default_settings().scoring_settings;
+        let mut melds = vec![sequence(); 4];
+        melds[1].is_open = true;
+
+        settings.use_30_fu_open_pinfu_ron = true;
+        assert_eq!(
+            calculate_fu(&hand(melds.clone(), WaitKind::Ryanmen, true), settings).total,
+            30
+        );
+        settings.use_30_fu_open_pinfu_ron = false;
+        assert_eq!(
+            calculate_fu(&hand(melds, WaitKind::Ryanmen, true), settings).total,
+            20
+        );
+    }
+
+    #[test]
+    fn test_double_wind_pair_depends_on_the_rule() {
+        let mut settings = get_default_settings().scoring_settings;
+        let mut hand = hand(vec![sequence(); 4], WaitKind::Tanki, true);
+        hand.pair_yakuhai_count = 2;
+
+        // 20 + 10 closed ron + 2 tanki + 4 pair
+        settings.use_4_fu_double_wind_pair = true;
+        assert_eq!(get_unrounded_fu(&calculate_fu(&hand, settings)), 36);
+        // 20 + 10 closed ron + 2 tanki + 2 pair
+        settings.use_4_fu_double_wind_pair = false;
+        assert_eq!(get_unrounded_fu(&calculate_fu(&hand, settings)), 34);
+    }
+
+    #[test]
+    fn test_shanpon_ron_triplet_is_counted_as_open() {
+        let settings = get_default_settings().scoring_settings;
+        let triplet = FuMeld {
+            kind: MeldKind::Triplet,
+            is_open: false,
+            is_terminal_or_honor: true,
+        };
+        let hand_ron = hand(
+            vec![triplet, triplet, sequence(), sequence()],
+            WaitKind::Shanpon,
+            true,
+        );
+        let hand_tsumo = hand(
+            vec![triplet, triplet, sequence(), sequence()],
+            WaitKind::Shanpon,
+            false,
+        );
+
+        // 20 + 10 closed ron + 4 (triplet completed by ron) + 8
+        assert_eq!(get_unrounded_fu(&calculate_fu(&hand_ron, settings)), 42);
+        // 20 + 2 tsumo + 8 + 8
+        assert_eq!(get_unrounded_fu(&calculate_fu(&hand_tsumo, settings)), 38);
+    }
+
+    #[test]
+    fn test_rinshan_tsumo_fu_depends_on_the_rule() {
+        let mut settings = get_default_settings().scoring_settings;
+        let quad = FuMeld {
+            kind: MeldKind::Quad,
+            is_open: true,
+            is_terminal_or_honor: false,
+        };
+        let mut hand = hand(
+            vec![quad, sequence(), sequence(), sequence()],
+            WaitKind::Ryanmen,
+            false,
+        );
+        hand.winning_meld_index = Some(1);
+        hand.is_rinshan = true;
+
+        // 20 + 8 open quad + 2 tsumo
+        settings.use_rinshan_tsumo_fu = true;
+        assert_eq!(get_unrounded_fu(&calculate_fu(&hand, settings)), 30);
+        // 20 + 8 open quad
+        settings.use_rinshan_tsumo_fu = false;
+        assert_eq!(get_unrounded_fu(&calculate_fu(&hand, settings)), 28);
+    }
+
+    #[test]
+    fn test_chiitoitsu_fu_depends_on_the_rule() {
+        let mut settings = get_default_settings().scoring_settings;
+        let mut hand = hand(Vec::new(), WaitKind::Tanki, true);
+        hand.is_chiitoitsu = true;
+
+        settings.use_legacy_chiitoitsu = false;
+        assert_eq!(calculate_fu(&hand, settings).total, 25);
+        settings.use_legacy_chiitoitsu = true;
+        assert_eq!(calculate_fu(&hand, settings).total, 50);
+    }
+}
@@ -0,0 +1,167 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::answer_parser::normalize_char;
+use crate::fu_calculator::{FuBreakdown, FuSource, MeldKind, WaitKind};
+use crate::hand_score::ScoringSettings;
+use crate::tile_hand::{generate_random_tile_hand, TileHand};
+use crate::tiles::*;
+use crate::yaku::{evaluate_hand, WinContext};
+use rand::Rng;
+
+#[derive(Debug, PartialEq)]
+pub enum FuAnswerParseError {
+    Empty,
+    InvalidNumber { text: String },
+}
+
+pub fn get_fu_question_text(hand: &TileHand) -> String {
+    let mut tiles = hand.get_all_tiles();
+    tiles.extend([hand.seat_wind, hand.round_wind]);
+
+    format!(
+        "{}\nSeat wind: {}, round wind: {}\n\n{}\n\nHow many fu is the hand?",
+        hand.format_hand(),
+        hand.seat_wind.get_honor_name(),
+        hand.round_wind.get_honor_name(),
+        get_honor_legend_text(&tiles),
+    )
+}
+
+// accepts answers like "30" or "30 fu"
+pub fn parse_fu_answer(text: &str) -> Result<u8, FuAnswerParseError> {
+    let text: String = text
+        .chars()
+        .map(normalize_char)
+        .collect::<String>()
+        .to_lowercase();
+    let text = text.trim();
+    let text = text.strip_suffix("fu").unwrap_or(text).trim();
+
+    if text.is_empty() {
+        return Err(FuAnswerParseError::Empty);
+    }
+
+    text.parse::<u8>()
+        .map_err(|_| FuAnswerParseError::InvalidNumber {
+            text: text.to_string(),
+        })
+}
+
+fn get_wait_name(wait: WaitKind) -> &'static str {
+    match wait {
+        WaitKind::Ryanmen => "ryanmen",
+        WaitKind::Kanchan => "kanchan",
+        WaitKind::Penchan => "penchan",
+        WaitKind::Shanpon => "shanpon",
+        WaitKind::Tanki => "tanki",
+    }
+}
+
+fn get_fu_source_name(source: FuSource) -> String {
+    match source {
+        FuSource::Base => "base".to_string(),
+        FuSource::Chiitoitsu => "chiitoitsu".to_string(),
+        FuSource::ClosedRon => "closed ron".to_string(),
+        FuSource::Tsumo => "tsumo".to_string(),
+        FuSource::Meld(kind, is_open, is_terminal_or_honor) => format!(
+            "{} {} {}",
+            if is_open { "open" } else { "closed" },
+            if is_terminal_or_honor {
+                "terminal/honor"
+            } else {
+                "simple"
+            },
+            match kind {
+                MeldKind::Sequence => "sequence",
+                MeldKind::Triplet => "triplet",
+                MeldKind::Quad => "quad",
+            }
+        ),
+        FuSource::Pair => "yakuhai pair".to_string(),
+        FuSource::Wait(wait) => format!("{} wait", get_wait_name(wait)),
+        FuSource::OpenPinfuRon => "open pinfu-shape ron".to_string(),
+    }
+}
+
+pub fn get_fu_breakdown_text(breakdown: &FuBreakdown) -> String {
+    let unrounded_fu: u32 = breakdown.parts.iter().map(|(_, fu)| *fu as u32).sum();
+    let parts_text = breakdown
+        .parts
+        .iter()
+        .map(|(source, fu)| format!("{} fu - {}", fu, get_fu_source_name(*source)))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    if unrounded_fu != breakdown.total as u32 {
+        format!(
+            "{}\n{} fu rounded up to {} fu",
+            parts_text, unrounded_fu, breakdown.total
+        )
+    } else {
+        format!("{}\nTotal: {} fu", parts_text, breakdown.total)
+    }
+}
+
+// a hand without yaku can't win, riichi isn't shown in the question so it doesn't count
+pub fn generate_fu_question_hand<R: Rng>(rng: &mut R, settings: ScoringSettings) -> TileHand {
+    let context = WinContext {
+        is_riichi: false,
+        is_ippatsu: false,
+        dora_indicators: Vec::new(),
+    };
+    loop {
+        let hand = generate_random_tile_hand(rng, settings);
+        if evaluate_hand(&hand, &context, settings)
+            .is_some_and(|evaluation| !evaluation.yaku.is_empty())
+        {
+            return hand;
+        }
+    }
+}
+
+// the generated hands always have only one possible fu value, so any interpretation can be shown
+pub fn get_fu_breakdown(hand: &TileHand, settings: ScoringSettings) -> FuBreakdown {
+    hand.calculate_possible_fu(settings)
+        .into_iter()
+        .next()
+        .expect("a generated hand should always be complete")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user_settings::get_default_settings;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_parse_fu_answer() {
+        assert_eq!(parse_fu_answer("30"), Ok(30));
+        assert_eq!(parse_fu_answer(" 40 fu"), Ok(40));
+        assert_eq!(parse_fu_answer("２５FU"), Ok(25));
+        assert_eq!(parse_fu_answer("fu"), Err(FuAnswerParseError::Empty));
+        assert_eq!(
+            parse_fu_answer("thirty"),
+            Err(FuAnswerParseError::InvalidNumber {
+                text: "thirty".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_fu_question_hands_have_yaku() {
+        let settings = get_default_settings().scoring_settings;
+        let context = WinContext {
+            is_riichi: false,
+            is_ippatsu: false,
+            dora_indicators: Vec::new(),
+        };
+        let mut rng = StdRng::seed_from_u64(33);
+        for _ in 0..50 {
+            let hand = generate_fu_question_hand(&mut rng, settings);
+            let evaluation = evaluate_hand(&hand, &context, settings).unwrap();
+            assert!(!evaluation.yaku.is_empty());
+        }
+    }
+}
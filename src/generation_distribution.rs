@@ -0,0 +1,340 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+// how often different hands are generated, the weights are relative to each other
+// and don't need to add up to anything
+// the first weight is for 1 han, the han of a hand depend on whether it's won by the dealer
+// and whether it's won by ron or tsumo (e.g. menzen tsumo adds a han)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HanWeights {
+    pub non_dealer_ron: Vec<f64>,
+    pub non_dealer_tsumo: Vec<f64>,
+    pub dealer_ron: Vec<f64>,
+    pub dealer_tsumo: Vec<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenerationDistribution {
+    pub han_weights: HanWeights,
+    // the weights by fu, hands that can't have the fu are skipped (e.g. 20 fu ron),
+    // 25 fu should not be here, chiitoitsu is generated from chiitoitsu_chance
+    pub ron_fu_weights: BTreeMap<u8, f64>,
+    pub tsumo_fu_weights: BTreeMap<u8, f64>,
+    // the first weight is for 0 honba, only used when honba are counted
+    pub honba_weights: Vec<f64>,
+    pub ron_chance: f64,
+    pub dealer_chance: f64,
+    pub chiitoitsu_chance: f64,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DistributionError {
+    EmptyWeights { name: &'static str },
+    InvalidWeight { name: &'static str },
+    InvalidChance { name: &'static str },
+}
+
+static LOADED_DISTRIBUTION: OnceLock<GenerationDistribution> = OnceLock::new();
+
+// cheap hands are the most common and every han keeps a small weight, so drills of rare hands
+// can still be generated, the same han weights are used for all kinds of wins by default
+pub fn get_default_generation_distribution() -> GenerationDistribution {
+    let han_weights = vec![
+        22.0, 26.0, 21.0, 12.0, 7.0, 4.0, 3.0, 1.5, 1.0, 0.8, 0.3, 0.2, 0.1, 0.05, 0.03, 0.02,
+        0.01, 0.01, 0.01, 0.01,
+    ];
+    GenerationDistribution {
+        han_weights: HanWeights {
+            non_dealer_ron: han_weights.clone(),
+            non_dealer_tsumo: han_weights.clone(),
+            dealer_ron: han_weights.clone(),
+            dealer_tsumo: han_weights,
+        },
+        ron_fu_weights: BTreeMap::from([
+            (30, 50.0),
+            (40, 30.0),
+            (50, 10.0),
+            (60, 5.0),
+            (70, 2.5),
+            (80, 1.0),
+            (90, 0.5),
+            (100, 0.3),
+            (110, 0.2),
+        ]),
+        tsumo_fu_weights: BTreeMap::from([
+            (20, 25.0),
+            (30, 40.0),
+            (40, 22.0),
+            (50, 7.0),
+            (60, 3.5),
+            (70, 1.5),
+            (80, 0.5),
+            (90, 0.3),
+            (100, 0.2),
+        ]),
+        honba_weights: vec![
+            70.0, 18.0, 7.0, 3.0, 1.0, 0.5, 0.3, 0.1, 0.05, 0.02, 0.01, 0.01, 0.01,
+        ],
+        ron_chance: 0.55,
+        dealer_chance: 0.27,
+        chiitoitsu_chance: 0.0252,
+    }
+}
+
+fn validate_weights<'a, I: IntoIterator<Item = &'a f64>>(
+    weights: I,
+    name: &'static str,
+) -> Result<(), DistributionError> {
+    let mut has_positive_weight = false;
+    for weight in weights {
+        if !weight.is_finite() || *weight < 0.0 {
+            return Err(DistributionError::InvalidWeight { name });
+        }
+        has_positive_weight |= *weight > 0.0;
+    }
+    if !has_positive_weight {
+        return Err(DistributionError::EmptyWeights { name });
+    }
+    Ok(())
+}
+
+fn validate_chance(chance: f64, name: &'static str) -> Result<(), DistributionError> {
+    if (0.0..=1.0).contains(&chance) {
+        Ok(())
+    } else {
+        Err(DistributionError::InvalidChance { name })
+    }
+}
+
+impl HanWeights {
+    pub fn get(&self, is_dealer: bool, is_ron: bool) -> &[f64] {
+        match (is_dealer, is_ron) {
+            (false, true) => &self.non_dealer_ron,
+            (false, false) => &self.non_dealer_tsumo,
+            (true, true) => &self.dealer_ron,
+            (true, false) => &self.dealer_tsumo,
+        }
+    }
+}
+
+impl GenerationDistribution {
+    pub fn validate(&self) -> Result<(), DistributionError> {
+        validate_weights(
+            &self.han_weights.non_dealer_ron,
+            "han_weights.non_dealer_ron",
+        )?;
+        validate_weights(
+            &self.han_weights.non_dealer_tsumo,
+            "han_weights.non_dealer_tsumo",
+        )?;
+        validate_weights(&self.han_weights.dealer_ron, "han_weights.dealer_ron")?;
+        validate_weights(&self.han_weights.dealer_tsumo, "han_weights.dealer_tsumo")?;
+        validate_weights(self.ron_fu_weights.values(), "ron_fu_weights")?;
+        validate_weights(self.tsumo_fu_weights.values(), "tsumo_fu_weights")?;
+        validate_weights(&self.honba_weights, "honba_weights")?;
+        validate_chance(self.ron_chance, "ron_chance")?;
+        validate_chance(self.dealer_chance, "dealer_chance")?;
+        validate_chance(self.chiitoitsu_chance, "chiitoitsu_chance")
+    }
+
+    // returns None if none of the allowed han have a weight
+    pub fn choose_han<R: Rng>(
+        &self,
+        rng: &mut R,
+        is_dealer: bool,
+        is_ron: bool,
+        min_han: u8,
+        max_han: u8,
+    ) -> Option<u8> {
+        let han_weights = self.han_weights.get(is_dealer, is_ron);
+        let options: Vec<(u8, f64)> = (min_han.max(1)..=max_han)
+            .map(|han| {
+                let weight = han_weights.get(han as usize - 1).copied();
+                (han, weight.unwrap_or(0.0))
+            })
+            .collect();
+        choose_weighted(rng, &options)
+    }
+
+    // the fu range can be empty when the filter excludes all fu possible for the hand
+    pub fn choose_fu<R: Rng>(
+        &self,
+        rng: &mut R,
+        is_ron: bool,
+        min_fu: u8,
+        max_fu: u8,
+    ) -> Option<u8> {
+        if min_fu > max_fu {
+            return None;
+        }
+
+        let weights = if is_ron {
+            &self.ron_fu_weights
+        } else {
+            &self.tsumo_fu_weights
+        };
+        let options: Vec<(u8, f64)> = weights
+            .range(min_fu..=max_fu)
+            .map(|(fu, weight)| (*fu, *weight))
+            .collect();
+        choose_weighted(rng, &options)
+    }
+
+    pub fn choose_honba<R: Rng>(&self, rng: &mut R, min_honba: u8, max_honba: u8) -> Option<u8> {
+        let options: Vec<(u8, f64)> = (min_honba..=max_honba)
+            .map(|honba| {
+                let weight = self.honba_weights.get(honba as usize).copied();
+                (honba, weight.unwrap_or(0.0))
+            })
+            .collect();
+        choose_weighted(rng, &options)
+    }
+}
+
+fn choose_weighted<R: Rng>(rng: &mut R, options: &[(u8, f64)]) -> Option<u8> {
+    options
+        .choose_weighted(rng, |(_, weight)| *weight)
+        .ok()
+        .map(|(value, _)| *value)
+}
+
+// reads the distribution from the file, the file is created with the default distribution
+// if it doesn't exist, so it can be edited later,
+// a broken file is reported and the default distribution is used instead, so the bot still starts
+pub fn read_generation_distribution_from_file(file_path: &Path) -> GenerationDistribution {
+    if !file_path.exists() {
+        let data = serde_json::to_string_pretty(&get_default_generation_distribution());
+        let data = match data {
+            Ok(data) => data,
+            Err(err) => {
+                log::error!(
+                    "Failed to serialize the default generation distribution: {}",
+                    err
+                );
+                return get_default_generation_distribution();
+            }
+        };
+        if let Err(err) = std::fs::write(file_path, data) {
+            log::error!(
+                "Failed to write the default generation distribution: {}",
+                err
+            );
+            return get_default_generation_distribution();
+        }
+    }
+
+    let data = match std::fs::read_to_string(file_path) {
+        Ok(data) => data,
+        Err(err) => {
+            log::error!("Failed to read the generation distribution file: {}", err);
+            return get_default_generation_distribution();
+        }
+    };
+    let distribution: GenerationDistribution = match serde_json::from_str(&data) {
+        Ok(distribution) => distribution,
+        Err(err) => {
+            log::error!("Failed to parse the generation distribution file: {}", err);
+            return get_default_generation_distribution();
+        }
+    };
+    if let Err(error) = distribution.validate() {
+        log::error!("Invalid generation distribution: {:?}", error);
+        return get_default_generation_distribution();
+    }
+    distribution
+}
+
+// should be called once at the start, before any hands are generated
+pub fn set_generation_distribution(distribution: GenerationDistribution) {
+    if LOADED_DISTRIBUTION.set(distribution).is_err() {
+        log::error!("The generation distribution has already been set");
+    }
+}
+
+pub fn get_generation_distribution() -> &'static GenerationDistribution {
+    LOADED_DISTRIBUTION.get_or_init(get_default_generation_distribution)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_default_distribution_is_valid() {
+        assert_eq!(get_default_generation_distribution().validate(), Ok(()));
+
+        let mut distribution = get_default_generation_distribution();
+        distribution.han_weights.dealer_tsumo = vec![0.0; 20];
+        assert_eq!(
+            distribution.validate(),
+            Err(DistributionError::EmptyWeights {
+                name: "han_weights.dealer_tsumo"
+            })
+        );
+        distribution = get_default_generation_distribution();
+        distribution.ron_chance = 1.5;
+        assert_eq!(
+            distribution.validate(),
+            Err(DistributionError::InvalidChance { name: "ron_chance" })
+        );
+    }
+
+    #[test]
+    fn test_choices_follow_the_weights_and_limits() {
+        let mut distribution = get_default_generation_distribution();
+        distribution.han_weights.non_dealer_ron = vec![0.0, 1.0, 0.0, 3.0];
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let mut fourth_han_count = 0;
+        for _ in 0..1000 {
+            let han = distribution
+                .choose_han(&mut rng, false, true, 1, 20)
+                .unwrap();
+            assert!(han == 2 || han == 4);
+            if han == 4 {
+                fourth_han_count += 1;
+            }
+        }
+        assert!((700..800).contains(&fourth_han_count));
+
+        // han above the table have no weight
+        assert_eq!(distribution.choose_han(&mut rng, false, true, 5, 20), None);
+        // other kinds of wins have their own weights
+        assert!(distribution
+            .choose_han(&mut rng, true, true, 5, 20)
+            .is_some());
+        assert_eq!(distribution.choose_fu(&mut rng, true, 20, 20), None);
+        assert_eq!(distribution.choose_fu(&mut rng, false, 20, 20), Some(20));
+    }
+
+    #[test]
+    fn test_invalid_file_falls_back_to_the_default_distribution() {
+        let file_path = std::env::temp_dir()
+            .join("test_invalid_file_falls_back_to_the_default_distribution.json");
+
+        std::fs::write(&file_path, "{ \"han_weights\": [1.0] }").unwrap();
+        assert_eq!(
+            read_generation_distribution_from_file(&file_path),
+            get_default_generation_distribution()
+        );
+
+        let mut distribution = get_default_generation_distribution();
+        distribution.ron_chance = 2.0;
+        std::fs::write(&file_path, serde_json::to_string(&distribution).unwrap()).unwrap();
+        assert_eq!(
+            read_generation_distribution_from_file(&file_path),
+            get_default_generation_distribution()
+        );
+
+        std::fs::remove_file(&file_path).unwrap();
+    }
+}
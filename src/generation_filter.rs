@@ -0,0 +1,274 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::answer_parser::normalize_char;
+use crate::hand_score::HandScoreData;
+use serde::{Deserialize, Serialize};
+
+pub const MAX_GENERATED_HAN: u8 = 20;
+pub const MAX_GENERATED_HONBA: u8 = 12;
+
+// restricts the hands that are generated for the score questions, the ranges are inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GenerationFilter {
+    pub min_han: u8,
+    pub max_han: u8,
+    pub min_fu: u8,
+    pub max_fu: u8,
+    // None if both are allowed
+    pub dealer: Option<bool>,
+    pub ron: Option<bool>,
+    // only used when honba are counted
+    pub min_honba: u8,
+    pub max_honba: u8,
+}
+
+// a named filter saved by the user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DrillPreset {
+    pub name: String,
+    pub filter: GenerationFilter,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FilterParseError {
+    InvalidText { text: String },
+    InvalidRange { text: String },
+    MissingUnit { text: String },
+}
+
+pub fn get_default_generation_filter() -> GenerationFilter {
+    GenerationFilter {
+        min_han: 1,
+        max_han: MAX_GENERATED_HAN,
+        min_fu: 20,
+        max_fu: 110,
+        dealer: None,
+        ron: None,
+        min_honba: 0,
+        max_honba: MAX_GENERATED_HONBA,
+    }
+}
+
+fn format_range(min: u8, max: u8, default_max: u8, unit: &str) -> String {
+    if max >= default_max {
+        format!("{}+ {}", min, unit)
+    } else if min == max {
+        format!("{} {}", min, unit)
+    } else {
+        format!("{}-{} {}", min, max, unit)
+    }
+}
+
+impl GenerationFilter {
+    pub fn matches(&self, hand: &HandScoreData, use_honba: bool) -> bool {
+        (self.min_han..=self.max_han).contains(&hand.han)
+            && (self.min_fu..=self.max_fu).contains(&hand.fu)
+            && self.dealer.is_none_or(|dealer| dealer == hand.is_dealer)
+            && self.ron.is_none_or(|ron| ron == hand.ron)
+            && (!use_honba || (self.min_honba..=self.max_honba).contains(&hand.honba))
+    }
+
+    // e.g. "1-4 han, 30-50 fu, non-dealer, tsumo"
+    pub fn get_description(&self) -> String {
+        let default_filter = get_default_generation_filter();
+        let mut parts = Vec::new();
+        if (self.min_han, self.max_han) != (default_filter.min_han, default_filter.max_han) {
+            parts.push(format_range(
+                self.min_han,
+                self.max_han,
+                MAX_GENERATED_HAN,
+                "han",
+            ));
+        }
+        if (self.min_fu, self.max_fu) != (default_filter.min_fu, default_filter.max_fu) {
+            parts.push(format_range(
+                self.min_fu,
+                self.max_fu,
+                default_filter.max_fu,
+                "fu",
+            ));
+        }
+        match self.dealer {
+            Some(true) => parts.push("dealer".to_string()),
+            Some(false) => parts.push("non-dealer".to_string()),
+            None => {}
+        }
+        match self.ron {
+            Some(true) => parts.push("ron".to_string()),
+            Some(false) => parts.push("tsumo".to_string()),
+            None => {}
+        }
+        if (self.min_honba, self.max_honba) != (default_filter.min_honba, default_filter.max_honba)
+        {
+            parts.push(format_range(
+                self.min_honba,
+                self.max_honba,
+                MAX_GENERATED_HONBA,
+                "honba",
+            ));
+        }
+
+        if parts.is_empty() {
+            "all hands".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+// "30-50", "5+" or "3"
+fn parse_range(text: &str) -> Option<(u8, u8)> {
+    if let Some(min) = text.strip_suffix('+') {
+        return Some((min.parse().ok()?, u8::MAX));
+    }
+    if let Some((min, max)) = text.split_once('-') {
+        let (min, max) = (min.parse().ok()?, max.parse().ok()?);
+        return (min <= max).then_some((min, max));
+    }
+    let value = text.parse().ok()?;
+    Some((value, value))
+}
+
+// accepts text like "non-dealer tsumo 30-50 fu", "han 1-4 honba 0-2" or "5+ han ron",
+// everything that is not mentioned is not restricted
+pub fn parse_generation_filter(text: &str) -> Result<GenerationFilter, FilterParseError> {
+    let text: String = text
+        .chars()
+        .map(normalize_char)
+        .collect::<String>()
+        .to_lowercase();
+
+    let mut filter = get_default_generation_filter();
+    let mut pending_range: Option<(String, (u8, u8))> = None;
+    let mut pending_unit: Option<&str> = None;
+
+    for word in text.split(|c: char| c == ',' || c.is_whitespace()) {
+        if word.is_empty() {
+            continue;
+        }
+
+        let unit = match word {
+            "dealer" => {
+                filter.dealer = Some(true);
+                continue;
+            }
+            "non-dealer" | "nondealer" => {
+                filter.dealer = Some(false);
+                continue;
+            }
+            "ron" => {
+                filter.ron = Some(true);
+                continue;
+            }
+            "tsumo" => {
+                filter.ron = Some(false);
+                continue;
+            }
+            "han" | "fu" | "honba" => word,
+            _ => {
+                if !word.starts_with(|c: char| c.is_ascii_digit()) {
+                    return Err(FilterParseError::InvalidText {
+                        text: word.to_string(),
+                    });
+                }
+                let Some(range) = parse_range(word) else {
+                    return Err(FilterParseError::InvalidRange {
+                        text: word.to_string(),
+                    });
+                };
+                match pending_unit.take() {
+                    Some(unit) => {
+                        apply_range(&mut filter, unit, range);
+                    }
+                    None => {
+                        if let Some((text, _)) = pending_range.replace((word.to_string(), range)) {
+                            return Err(FilterParseError::MissingUnit { text });
+                        }
+                    }
+                }
+                continue;
+            }
+        };
+
+        match pending_range.take() {
+            Some((_, range)) => apply_range(&mut filter, unit, range),
+            None => pending_unit = Some(unit),
+        }
+    }
+
+    if let Some((text, _)) = pending_range {
+        return Err(FilterParseError::MissingUnit { text });
+    }
+    if let Some(unit) = pending_unit {
+        return Err(FilterParseError::InvalidRange {
+            text: unit.to_string(),
+        });
+    }
+    Ok(filter)
+}
+
+fn apply_range(filter: &mut GenerationFilter, unit: &str, (min, max): (u8, u8)) {
+    match unit {
+        "han" => {
+            filter.min_han = min.max(1);
+            filter.max_han = max.min(MAX_GENERATED_HAN);
+        }
+        "fu" => {
+            filter.min_fu = min;
+            filter.max_fu = max;
+        }
+        _ => {
+            filter.min_honba = min;
+            filter.max_honba = max.min(MAX_GENERATED_HONBA);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_generation_filter() {
+        let filter = parse_generation_filter("only non-dealer tsumo 30-50 fu");
+        assert_eq!(
+            filter,
+            Err(FilterParseError::InvalidText {
+                text: "only".to_string()
+            })
+        );
+
+        let filter = parse_generation_filter("Non-dealer tsumo 30-50 fu, han 1-4").unwrap();
+        assert_eq!((filter.min_han, filter.max_han), (1, 4));
+        assert_eq!((filter.min_fu, filter.max_fu), (30, 50));
+        assert_eq!(filter.dealer, Some(false));
+        assert_eq!(filter.ron, Some(false));
+        assert_eq!(
+            filter.get_description(),
+            "1-4 han, 30-50 fu, non-dealer, tsumo"
+        );
+
+        let filter = parse_generation_filter("5+ han ron honba 1").unwrap();
+        assert_eq!((filter.min_han, filter.max_han), (5, MAX_GENERATED_HAN));
+        assert_eq!((filter.min_honba, filter.max_honba), (1, 1));
+        assert_eq!(filter.get_description(), "5+ han, ron, 1 honba");
+
+        assert_eq!(
+            parse_generation_filter("30 40 fu"),
+            Err(FilterParseError::MissingUnit {
+                text: "30".to_string()
+            })
+        );
+        assert_eq!(
+            parse_generation_filter("50-30 fu"),
+            Err(FilterParseError::InvalidRange {
+                text: "50-30".to_string()
+            })
+        );
+        assert_eq!(
+            parse_generation_filter("").unwrap(),
+            get_default_generation_filter()
+        );
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::hand_score::{HandScoreData, LimitHand};
+use crate::mistake_classifier::MistakeType;
+use std::collections::HashMap;
+use teloxide::types::UserId;
+
+pub const DEFAULT_QUESTIONS_COUNT: u32 = 10;
+pub const MAX_QUESTIONS_COUNT: u32 = 50;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemberScore {
+    pub name: String,
+    pub points: u32,
+    pub wrong_answers: u32,
+}
+
+// an answer of a member that goes to the statistics of the member's own state
+#[derive(Debug, Clone)]
+pub struct GroupAnswer {
+    pub user_id: UserId,
+    pub hand_score: HandScoreData,
+    pub limit: Option<LimitHand>,
+    pub is_correct: bool,
+    pub mistake: Option<MistakeType>,
+    pub answer_seconds: u32,
+}
+
+// a round of questions in a group chat where the first correct answer gets a point
+#[derive(Debug, Clone)]
+pub struct GroupGame {
+    pub hand_score: HandScoreData,
+    pub questions_count: u32,
+    // starts from 1
+    pub question_number: u32,
+    // unix timestamp of when the current question was asked
+    pub question_time: i64,
+    pub members: HashMap<UserId, MemberScore>,
+    // only this member can restart the game, anyone can stop it with /group stop
+    pub started_by: UserId,
+}
+
+impl GroupGame {
+    pub fn new(
+        hand_score: HandScoreData,
+        questions_count: u32,
+        started_by: UserId,
+        question_time: i64,
+    ) -> GroupGame {
+        GroupGame {
+            hand_score,
+            questions_count,
+            question_number: 1,
+            question_time,
+            members: HashMap::new(),
+            started_by,
+        }
+    }
+
+    fn get_member(&mut self, user_id: UserId, name: &str) -> &mut MemberScore {
+        let member = self.members.entry(user_id).or_insert_with(|| MemberScore {
+            name: String::new(),
+            points: 0,
+            wrong_answers: 0,
+        });
+        // members can change their names during the game
+        member.name = name.to_string();
+        member
+    }
+
+    pub fn record_correct_answer(&mut self, user_id: UserId, name: &str) {
+        self.get_member(user_id, name).points += 1;
+    }
+
+    pub fn record_wrong_answer(&mut self, user_id: UserId, name: &str) {
+        self.get_member(user_id, name).wrong_answers += 1;
+    }
+
+    pub fn is_last_question(&self) -> bool {
+        self.question_number >= self.questions_count
+    }
+
+    pub fn start_next_question(&mut self, hand_score: HandScoreData, question_time: i64) {
+        self.hand_score = hand_score;
+        self.question_number += 1;
+        self.question_time = question_time;
+    }
+
+    // the most points first, fewer wrong answers break the ties
+    pub fn get_scoreboard(&self) -> Vec<&MemberScore> {
+        let mut scoreboard: Vec<&MemberScore> = self.members.values().collect();
+        scoreboard.sort_by(|first, second| {
+            second
+                .points
+                .cmp(&first.points)
+                .then(first.wrong_answers.cmp(&second.wrong_answers))
+                .then(first.name.cmp(&second.name))
+        });
+        scoreboard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hand() -> HandScoreData {
+        HandScoreData {
+            han: 1,
+            fu: 30,
+            honba: 0,
+            ron: true,
+            is_dealer: false,
+        }
+    }
+
+    #[test]
+    fn test_scoreboard_order() {
+        let mut game = GroupGame::new(hand(), 3, UserId(1), 0);
+        game.record_wrong_answer(UserId(1), "Alice");
+        game.record_correct_answer(UserId(1), "Alice");
+        game.record_correct_answer(UserId(2), "Bob");
+        game.record_correct_answer(UserId(3), "Carol");
+        game.record_correct_answer(UserId(3), "Carol");
+        game.record_wrong_answer(UserId(4), "Dave");
+
+        let names: Vec<&str> = game
+            .get_scoreboard()
+            .iter()
+            .map(|member| member.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Carol", "Bob", "Alice", "Dave"]);
+    }
+
+    #[test]
+    fn test_questions_count() {
+        let mut game = GroupGame::new(hand(), 2, UserId(1), 0);
+        assert!(!game.is_last_question());
+        game.start_next_question(hand(), 10);
+        assert_eq!(game.question_number, 2);
+        assert!(game.is_last_question());
+    }
+}
@@ -1,17 +1,38 @@
 // Copyright (C) Pavel Grebnev 2024
 // Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
 
+use crate::generation_distribution::{get_generation_distribution, GenerationDistribution};
+use crate::generation_filter::GenerationFilter;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+// generation gives up on the filter after that many hands that don't match it
+const MAX_GENERATION_ATTEMPTS: u32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum KazoeYakumanRule {
+    // 13+ han is yakuman
+    Yakuman,
+    // 13+ han is capped at sanbaiman
+    Sanbaiman,
+    // every 13 han count as one more yakuman
+    MultipleYakuman,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct ScoringSettings {
     pub use_kiriage_mangan: bool,
     pub use_honba: bool,
-    pub use_kazoe_yakuman: bool,
-    pub only_less_than_5_han: bool,
+    pub kazoe_yakuman: KazoeYakumanRule,
+    pub generation_filter: GenerationFilter,
+    pub use_4_fu_double_wind_pair: bool,
+    pub use_30_fu_open_pinfu_ron: bool,
+    pub use_legacy_chiitoitsu: bool,
+    pub use_rinshan_tsumo_fu: bool,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct HandScoreData {
     pub han: u8,
     pub fu: u8,
@@ -20,131 +41,268 @@ pub struct HandScoreData {
     pub is_dealer: bool,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum LimitHand {
+    Mangan,
+    Haneman,
+    Baiman,
+    Sanbaiman,
+    // the number of yakuman counted
+    Yakuman(u8),
+}
+
+#[derive(Debug, Clone, Copy)]
 pub struct HandScoreTotals {
-    pub dealer: u16,
-    pub others: u16,
+    pub dealer: u32,
+    pub others: u32,
+    pub limit: Option<LimitHand>,
 }
 
-impl HandScoreData {
-    fn generate_gaussian(mean: f64, std_dev: f64, min: f64, max: f64) -> f64 {
-        if min >= max {
-            return min;
+// one of the two payments of a non-dealer tsumo
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TsumoPayment {
+    NonDealer,
+    Dealer,
+}
+
+impl TsumoPayment {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            TsumoPayment::NonDealer => "non-dealers' payment",
+            TsumoPayment::Dealer => "dealer's payment",
         }
+    }
+}
 
-        let mut loop_count = 0;
+impl HandScoreTotals {
+    pub fn get_payment(&self, payment: TsumoPayment) -> u32 {
+        match payment {
+            TsumoPayment::NonDealer => self.others,
+            TsumoPayment::Dealer => self.dealer,
+        }
+    }
 
-        while loop_count < 100 {
-            loop_count += 1;
+    // the limit name is not compared, only what the players pay
+    pub fn is_same_payment(&self, other: &HandScoreTotals) -> bool {
+        self.others == other.others && self.dealer == other.dealer
+    }
+}
 
-            let mut x = 0.0;
-            let mut y = 0.0;
-            let mut s = 0.0;
-            while s >= 1.0 || s == 0.0 {
-                x = 2.0 * rand::random::<f64>() - 1.0;
-                y = 2.0 * rand::random::<f64>() - 1.0;
-                s = x * x + y * y;
-            }
-            let result = mean + std_dev * x * (-2.0 * s.ln() / s).sqrt();
+impl LimitHand {
+    pub fn get_base_points(&self) -> u32 {
+        match self {
+            LimitHand::Mangan => 2000,
+            LimitHand::Haneman => 3000,
+            LimitHand::Baiman => 4000,
+            LimitHand::Sanbaiman => 6000,
+            LimitHand::Yakuman(count) => 8000 * *count as u32,
+        }
+    }
 
-            if result >= min && result <= max {
-                return result;
-            }
+    pub fn get_name(&self) -> String {
+        match self {
+            LimitHand::Mangan => "mangan".to_string(),
+            LimitHand::Haneman => "haneman".to_string(),
+            LimitHand::Baiman => "baiman".to_string(),
+            LimitHand::Sanbaiman => "sanbaiman".to_string(),
+            LimitHand::Yakuman(1) => "yakuman".to_string(),
+            LimitHand::Yakuman(2) => "double yakuman".to_string(),
+            LimitHand::Yakuman(3) => "triple yakuman".to_string(),
+            LimitHand::Yakuman(count) => format!("{}x yakuman", count),
         }
+    }
 
-        min
+    pub fn from_name(name: &str) -> Option<LimitHand> {
+        match name.trim().to_lowercase().as_str() {
+            "mangan" | "満貫" => Some(LimitHand::Mangan),
+            "haneman" | "跳満" => Some(LimitHand::Haneman),
+            "baiman" | "倍満" => Some(LimitHand::Baiman),
+            "sanbaiman" | "三倍満" => Some(LimitHand::Sanbaiman),
+            "yakuman" | "役満" => Some(LimitHand::Yakuman(1)),
+            "double yakuman" | "ダブル役満" => Some(LimitHand::Yakuman(2)),
+            "triple yakuman" | "トリプル役満" => Some(LimitHand::Yakuman(3)),
+            _ => None,
+        }
     }
+}
 
-    pub fn generate_winning_hand(settings: ScoringSettings) -> HandScoreData {
-        let is_ron = rand::random::<bool>();
-        let is_chiitoi = rand::random::<f32>() < 0.0252;
+// hands generated from a seed that can be shared to get the same hands
+#[derive(Debug, Clone)]
+pub struct SeededHands {
+    pub seed: u64,
+    rng: StdRng,
+}
 
-        let min_han = if is_chiitoi { 2.0 } else { 1.0 };
-        let max_han = if settings.only_less_than_5_han { 5.0 } else { 20.0 };
+impl SeededHands {
+    pub fn new(seed: u64) -> SeededHands {
+        SeededHands {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
 
-        // bell curve for han with mean 2 and standard deviation 3
-        let han = HandScoreData::generate_gaussian(2.0, 3.0, min_han, max_han) as u8;
+    pub fn generate_next_hand(&mut self, settings: ScoringSettings) -> HandScoreData {
+        HandScoreData::generate_winning_hand(&mut self.rng, settings)
+    }
+}
 
-        let (min_fu, max_fu) = if is_chiitoi {
-            (25.0, 25.0)
+impl HandScoreData {
+    fn generate_hand_candidate<R: Rng>(
+        rng: &mut R,
+        settings: ScoringSettings,
+        distribution: &GenerationDistribution,
+    ) -> Option<HandScoreData> {
+        let filter = settings.generation_filter;
+        let is_ron = filter
+            .ron
+            .unwrap_or_else(|| rng.gen_bool(distribution.ron_chance));
+        let is_dealer = filter
+            .dealer
+            .unwrap_or_else(|| rng.gen_bool(distribution.dealer_chance));
+        let is_chiitoi = rng.gen_bool(distribution.chiitoitsu_chance);
+
+        // legacy chiitoitsu is 50 fu 1 han, modern is 25 fu 2 han
+        let chiitoi_fu = if settings.use_legacy_chiitoitsu {
+            50
         } else {
-            let min = if han == 1 || is_ron {
-                30.0
-            } else {
-                20.0
-            };
-            let max = if is_ron { 110.0 } else { 100.0 };
-            (min, max)
+            25
+        };
+        let min_han = if is_chiitoi && !settings.use_legacy_chiitoitsu {
+            2
+        } else {
+            1
         };
 
-        // bell curve for fu with mean 32 and standard deviation 10
-        let fu_not_rounded = Self::generate_gaussian(32.0, 10.0, min_fu, max_fu) as u8;
-        let fu = if min_fu == 25.0 {
-            25
+        let han = distribution.choose_han(
+            rng,
+            is_dealer,
+            is_ron,
+            min_han.max(filter.min_han),
+            filter.max_han,
+        )?;
+
+        let fu = if is_chiitoi {
+            chiitoi_fu
         } else {
-            // round up to the nearest 10
-            (fu_not_rounded + 5) / 10 * 10
+            let min_fu: u8 = if han == 1 || is_ron { 30 } else { 20 };
+            let max_fu: u8 = if is_ron { 110 } else { 100 };
+            distribution.choose_fu(
+                rng,
+                is_ron,
+                min_fu.max(filter.min_fu),
+                max_fu.min(filter.max_fu),
+            )?
         };
 
-        // bell curve for honba with mean 0 and standard deviation 1.5
         let honba = if settings.use_honba {
-            Self::generate_gaussian(0.0, 1.5, 0.0, 12.0) as u8
+            distribution.choose_honba(rng, filter.min_honba, filter.max_honba)?
         } else {
             0
         };
 
-        let mut score = HandScoreData {
+        Some(HandScoreData {
             han,
             fu,
             honba,
-            ron: if rand::random::<bool>() { true } else { false },
-            is_dealer: if rand::random::<i8>() % 4 == 0 {
-                true
-            } else {
-                false
-            },
-        };
+            ron: is_ron,
+            is_dealer,
+        })
+    }
 
-        score
+    pub fn generate_winning_hand<R: Rng>(rng: &mut R, settings: ScoringSettings) -> HandScoreData {
+        Self::generate_winning_hand_with_distribution(rng, settings, get_generation_distribution())
     }
 
-    pub fn calculate_totals(&self, settings: ScoringSettings) -> HandScoreTotals {
-        // 0 is 5 han, max is 11 han
-        const LIMITS: [u16; 7] = [2000, 3000, 3000, 4000, 4000, 4000, 6000];
+    pub fn generate_winning_hand_with_distribution<R: Rng>(
+        rng: &mut R,
+        settings: ScoringSettings,
+        distribution: &GenerationDistribution,
+    ) -> HandScoreData {
+        // the candidates already follow the filter where possible,
+        // the rest of the combinations, like 20 fu with 1 han, are regenerated
+        for _ in 0..MAX_GENERATION_ATTEMPTS {
+            let candidate = Self::generate_hand_candidate(rng, settings, distribution);
+            if let Some(hand) = candidate {
+                if settings
+                    .generation_filter
+                    .matches(&hand, settings.use_honba)
+                {
+                    return hand;
+                }
+            }
+        }
 
-        // 0 is 5 han, max is 13 han
-        const LIMITS_KAZOE: [u16; 9] = [2000, 3000, 3000, 4000, 4000, 4000, 6000, 6000, 8000];
+        // the filter is checked when it is set, so this only happens with a broken distribution
+        log::error!("Failed to generate a hand matching the filter");
+        HandScoreData {
+            han: 1,
+            fu: 30,
+            honba: 0,
+            ron: true,
+            is_dealer: false,
+        }
+    }
 
-        let mut base: u16 = 0;
-        if self.han > 5 {
-            // limit hands
-            let index = (self.han - 5) as usize;
+    // a filter can be too narrow to generate any possible hand, e.g. 20 fu ron
+    pub fn can_generate_hands(settings: ScoringSettings) -> bool {
+        let mut rng = StdRng::seed_from_u64(0);
+        (0..MAX_GENERATION_ATTEMPTS).any(|_| {
+            Self::generate_hand_candidate(&mut rng, settings, get_generation_distribution())
+                .is_some_and(|hand| {
+                    settings
+                        .generation_filter
+                        .matches(&hand, settings.use_honba)
+                })
+        })
+    }
 
-            if settings.use_kazoe_yakuman {
-                if index >= LIMITS_KAZOE.len() {
-                    base = LIMITS_KAZOE[LIMITS_KAZOE.len() - 1];
+    pub fn get_limit(&self, settings: ScoringSettings) -> Option<LimitHand> {
+        match self.han {
+            0..=4 => {
+                // 4 han 30 fu or 3 han 60 fu is mangan when kiriage mangan is on
+                let is_kiriage = self.han == 4 && self.fu == 30 || self.han == 3 && self.fu == 60;
+                if self.fu as u32 * 2u32.pow(2 + self.han as u32) >= 2000
+                    || settings.use_kiriage_mangan && is_kiriage
+                {
+                    Some(LimitHand::Mangan)
                 } else {
-                    base = LIMITS_KAZOE[index];
-                }
-            } else {
-                if index >= LIMITS.len() {
-                    base = LIMITS[LIMITS.len() - 1];
-                } else {
-                    base = LIMITS[index];
+                    None
                 }
             }
-        } else if settings.use_kiriage_mangan
-            && (self.han == 4 && self.fu == 30 || self.han == 3 && self.fu == 60)
-        {
-            // 4 han 30 fu or 3 han 60 fu is mangan when kiriage mangan is on
-            base = 2000;
-        } else {
-            // normal hands
-            base = std::cmp::min(self.fu as u16 * 2u16.pow(2 + self.han as u32), 2000);
+            5 => Some(LimitHand::Mangan),
+            6..=7 => Some(LimitHand::Haneman),
+            8..=10 => Some(LimitHand::Baiman),
+            11..=12 => Some(LimitHand::Sanbaiman),
+            _ => match settings.kazoe_yakuman {
+                KazoeYakumanRule::Yakuman => Some(LimitHand::Yakuman(1)),
+                KazoeYakumanRule::Sanbaiman => Some(LimitHand::Sanbaiman),
+                KazoeYakumanRule::MultipleYakuman => Some(LimitHand::Yakuman(self.han / 13)),
+            },
         }
+    }
 
+    pub fn calculate_totals(&self, settings: ScoringSettings) -> HandScoreTotals {
+        let limit = self.get_limit(settings);
+
+        let base = match limit {
+            Some(limit) => limit.get_base_points(),
+            // normal hands
+            None => self.fu as u32 * 2u32.pow(2 + self.han as u32),
+        };
+
+        self.calculate_totals_from_base(base, limit)
+    }
+
+    // what the hand would pay with the same seat, win type and honba if it was the given limit
+    pub fn calculate_totals_for_limit(&self, limit: LimitHand) -> HandScoreTotals {
+        self.calculate_totals_from_base(limit.get_base_points(), Some(limit))
+    }
+
+    fn calculate_totals_from_base(&self, base: u32, limit: Option<LimitHand>) -> HandScoreTotals {
         let mut totals = HandScoreTotals {
             dealer: 0,
             others: 0,
+            limit,
         };
 
         if self.ron {
@@ -153,27 +311,221 @@ impl HandScoreData {
             } else {
                 totals.others = base * 4;
             }
+        } else if self.is_dealer {
+            totals.others = base * 2;
         } else {
-            if self.is_dealer {
-                totals.others = base * 2;
-            } else {
-                totals.dealer = base * 2;
-                totals.others = base;
-            }
+            totals.dealer = base * 2;
+            totals.others = base;
         }
 
         // round totals up to the next 100
-        totals.dealer = (totals.dealer + 99) / 100 * 100;
-        totals.others = (totals.others + 99) / 100 * 100;
+        totals.dealer = totals.dealer.div_ceil(100) * 100;
+        totals.others = totals.others.div_ceil(100) * 100;
 
         // add honba
         totals.dealer += if totals.dealer != 0 {
-            self.honba as u16 * 100
+            self.honba as u32 * 100
         } else {
             0
         };
-        totals.others += self.honba as u16 * if self.ron { 300 } else { 100 };
+        totals.others += self.honba as u32 * if self.ron { 300 } else { 100 };
 
         totals
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generation_distribution::get_default_generation_distribution;
+    use crate::generation_filter::parse_generation_filter;
+    use crate::user_settings::get_default_settings;
+
+    fn hand(han: u8, fu: u8, ron: bool, is_dealer: bool) -> HandScoreData {
+        HandScoreData {
+            han,
+            fu,
+            honba: 0,
+            ron,
+            is_dealer,
+        }
+    }
+
+    #[test]
+    fn test_limit_hands_are_named_by_han() {
+        let settings = get_default_settings().scoring_settings;
+
+        assert_eq!(hand(3, 40, true, false).get_limit(settings), None);
+        assert_eq!(
+            hand(4, 40, true, false).get_limit(settings),
+            Some(LimitHand::Mangan)
+        );
+        assert_eq!(
+            hand(7, 30, true, false).get_limit(settings),
+            Some(LimitHand::Haneman)
+        );
+        assert_eq!(
+            hand(10, 30, true, false).get_limit(settings),
+            Some(LimitHand::Baiman)
+        );
+        assert_eq!(
+            hand(12, 30, true, false).get_limit(settings),
+            Some(LimitHand::Sanbaiman)
+        );
+    }
+
+    #[test]
+    fn test_kazoe_yakuman_rule_changes_13_han_payment() {
+        let mut settings = get_default_settings().scoring_settings;
+
+        settings.kazoe_yakuman = KazoeYakumanRule::Yakuman;
+        assert_eq!(
+            hand(13, 30, true, true).calculate_totals(settings).others,
+            48000
+        );
+        settings.kazoe_yakuman = KazoeYakumanRule::Sanbaiman;
+        assert_eq!(
+            hand(13, 30, true, true).calculate_totals(settings).others,
+            36000
+        );
+        settings.kazoe_yakuman = KazoeYakumanRule::MultipleYakuman;
+        assert_eq!(
+            hand(26, 30, true, true).calculate_totals(settings).others,
+            96000
+        );
+    }
+
+    fn generate_hands(seed: u64, count: usize, settings: ScoringSettings) -> Vec<HandScoreData> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..count)
+            .map(|_| HandScoreData::generate_winning_hand(&mut rng, settings))
+            .collect()
+    }
+
+    #[test]
+    fn test_generation_is_reproducible_with_the_same_seed() {
+        let settings = get_default_settings().scoring_settings;
+        assert_eq!(
+            generate_hands(42, 100, settings),
+            generate_hands(42, 100, settings)
+        );
+        assert_ne!(
+            generate_hands(42, 100, settings),
+            generate_hands(43, 100, settings)
+        );
+
+        let mut seeded_hands = SeededHands::new(42);
+        let replayed_hands: Vec<HandScoreData> = (0..100)
+            .map(|_| seeded_hands.generate_next_hand(settings))
+            .collect();
+        assert_eq!(replayed_hands, generate_hands(42, 100, settings));
+    }
+
+    #[test]
+    fn test_generated_hands_are_possible() {
+        let settings = get_default_settings().scoring_settings;
+        for hand in generate_hands(1, 10000, settings) {
+            assert!((1..=20).contains(&hand.han));
+            assert!(
+                [20, 25, 30, 40, 50, 60, 70, 80, 90, 100, 110].contains(&hand.fu),
+                "{:?}",
+                hand
+            );
+            // pinfu tsumo is the only 20 fu hand, and it always has at least 2 han
+            if hand.fu == 20 {
+                assert!(!hand.ron && hand.han >= 2, "{:?}", hand);
+            }
+            if hand.fu == 25 {
+                assert!(hand.han >= 2, "{:?}", hand);
+            }
+            if hand.fu == 110 {
+                assert!(hand.ron, "{:?}", hand);
+            }
+        }
+
+        let mut settings = settings;
+        settings.generation_filter.max_han = 4;
+        settings.use_honba = false;
+        for hand in generate_hands(2, 10000, settings) {
+            assert!(hand.han <= 4);
+            assert_eq!(hand.honba, 0);
+        }
+    }
+
+    #[test]
+    fn test_generated_hands_follow_the_filter() {
+        let mut settings = get_default_settings().scoring_settings;
+        settings.use_honba = true;
+        settings.generation_filter =
+            parse_generation_filter("non-dealer tsumo 30-50 fu 3-4 han honba 1-2").unwrap();
+        assert!(HandScoreData::can_generate_hands(settings));
+        for hand in generate_hands(4, 1000, settings) {
+            assert!(!hand.is_dealer && !hand.ron, "{:?}", hand);
+            assert!((30..=50).contains(&hand.fu), "{:?}", hand);
+            assert!((3..=4).contains(&hand.han), "{:?}", hand);
+            assert!((1..=2).contains(&hand.honba), "{:?}", hand);
+        }
+
+        // chiitoitsu is the only 25 fu hand
+        settings.generation_filter = parse_generation_filter("25 fu").unwrap();
+        for hand in generate_hands(5, 100, settings) {
+            assert_eq!(hand.fu, 25);
+        }
+
+        settings.generation_filter = parse_generation_filter("20 fu ron").unwrap();
+        assert!(!HandScoreData::can_generate_hands(settings));
+    }
+
+    #[test]
+    fn test_generated_hands_distribution() {
+        let mut settings = get_default_settings().scoring_settings;
+        settings.use_honba = true;
+        let hands = generate_hands(3, 10000, settings);
+        let share = |filter: &dyn Fn(&HandScoreData) -> bool| {
+            hands.iter().filter(|hand| filter(hand)).count() as f64 / hands.len() as f64
+        };
+
+        assert!((0.5..0.6).contains(&share(&|hand| hand.ron)));
+        assert!((0.22..0.32).contains(&share(&|hand| hand.is_dealer)));
+        assert!((0.01..0.05).contains(&share(&|hand| hand.fu == 25)));
+        assert!((0.7..0.9).contains(&share(&|hand| hand.han <= 4)));
+        assert!((0.6..0.9).contains(&share(&|hand| hand.fu == 30 || hand.fu == 40)));
+        assert!((0.0..0.02).contains(&share(&|hand| hand.han >= 11)));
+        assert!((0.6..0.8).contains(&share(&|hand| hand.honba == 0)));
+        assert!((0.0..0.1).contains(&share(&|hand| hand.honba >= 3)));
+    }
+
+    #[test]
+    fn test_generation_uses_the_given_distribution() {
+        let settings = get_default_settings().scoring_settings;
+        let mut distribution = get_default_generation_distribution();
+        distribution.han_weights.non_dealer_ron = vec![0.0, 0.0, 1.0];
+        distribution.han_weights.dealer_ron = vec![0.0, 0.0, 0.0, 0.0, 1.0];
+        distribution.ron_chance = 1.0;
+        distribution.chiitoitsu_chance = 0.0;
+
+        let mut rng = StdRng::seed_from_u64(6);
+        for _ in 0..100 {
+            let hand = HandScoreData::generate_winning_hand_with_distribution(
+                &mut rng,
+                settings,
+                &distribution,
+            );
+            assert_eq!(hand.han, if hand.is_dealer { 5 } else { 3 });
+            assert!(hand.ron);
+        }
+    }
+
+    #[test]
+    fn test_limit_name_resolves_to_the_payment_for_the_seat_and_win_type() {
+        let mut non_dealer_tsumo = hand(2, 30, false, false);
+        non_dealer_tsumo.honba = 2;
+        let totals =
+            non_dealer_tsumo.calculate_totals_for_limit(LimitHand::from_name("満貫").unwrap());
+        assert_eq!((totals.others, totals.dealer), (2200, 4200));
+
+        let totals = hand(2, 30, true, true)
+            .calculate_totals_for_limit(LimitHand::from_name("Haneman").unwrap());
+        assert_eq!((totals.others, totals.dealer), (18000, 0));
+    }
+}
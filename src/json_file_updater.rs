@@ -73,6 +73,7 @@ use serde_json::Value as JsonValue;
 ///        c. document what happened for the future reference
 ///       This way you can limit the damage and make sure to merge the update paths as soon as
 ///       possible
+#[allow(clippy::doc_overindented_list_items)]
 pub struct JsonFileUpdater {
     latest_version: String,
     version_field_name: String,
@@ -123,6 +124,7 @@ impl JsonFileUpdater {
         self.add_update_function(version_to, |_| {});
     }
 
+    #[allow(clippy::needless_return)]
     pub fn update_json(&self, json: &mut JsonValue) -> UpdateResult {
         if self.patchers.is_empty() {
             return UpdateResult::NoUpdateNeeded;
@@ -1,13 +1,34 @@
 // Copyright (C) Pavel Grebnev 2024
 // Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
 
+mod achievements;
+mod adaptive_difficulty;
+mod answer_parser;
+mod daily_challenge;
+mod fu_calculator;
+mod fu_quiz;
+mod generation_distribution;
+mod generation_filter;
+mod group_game;
 mod hand_score;
 mod json_file_updater;
+mod mistake_classifier;
+mod multiple_choice;
+mod proficiency;
+mod reverse_quiz;
+mod review_schedule;
+mod session;
+mod speed_run;
 mod telegram_bot;
+mod tile_hand;
+mod tiles;
 mod translations;
 mod user_settings;
 mod user_state;
 mod user_state_updaters;
+mod user_statistics;
+mod yaku;
+mod yaku_quiz;
 
 extern crate rand;
 
@@ -0,0 +1,350 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::hand_score::{HandScoreData, HandScoreTotals, LimitHand, ScoringSettings};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MistakeType {
+    ForgotRoundingUp,
+    RoundedBasePoints,
+    WrongSeatMultiplier,
+    ForgotHonba,
+    WrongHonbaAmount,
+    MissedKiriageMangan,
+    AppliedKiriageMangan,
+    MissedManganCap,
+    WrongLimit,
+    WrongFu,
+    WrongHan,
+}
+
+// ordered from the most specific to the most generic mistakes
+pub const ALL_MISTAKE_TYPES: [MistakeType; 11] = [
+    MistakeType::MissedKiriageMangan,
+    MistakeType::AppliedKiriageMangan,
+    MistakeType::MissedManganCap,
+    MistakeType::ForgotHonba,
+    MistakeType::WrongHonbaAmount,
+    MistakeType::ForgotRoundingUp,
+    MistakeType::RoundedBasePoints,
+    MistakeType::WrongSeatMultiplier,
+    MistakeType::WrongLimit,
+    MistakeType::WrongFu,
+    MistakeType::WrongHan,
+];
+
+fn get_base_points_without_limit(hand: &HandScoreData) -> u32 {
+    hand.fu as u32 * 2u32.pow(2 + hand.han as u32)
+}
+
+// splits the base points between payers the same way as the real calculation,
+// but allows to skip rounding and to pay a different amount per honba
+fn split_payments(
+    hand: &HandScoreData,
+    base: u32,
+    round_up: bool,
+    honba_payment_per_payer: u32,
+) -> HandScoreTotals {
+    let round = |value: u32| {
+        if round_up {
+            value.div_ceil(100) * 100
+        } else {
+            value
+        }
+    };
+
+    let (others, dealer) = if hand.ron {
+        (round(base * if hand.is_dealer { 6 } else { 4 }), 0)
+    } else if hand.is_dealer {
+        (round(base * 2), 0)
+    } else {
+        (round(base), round(base * 2))
+    };
+
+    let honba = hand.honba as u32 * honba_payment_per_payer;
+    HandScoreTotals {
+        dealer: if dealer != 0 { dealer + honba } else { 0 },
+        others: others + honba,
+        limit: None,
+    }
+}
+
+fn get_correct_honba_payment(hand: &HandScoreData) -> u32 {
+    if hand.ron {
+        300
+    } else {
+        100
+    }
+}
+
+fn is_kiriage_hand(hand: &HandScoreData) -> bool {
+    hand.han == 4 && hand.fu == 30 || hand.han == 3 && hand.fu == 60
+}
+
+// returns all different scores that a player making the given mistake could come up with
+pub fn get_mistaken_totals(
+    hand: &HandScoreData,
+    settings: ScoringSettings,
+    mistake: MistakeType,
+) -> Vec<HandScoreTotals> {
+    let is_limit = hand.get_limit(settings).is_some();
+
+    match mistake {
+        MistakeType::ForgotRoundingUp if !is_limit => {
+            vec![split_payments(
+                hand,
+                get_base_points_without_limit(hand),
+                false,
+                get_correct_honba_payment(hand),
+            )]
+        }
+        MistakeType::RoundedBasePoints if !is_limit => {
+            vec![split_payments(
+                hand,
+                get_base_points_without_limit(hand).div_ceil(100) * 100,
+                true,
+                get_correct_honba_payment(hand),
+            )]
+        }
+        MistakeType::WrongSeatMultiplier => {
+            let mut mistaken_hand = *hand;
+            mistaken_hand.is_dealer = !hand.is_dealer;
+            vec![mistaken_hand.calculate_totals(settings)]
+        }
+        MistakeType::ForgotHonba if hand.honba > 0 => {
+            let mut mistaken_hand = *hand;
+            mistaken_hand.honba = 0;
+            vec![mistaken_hand.calculate_totals(settings)]
+        }
+        MistakeType::WrongHonbaAmount if hand.honba > 0 => {
+            let mut hand_without_honba = *hand;
+            hand_without_honba.honba = 0;
+            let totals_without_honba = hand_without_honba.calculate_totals(settings);
+            let correct_honba_payment = get_correct_honba_payment(hand);
+
+            [100, 300]
+                .iter()
+                .filter(|payment| **payment != correct_honba_payment)
+                .map(|payment| {
+                    let honba = hand.honba as u32 * payment;
+                    HandScoreTotals {
+                        dealer: if totals_without_honba.dealer != 0 {
+                            totals_without_honba.dealer + honba
+                        } else {
+                            0
+                        },
+                        others: totals_without_honba.others + honba,
+                        limit: None,
+                    }
+                })
+                .collect()
+        }
+        MistakeType::MissedKiriageMangan
+            if settings.use_kiriage_mangan && is_kiriage_hand(hand) =>
+        {
+            let mut mistaken_settings = settings;
+            mistaken_settings.use_kiriage_mangan = false;
+            vec![hand.calculate_totals(mistaken_settings)]
+        }
+        MistakeType::AppliedKiriageMangan
+            if !settings.use_kiriage_mangan && is_kiriage_hand(hand) =>
+        {
+            let mut mistaken_settings = settings;
+            mistaken_settings.use_kiriage_mangan = true;
+            vec![hand.calculate_totals(mistaken_settings)]
+        }
+        MistakeType::MissedManganCap if hand.han <= 5 && is_limit && !is_kiriage_hand(hand) => {
+            vec![split_payments(
+                hand,
+                get_base_points_without_limit(hand),
+                true,
+                get_correct_honba_payment(hand),
+            )]
+        }
+        MistakeType::WrongLimit if hand.han >= 5 => {
+            let correct_limit = hand.get_limit(settings);
+            [
+                LimitHand::Mangan,
+                LimitHand::Haneman,
+                LimitHand::Baiman,
+                LimitHand::Sanbaiman,
+                LimitHand::Yakuman(1),
+            ]
+            .iter()
+            .filter(|limit| Some(**limit) != correct_limit)
+            .map(|limit| hand.calculate_totals_for_limit(*limit))
+            .collect()
+        }
+        MistakeType::WrongFu if !is_limit && hand.fu != 25 => [hand.fu - 10, hand.fu + 10]
+            .iter()
+            .filter(|fu| **fu >= 20)
+            .map(|fu| {
+                let mut mistaken_hand = *hand;
+                mistaken_hand.fu = *fu;
+                mistaken_hand.calculate_totals(settings)
+            })
+            .collect(),
+        MistakeType::WrongHan if hand.han <= 5 => [hand.han - 1, hand.han + 1]
+            .iter()
+            .filter(|han| **han >= 1)
+            .map(|han| {
+                let mut mistaken_hand = *hand;
+                mistaken_hand.han = *han;
+                mistaken_hand.calculate_totals(settings)
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn is_matching_answer(
+    answered_first: u32,
+    answered_second: u32,
+    totals: &HandScoreTotals,
+    allow_swapped: bool,
+) -> bool {
+    answered_first == totals.others && answered_second == totals.dealer
+        || allow_swapped && answered_first == totals.dealer && answered_second == totals.others
+}
+
+// tries to find what mistake could lead to the given wrong answer
+pub fn classify_mistake(
+    hand: &HandScoreData,
+    settings: ScoringSettings,
+    answered_first: u32,
+    answered_second: u32,
+    allow_swapped: bool,
+) -> Option<MistakeType> {
+    let correct_totals = hand.calculate_totals(settings);
+    if is_matching_answer(
+        answered_first,
+        answered_second,
+        &correct_totals,
+        allow_swapped,
+    ) {
+        return None;
+    }
+
+    ALL_MISTAKE_TYPES.iter().copied().find(|mistake| {
+        get_mistaken_totals(hand, settings, *mistake)
+            .iter()
+            .any(|totals| {
+                is_matching_answer(answered_first, answered_second, totals, allow_swapped)
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user_settings::get_default_settings;
+
+    fn hand(han: u8, fu: u8, honba: u8, ron: bool, is_dealer: bool) -> HandScoreData {
+        HandScoreData {
+            han,
+            fu,
+            honba,
+            ron,
+            is_dealer,
+        }
+    }
+
+    #[test]
+    fn test_classify_rounding_mistakes() {
+        let settings = get_default_settings().scoring_settings;
+
+        // 1 han 30 fu non-dealer ron is 960 before rounding
+        assert_eq!(
+            classify_mistake(&hand(1, 30, 0, true, false), settings, 960, 0, true),
+            Some(MistakeType::ForgotRoundingUp)
+        );
+        // 1 han 30 fu base points are 240, rounding them to 300 gives 1200
+        assert_eq!(
+            classify_mistake(&hand(1, 30, 0, true, false), settings, 1200, 0, true),
+            Some(MistakeType::RoundedBasePoints)
+        );
+    }
+
+    #[test]
+    fn test_classify_seat_and_honba_mistakes() {
+        let settings = get_default_settings().scoring_settings;
+
+        // 3 han 30 fu non-dealer ron is 3900, dealer ron would be 5800
+        assert_eq!(
+            classify_mistake(&hand(3, 30, 0, true, false), settings, 5800, 0, true),
+            Some(MistakeType::WrongSeatMultiplier)
+        );
+        // 3900 + 2 honba is 4500
+        assert_eq!(
+            classify_mistake(&hand(3, 30, 2, true, false), settings, 3900, 0, true),
+            Some(MistakeType::ForgotHonba)
+        );
+        assert_eq!(
+            classify_mistake(&hand(3, 30, 2, true, false), settings, 4100, 0, true),
+            Some(MistakeType::WrongHonbaAmount)
+        );
+        // 3 han 30 fu non-dealer tsumo is 1000/2000, with 2 honba each payment is 200 more
+        assert_eq!(
+            classify_mistake(&hand(3, 30, 2, false, false), settings, 1200, 2200, true),
+            None
+        );
+        assert_eq!(
+            classify_mistake(&hand(3, 30, 2, false, false), settings, 1600, 2600, true),
+            Some(MistakeType::WrongHonbaAmount)
+        );
+        // dealer tsumo is 2000 all, the mistake adds 300 per honba to each payment
+        let mistaken_totals = get_mistaken_totals(
+            &hand(3, 30, 2, false, true),
+            settings,
+            MistakeType::WrongHonbaAmount,
+        );
+        assert_eq!(mistaken_totals.len(), 1);
+        assert_eq!(mistaken_totals[0].others, 2600);
+        assert_eq!(mistaken_totals[0].dealer, 0);
+    }
+
+    #[test]
+    fn test_classify_mangan_mistakes() {
+        let mut settings = get_default_settings().scoring_settings;
+
+        // 4 han 40 fu is mangan, not 10300 (2560 * 4 rounded up)
+        assert_eq!(
+            classify_mistake(&hand(4, 40, 0, true, false), settings, 10300, 0, true),
+            Some(MistakeType::MissedManganCap)
+        );
+
+        settings.use_kiriage_mangan = true;
+        assert_eq!(
+            classify_mistake(&hand(4, 30, 0, true, false), settings, 7700, 0, true),
+            Some(MistakeType::MissedKiriageMangan)
+        );
+
+        settings.use_kiriage_mangan = false;
+        assert_eq!(
+            classify_mistake(&hand(4, 30, 0, false, false), settings, 2000, 4000, true),
+            Some(MistakeType::AppliedKiriageMangan)
+        );
+
+        // 7 han is haneman, 16000 is baiman
+        assert_eq!(
+            classify_mistake(&hand(7, 30, 0, true, false), settings, 16000, 0, true),
+            Some(MistakeType::WrongLimit)
+        );
+    }
+
+    #[test]
+    fn test_unknown_mistake_is_not_classified() {
+        let settings = get_default_settings().scoring_settings;
+
+        assert_eq!(
+            classify_mistake(&hand(2, 30, 0, true, false), settings, 12300, 0, true),
+            None
+        );
+        // the correct answer is not a mistake
+        assert_eq!(
+            classify_mistake(&hand(2, 30, 0, true, false), settings, 2000, 0, true),
+            None
+        );
+    }
+}
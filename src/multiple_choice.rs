@@ -0,0 +1,105 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::hand_score::{HandScoreData, HandScoreTotals, ScoringSettings};
+use crate::mistake_classifier::{get_mistaken_totals, ALL_MISTAKE_TYPES};
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+pub const CHOICES_COUNT: usize = 4;
+
+// scores of other han and fu for the same seat and win type, the closest to the correct score first
+fn get_fallback_totals(hand: &HandScoreData, settings: ScoringSettings) -> Vec<HandScoreTotals> {
+    let correct_totals = hand.calculate_totals(settings);
+
+    let mut totals: Vec<HandScoreTotals> = (1..=13)
+        .flat_map(|han| {
+            [30, 40, 50, 60, 70].iter().map(move |fu| {
+                let mut other_hand = *hand;
+                other_hand.han = han;
+                other_hand.fu = *fu;
+                other_hand.calculate_totals(settings)
+            })
+        })
+        .collect();
+    totals.sort_by_key(|totals| totals.others.abs_diff(correct_totals.others));
+    totals
+}
+
+// returns the correct score and distractors based on plausible mistakes in random order
+pub fn generate_choices<R: Rng>(
+    rng: &mut R,
+    hand: &HandScoreData,
+    settings: ScoringSettings,
+) -> Vec<HandScoreTotals> {
+    let correct_totals = hand.calculate_totals(settings);
+
+    let mut mistaken_totals: Vec<HandScoreTotals> = ALL_MISTAKE_TYPES
+        .iter()
+        .flat_map(|mistake| get_mistaken_totals(hand, settings, *mistake))
+        .collect();
+    mistaken_totals.shuffle(rng);
+
+    let mut choices = vec![correct_totals];
+    for totals in mistaken_totals
+        .into_iter()
+        .chain(get_fallback_totals(hand, settings))
+    {
+        if choices.len() == CHOICES_COUNT {
+            break;
+        }
+        // all choices should be written in the same format as the correct answer
+        let has_same_format = (totals.dealer == 0) == (correct_totals.dealer == 0);
+        if has_same_format
+            && totals.others > 0
+            && !choices.iter().any(|choice| choice.is_same_payment(&totals))
+        {
+            choices.push(totals);
+        }
+    }
+
+    choices.shuffle(rng);
+    choices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user_settings::get_default_settings;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_choices_are_unique_and_contain_the_correct_score() {
+        let settings = get_default_settings().scoring_settings;
+        let mut rng = StdRng::seed_from_u64(1);
+
+        for han in 1..=13 {
+            for fu in [20, 25, 30, 40, 70, 110] {
+                for (ron, is_dealer) in [(true, true), (true, false), (false, true), (false, false)]
+                {
+                    let hand = HandScoreData {
+                        han,
+                        fu,
+                        honba: 1,
+                        ron,
+                        is_dealer,
+                    };
+                    let correct_totals = hand.calculate_totals(settings);
+                    let choices = generate_choices(&mut rng, &hand, settings);
+
+                    assert_eq!(choices.len(), CHOICES_COUNT);
+                    assert!(choices
+                        .iter()
+                        .any(|choice| choice.is_same_payment(&correct_totals)));
+                    for (index, choice) in choices.iter().enumerate() {
+                        assert_eq!(choice.dealer == 0, correct_totals.dealer == 0);
+                        assert!(!choices[index + 1..]
+                            .iter()
+                            .any(|other| choice.is_same_payment(other)));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,259 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::hand_score::LimitHand;
+use crate::user_statistics::UserStatistics;
+
+// the requirements are checked on this many last answers
+pub const RECENT_ANSWERS_COUNT: usize = 100;
+pub const LEVEL_TEST_QUESTIONS_COUNT: u32 = 20;
+const LIMIT_HAND_KINDS_COUNT: usize = 5;
+
+pub struct ProficiencyLevel {
+    pub name: &'static str,
+    min_answers_count: usize,
+    min_accuracy_percent: u32,
+    max_average_seconds: Option<u32>,
+    // kinds of limit hands answered correctly at least once, all yakuman count as one kind
+    min_limit_kinds_count: usize,
+    // correct answers for hands with honba among the recent answers
+    min_honba_answers_count: usize,
+}
+
+// the levels from the lowest to the highest, saved statistics keep the index of the level
+pub const PROFICIENCY_LEVELS: [ProficiencyLevel; 11] = [
+    level("10 kyu", 20, 50, None, 0, 0),
+    level("9 kyu", 30, 60, None, 0, 0),
+    level("8 kyu", 50, 70, None, 0, 0),
+    level("7 kyu", 50, 75, Some(60), 0, 0),
+    level("6 kyu", 100, 80, Some(45), 1, 0),
+    level("5 kyu", 100, 85, Some(30), 2, 5),
+    level("4 kyu", 100, 88, Some(25), 3, 10),
+    level("3 kyu", 100, 90, Some(20), 4, 10),
+    level("2 kyu", 100, 93, Some(15), 5, 15),
+    level("1 kyu", 100, 95, Some(12), 5, 20),
+    level("1 dan", 100, 98, Some(10), 5, 20),
+];
+
+const fn level(
+    name: &'static str,
+    min_answers_count: usize,
+    min_accuracy_percent: u32,
+    max_average_seconds: Option<u32>,
+    min_limit_kinds_count: usize,
+    min_honba_answers_count: usize,
+) -> ProficiencyLevel {
+    ProficiencyLevel {
+        name,
+        min_answers_count,
+        min_accuracy_percent,
+        max_average_seconds,
+        min_limit_kinds_count,
+        min_honba_answers_count,
+    }
+}
+
+// what the requirements of the levels are compared with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecentPerformance {
+    pub answers_count: usize,
+    pub accuracy_percent: u32,
+    pub average_seconds: Option<u32>,
+    pub limit_kinds_count: usize,
+    pub honba_answers_count: usize,
+}
+
+pub fn get_recent_performance(statistics: &UserStatistics) -> RecentPerformance {
+    let recent_answers = &statistics.answers[statistics
+        .answers
+        .len()
+        .saturating_sub(RECENT_ANSWERS_COUNT)..];
+    let correct_count = recent_answers
+        .iter()
+        .filter(|record| record.is_correct)
+        .count();
+    let timed_seconds: Vec<u32> = recent_answers
+        .iter()
+        .filter_map(|record| record.answer_seconds)
+        .collect();
+
+    let has_limit = |limit: LimitHand| statistics.correct_limits.contains(&limit);
+    let limit_kinds_count = [
+        LimitHand::Mangan,
+        LimitHand::Haneman,
+        LimitHand::Baiman,
+        LimitHand::Sanbaiman,
+    ]
+    .into_iter()
+    .filter(|limit| has_limit(*limit))
+    .count()
+        + statistics
+            .correct_limits
+            .iter()
+            .any(|limit| matches!(limit, LimitHand::Yakuman(_))) as usize;
+
+    RecentPerformance {
+        answers_count: recent_answers.len(),
+        accuracy_percent: (correct_count * 100)
+            .checked_div(recent_answers.len())
+            .unwrap_or(0) as u32,
+        average_seconds: (!timed_seconds.is_empty())
+            .then(|| timed_seconds.iter().sum::<u32>() / timed_seconds.len() as u32),
+        limit_kinds_count,
+        honba_answers_count: recent_answers
+            .iter()
+            .filter(|record| record.is_correct && record.honba > 0)
+            .count(),
+    }
+}
+
+impl ProficiencyLevel {
+    // descriptions of the requirements that are not met yet, empty if the level can be tested
+    pub fn get_missing_requirements(&self, performance: &RecentPerformance) -> Vec<String> {
+        let mut missing = Vec::new();
+        if performance.answers_count < self.min_answers_count {
+            missing.push(format!(
+                "answer at least {} hands (now {})",
+                self.min_answers_count, performance.answers_count
+            ));
+        }
+        if performance.accuracy_percent < self.min_accuracy_percent {
+            missing.push(format!(
+                "{}% accuracy over the last {} hands (now {}%)",
+                self.min_accuracy_percent, RECENT_ANSWERS_COUNT, performance.accuracy_percent
+            ));
+        }
+        if let Some(max_average_seconds) = self.max_average_seconds {
+            if performance
+                .average_seconds
+                .is_none_or(|seconds| seconds > max_average_seconds)
+            {
+                missing.push(format!(
+                    "{} seconds or less per hand on average (now {})",
+                    max_average_seconds,
+                    performance
+                        .average_seconds
+                        .map_or("unknown".to_string(), |seconds| seconds.to_string())
+                ));
+            }
+        }
+        if performance.limit_kinds_count < self.min_limit_kinds_count {
+            missing.push(format!(
+                "correct answers for {} of the {} kinds of limit hands (now {})",
+                self.min_limit_kinds_count, LIMIT_HAND_KINDS_COUNT, performance.limit_kinds_count
+            ));
+        }
+        if performance.honba_answers_count < self.min_honba_answers_count {
+            missing.push(format!(
+                "{} correct answers for hands with honba among the last {} hands (now {}), turn on /toggle_honba to get them",
+                self.min_honba_answers_count, RECENT_ANSWERS_COUNT, performance.honba_answers_count
+            ));
+        }
+        missing
+    }
+
+    pub fn is_honba_tested(&self) -> bool {
+        self.min_honba_answers_count > 0
+    }
+
+    // the test is held to the same accuracy and speed as the practice
+    pub fn is_test_passed(&self, correct_count: u32, answered_count: u32, seconds: i64) -> bool {
+        if answered_count < LEVEL_TEST_QUESTIONS_COUNT {
+            return false;
+        }
+        let is_accurate = correct_count * 100 >= self.min_accuracy_percent * answered_count;
+        let is_fast = self.max_average_seconds.is_none_or(|max_average_seconds| {
+            seconds <= max_average_seconds as i64 * answered_count as i64
+        });
+        is_accurate && is_fast
+    }
+
+    pub fn get_test_requirements_text(&self) -> String {
+        match self.max_average_seconds {
+            Some(max_average_seconds) => format!(
+                "at least {}% correct answers and {} seconds or less per hand on average",
+                self.min_accuracy_percent, max_average_seconds
+            ),
+            None => format!("at least {}% correct answers", self.min_accuracy_percent),
+        }
+    }
+}
+
+// the highest level above the current one which test can be taken
+pub fn get_testable_level_index(
+    statistics: &UserStatistics,
+    performance: &RecentPerformance,
+) -> Option<usize> {
+    let first_index = statistics.proficiency_level.map_or(0, |index| index + 1);
+    (first_index..PROFICIENCY_LEVELS.len()).rev().find(|index| {
+        PROFICIENCY_LEVELS[*index]
+            .get_missing_requirements(performance)
+            .is_empty()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::review_schedule::HandCombination;
+    use crate::user_statistics::{get_default_statistics, AnswerRecord};
+
+    fn record(is_correct: bool, honba: u8, answer_seconds: u32) -> AnswerRecord {
+        AnswerRecord {
+            combination: HandCombination {
+                han: 2,
+                fu: 30,
+                is_dealer: false,
+                ron: true,
+            },
+            honba,
+            is_correct,
+            mistake: None,
+            limit: None,
+            answer_seconds: Some(answer_seconds),
+            time: 0,
+        }
+    }
+
+    #[test]
+    fn test_testable_level_depends_on_recent_answers() {
+        let mut statistics = get_default_statistics();
+        for index in 0..150 {
+            // the first 50 answers are too old to count
+            statistics.record_answer(record(index >= 50 && index % 10 != 0, 0, 20));
+        }
+
+        let performance = get_recent_performance(&statistics);
+        assert_eq!(performance.answers_count, 100);
+        assert_eq!(performance.accuracy_percent, 90);
+        assert_eq!(performance.average_seconds, Some(20));
+
+        // 6 kyu needs a correctly answered limit hand
+        assert_eq!(get_testable_level_index(&statistics, &performance), Some(3));
+        assert_eq!(
+            PROFICIENCY_LEVELS[4]
+                .get_missing_requirements(&performance)
+                .len(),
+            1
+        );
+
+        statistics.correct_limits.push(LimitHand::Mangan);
+        let performance = get_recent_performance(&statistics);
+        assert_eq!(get_testable_level_index(&statistics, &performance), Some(4));
+
+        // levels that were already passed are not tested again
+        statistics.proficiency_level = Some(4);
+        assert_eq!(get_testable_level_index(&statistics, &performance), None);
+    }
+
+    #[test]
+    fn test_level_test_requires_accuracy_and_speed() {
+        let level = &PROFICIENCY_LEVELS[5];
+        let count = LEVEL_TEST_QUESTIONS_COUNT;
+        assert!(level.is_test_passed(count * 85 / 100, count, 30 * count as i64));
+        assert!(!level.is_test_passed(count * 85 / 100 - 1, count, 30 * count as i64));
+        assert!(!level.is_test_passed(count, count, 30 * count as i64 + 1));
+        // stopped before the last question
+        assert!(!level.is_test_passed(count - 1, count - 1, 0));
+    }
+}
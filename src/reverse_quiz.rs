@@ -0,0 +1,365 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::answer_parser::normalize_char;
+use crate::hand_score::{HandScoreData, HandScoreTotals, LimitHand, ScoringSettings};
+
+// the same limit as in the hand generation
+const MAX_HAN: u8 = 20;
+const FU_VALUES: [u8; 11] = [20, 25, 30, 40, 50, 60, 70, 80, 90, 100, 110];
+
+#[derive(Debug, PartialEq)]
+pub enum ReverseAnswer {
+    // fu can be omitted for hands with 5 or more han
+    HanFu(u8, Option<u8>),
+    Limit(LimitHand),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ReverseAnswerParseError {
+    Empty,
+    InvalidText { text: String },
+    MissingHan,
+}
+
+pub fn get_reverse_question_text(hand_score: &HandScoreData, totals: &HandScoreTotals) -> String {
+    let payment = if hand_score.ron {
+        format!("{}", totals.others)
+    } else if hand_score.is_dealer {
+        format!("{} all", totals.others)
+    } else {
+        format!("{}/{}", totals.others, totals.dealer)
+    };
+
+    format!(
+        "{} {} for {}{}",
+        if hand_score.is_dealer {
+            "Dealer"
+        } else {
+            "Non-dealer"
+        },
+        if hand_score.ron { "ron" } else { "tsumo" },
+        payment,
+        if hand_score.honba > 0 {
+            format!(" with {} honba", hand_score.honba)
+        } else {
+            "".to_string()
+        }
+    )
+}
+
+// the combinations that can appear in a real hand, for 5 or more han the fu doesn't matter
+fn is_possible_combination(han: u8, fu: u8, ron: bool, settings: ScoringSettings) -> bool {
+    if han >= 5 {
+        return true;
+    }
+    match fu {
+        // only pinfu tsumo has 20 fu, and pinfu + tsumo is already 2 han
+        20 => !ron && han >= 2,
+        25 => !settings.use_legacy_chiitoitsu && han >= 2,
+        _ => FU_VALUES.contains(&fu),
+    }
+}
+
+// returns (han, fu) combinations that give the same payment, fu is None for 5 or more han
+pub fn get_matching_combinations(
+    hand_score: &HandScoreData,
+    settings: ScoringSettings,
+) -> Vec<(u8, Option<u8>)> {
+    let totals = hand_score.calculate_totals(settings);
+    let mut combinations = Vec::new();
+
+    for han in 1..=MAX_HAN {
+        let fu_values: Vec<Option<u8>> = if han >= 5 {
+            vec![None]
+        } else {
+            FU_VALUES.iter().map(|fu| Some(*fu)).collect()
+        };
+
+        for fu in fu_values {
+            if !is_possible_combination(han, fu.unwrap_or(30), hand_score.ron, settings) {
+                continue;
+            }
+            let mut candidate = *hand_score;
+            candidate.han = han;
+            candidate.fu = fu.unwrap_or(30);
+            if candidate
+                .calculate_totals(settings)
+                .is_same_payment(&totals)
+            {
+                combinations.push((han, fu));
+            }
+        }
+    }
+
+    combinations
+}
+
+pub fn is_correct_reverse_answer(
+    hand_score: &HandScoreData,
+    answer: &ReverseAnswer,
+    settings: ScoringSettings,
+) -> bool {
+    let totals = hand_score.calculate_totals(settings);
+    match answer {
+        ReverseAnswer::HanFu(han, fu) => get_matching_combinations(hand_score, settings)
+            .iter()
+            .any(|(matching_han, matching_fu)| {
+                matching_han == han && (matching_fu.is_none() || matching_fu == fu)
+            }),
+        ReverseAnswer::Limit(limit) => hand_score
+            .calculate_totals_for_limit(*limit)
+            .is_same_payment(&totals),
+    }
+}
+
+// e.g. "3 han 40 fu, 2 han 80 fu" or "6-7 han"
+pub fn format_combinations(combinations: &[(u8, Option<u8>)]) -> String {
+    let mut parts = Vec::new();
+    let mut index = 0;
+    while index < combinations.len() {
+        match combinations[index] {
+            (han, Some(fu)) => {
+                parts.push(format!("{} han {} fu", han, fu));
+                index += 1;
+            }
+            (first_han, None) => {
+                // group consecutive han values that don't need fu
+                let mut last_han = first_han;
+                while let Some((han, None)) = combinations.get(index + 1) {
+                    if *han != last_han + 1 {
+                        break;
+                    }
+                    last_han = *han;
+                    index += 1;
+                }
+                index += 1;
+                parts.push(if last_han == MAX_HAN {
+                    format!("{}+ han", first_han)
+                } else if last_han != first_han {
+                    format!("{}-{} han", first_han, last_han)
+                } else {
+                    format!("{} han", first_han)
+                });
+            }
+        }
+    }
+    parts.join(", ")
+}
+
+// a unit that is given twice makes the answer ambiguous, so it is rejected instead of overwritten
+fn set_answer_unit(
+    target: &mut Option<u8>,
+    number: u8,
+    text: &str,
+) -> Result<(), ReverseAnswerParseError> {
+    if target.replace(number).is_some() {
+        return Err(ReverseAnswerParseError::InvalidText {
+            text: text.to_string(),
+        });
+    }
+    Ok(())
+}
+
+// the numbers without units are read as han first and then as fu
+fn set_unitless_number(
+    han: &mut Option<u8>,
+    fu: &mut Option<u8>,
+    number: u8,
+) -> Result<(), ReverseAnswerParseError> {
+    let target = if han.is_none() { han } else { fu };
+    set_answer_unit(target, number, &number.to_string())
+}
+
+// accepts "3 han 40 fu", "40 fu 3 han", "3/40", "3 40", "5 han" or a limit name
+pub fn parse_reverse_answer(text: &str) -> Result<ReverseAnswer, ReverseAnswerParseError> {
+    let text: String = text
+        .chars()
+        .map(normalize_char)
+        .collect::<String>()
+        .to_lowercase();
+
+    if text.trim().is_empty() {
+        return Err(ReverseAnswerParseError::Empty);
+    }
+
+    if let Some(limit) = LimitHand::from_name(&text) {
+        return Ok(ReverseAnswer::Limit(limit));
+    }
+
+    // separate numbers from units, so "3han40fu" can be read the same way as "3 han 40 fu"
+    let mut spaced_text = String::new();
+    let mut previous_char: Option<char> = None;
+    for c in text.chars() {
+        if let Some(previous_char) = previous_char {
+            if previous_char.is_ascii_digit() != c.is_ascii_digit() {
+                spaced_text.push(' ');
+            }
+        }
+        spaced_text.push(c);
+        previous_char = Some(c);
+    }
+
+    let mut han = None;
+    let mut fu = None;
+    let mut pending_number = None;
+    for word in spaced_text.split(|c: char| c == '/' || c == '-' || c == ',' || c.is_whitespace()) {
+        if word.is_empty() {
+            continue;
+        }
+        if let Ok(number) = word.parse::<u8>() {
+            if let Some(pending_number) = pending_number.replace(number) {
+                // two numbers in a row without units are han and fu
+                set_unitless_number(&mut han, &mut fu, pending_number)?;
+            }
+            continue;
+        }
+
+        let target = match word {
+            "han" => &mut han,
+            "fu" => &mut fu,
+            _ => {
+                return Err(ReverseAnswerParseError::InvalidText {
+                    text: word.to_string(),
+                })
+            }
+        };
+        let Some(number) = pending_number.take() else {
+            return Err(ReverseAnswerParseError::InvalidText {
+                text: word.to_string(),
+            });
+        };
+        set_answer_unit(target, number, word)?;
+    }
+
+    if let Some(pending_number) = pending_number {
+        set_unitless_number(&mut han, &mut fu, pending_number)?;
+    }
+
+    match han {
+        Some(han) => Ok(ReverseAnswer::HanFu(han, fu)),
+        None => Err(ReverseAnswerParseError::MissingHan),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user_settings::get_default_settings;
+
+    fn hand(han: u8, fu: u8, ron: bool, is_dealer: bool) -> HandScoreData {
+        HandScoreData {
+            han,
+            fu,
+            honba: 0,
+            ron,
+            is_dealer,
+        }
+    }
+
+    #[test]
+    fn test_matching_combinations() {
+        let settings = get_default_settings().scoring_settings;
+
+        // non-dealer ron for 5200
+        assert_eq!(
+            get_matching_combinations(&hand(3, 40, true, false), settings),
+            vec![(2, Some(80)), (3, Some(40))]
+        );
+        // 4 han 20 fu is only possible with tsumo
+        assert_eq!(
+            get_matching_combinations(&hand(3, 40, false, false), settings),
+            vec![(2, Some(80)), (3, Some(40)), (4, Some(20))]
+        );
+        assert_eq!(
+            format_combinations(&get_matching_combinations(
+                &hand(6, 30, true, false),
+                settings
+            )),
+            "6-7 han"
+        );
+    }
+
+    #[test]
+    fn test_any_matching_combination_is_correct() {
+        let settings = get_default_settings().scoring_settings;
+        let hand_score = hand(3, 40, true, false);
+
+        assert!(is_correct_reverse_answer(
+            &hand_score,
+            &ReverseAnswer::HanFu(2, Some(80)),
+            settings
+        ));
+        assert!(!is_correct_reverse_answer(
+            &hand_score,
+            &ReverseAnswer::HanFu(3, Some(30)),
+            settings
+        ));
+        assert!(is_correct_reverse_answer(
+            &hand(4, 40, true, false),
+            &ReverseAnswer::HanFu(5, None),
+            settings
+        ));
+        assert!(is_correct_reverse_answer(
+            &hand(4, 40, true, false),
+            &ReverseAnswer::Limit(LimitHand::Mangan),
+            settings
+        ));
+    }
+
+    #[test]
+    fn test_parse_reverse_answer() {
+        assert_eq!(
+            parse_reverse_answer("3 han 40 fu"),
+            Ok(ReverseAnswer::HanFu(3, Some(40)))
+        );
+        assert_eq!(
+            parse_reverse_answer("40fu3han"),
+            Ok(ReverseAnswer::HanFu(3, Some(40)))
+        );
+        assert_eq!(
+            parse_reverse_answer("3/40"),
+            Ok(ReverseAnswer::HanFu(3, Some(40)))
+        );
+        assert_eq!(
+            parse_reverse_answer("6 han"),
+            Ok(ReverseAnswer::HanFu(6, None))
+        );
+        assert_eq!(
+            parse_reverse_answer("Haneman"),
+            Ok(ReverseAnswer::Limit(LimitHand::Haneman))
+        );
+        assert_eq!(
+            parse_reverse_answer("40 fu"),
+            Err(ReverseAnswerParseError::MissingHan)
+        );
+        assert_eq!(
+            parse_reverse_answer("3 points"),
+            Err(ReverseAnswerParseError::InvalidText {
+                text: "points".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_reverse_answer_rejects_repeated_units() {
+        assert_eq!(
+            parse_reverse_answer("3 han 40 fu 5 han"),
+            Err(ReverseAnswerParseError::InvalidText {
+                text: "han".to_string()
+            })
+        );
+        assert_eq!(
+            parse_reverse_answer("3 40 50"),
+            Err(ReverseAnswerParseError::InvalidText {
+                text: "50".to_string()
+            })
+        );
+        assert_eq!(
+            parse_reverse_answer("40 fu 3 30 fu"),
+            Err(ReverseAnswerParseError::InvalidText {
+                text: "fu".to_string()
+            })
+        );
+    }
+}
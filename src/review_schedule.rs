@@ -0,0 +1,163 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::hand_score::HandScoreData;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Leitner boxes, a missed combination starts in the first box and moves to the next box
+// each time it is answered correctly, after the last box it is considered learned
+const BOX_INTERVALS_SECONDS: [i64; 5] = [2 * 60, 10 * 60, 60 * 60, 24 * 60 * 60, 3 * 24 * 60 * 60];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct HandCombination {
+    pub han: u8,
+    pub fu: u8,
+    pub is_dealer: bool,
+    pub ron: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewItem {
+    pub combination: HandCombination,
+    pub box_index: usize,
+    // unix timestamp after which the combination should be asked again
+    pub due_time: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewSchedule {
+    pub items: Vec<ReviewItem>,
+}
+
+impl HandCombination {
+    pub fn from_hand(hand: &HandScoreData) -> HandCombination {
+        HandCombination {
+            han: hand.han,
+            fu: hand.fu,
+            is_dealer: hand.is_dealer,
+            ron: hand.ron,
+        }
+    }
+
+    // keeps the honba of the given hand
+    pub fn apply_to_hand(&self, hand: &mut HandScoreData) {
+        hand.han = self.han;
+        hand.fu = self.fu;
+        hand.is_dealer = self.is_dealer;
+        hand.ron = self.ron;
+    }
+}
+
+impl ReviewSchedule {
+    pub fn record_answer(&mut self, combination: HandCombination, is_correct: bool, time: i64) {
+        let item_index = self
+            .items
+            .iter()
+            .position(|item| item.combination == combination);
+
+        match (item_index, is_correct) {
+            (Some(index), false) => {
+                let item = &mut self.items[index];
+                item.box_index = 0;
+                item.due_time = time + BOX_INTERVALS_SECONDS[0];
+            }
+            (None, false) => self.items.push(ReviewItem {
+                combination,
+                box_index: 0,
+                due_time: time + BOX_INTERVALS_SECONDS[0],
+            }),
+            // only reviews that were due count, otherwise the same combination
+            // generated twice in a row would skip boxes
+            (Some(index), true) if self.items[index].due_time <= time => {
+                let item = &mut self.items[index];
+                item.box_index += 1;
+                if item.box_index >= BOX_INTERVALS_SECONDS.len() {
+                    self.items.remove(index);
+                } else {
+                    item.due_time = time + BOX_INTERVALS_SECONDS[item.box_index];
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // the combination that has been waiting for its review the longest
+    pub fn get_due_combination(&self, time: i64) -> Option<HandCombination> {
+        self.items
+            .iter()
+            .filter(|item| item.due_time <= time)
+            .min_by_key(|item| item.due_time)
+            .map(|item| item.combination)
+    }
+}
+
+pub fn get_default_review_schedule() -> ReviewSchedule {
+    ReviewSchedule { items: Vec::new() }
+}
+
+pub fn get_current_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn combination(han: u8, fu: u8) -> HandCombination {
+        HandCombination {
+            han,
+            fu,
+            is_dealer: false,
+            ron: true,
+        }
+    }
+
+    #[test]
+    fn test_missed_combination_is_reviewed_with_increasing_intervals() {
+        let mut schedule = get_default_review_schedule();
+        schedule.record_answer(combination(3, 40), false, 0);
+
+        assert_eq!(schedule.get_due_combination(0), None);
+        let mut time = BOX_INTERVALS_SECONDS[0];
+        assert_eq!(schedule.get_due_combination(time), Some(combination(3, 40)));
+
+        for interval in &BOX_INTERVALS_SECONDS[1..] {
+            schedule.record_answer(combination(3, 40), true, time);
+            assert_eq!(schedule.get_due_combination(time + interval - 1), None);
+            time += interval;
+            assert_eq!(schedule.get_due_combination(time), Some(combination(3, 40)));
+        }
+
+        // answered correctly after the last box
+        schedule.record_answer(combination(3, 40), true, time);
+        assert!(schedule.items.is_empty());
+    }
+
+    #[test]
+    fn test_mistake_moves_combination_back_to_the_first_box() {
+        let mut schedule = get_default_review_schedule();
+        schedule.record_answer(combination(2, 30), false, 0);
+        schedule.record_answer(combination(2, 30), true, 1000);
+        assert_eq!(schedule.items[0].box_index, 1);
+
+        schedule.record_answer(combination(2, 30), false, 2000);
+        assert_eq!(schedule.items[0].box_index, 0);
+        assert_eq!(schedule.items[0].due_time, 2000 + BOX_INTERVALS_SECONDS[0]);
+    }
+
+    #[test]
+    fn test_correct_answer_before_the_review_is_due_is_ignored() {
+        let mut schedule = get_default_review_schedule();
+        schedule.record_answer(combination(1, 30), false, 0);
+        schedule.record_answer(combination(1, 30), true, 1);
+        assert_eq!(schedule.items[0].box_index, 0);
+
+        // correct answers for combinations that were never missed are not tracked
+        schedule.record_answer(combination(4, 30), true, 1);
+        assert_eq!(schedule.items.len(), 1);
+    }
+}
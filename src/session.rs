@@ -0,0 +1,147 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::hand_score::HandScoreData;
+use crate::mistake_classifier::MistakeType;
+
+pub const DEFAULT_SESSION_QUESTIONS_COUNT: u32 = 20;
+pub const MAX_SESSION_QUESTIONS_COUNT: u32 = 100;
+
+#[derive(Debug, Clone)]
+pub struct SessionAnswer {
+    pub hand: HandScoreData,
+    pub is_correct: bool,
+    // None if the mistake couldn't be recognized
+    pub mistake: Option<MistakeType>,
+    // only kept in exams, where the answers are shown in the results
+    pub answer_text: Option<String>,
+}
+
+// a fixed number of score questions that ends with a summary
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub questions_count: u32,
+    // unix timestamp of the message that started the session
+    pub start_time: i64,
+    // in exams the correct answers are shown only after the last question
+    pub is_exam: bool,
+    // index of the proficiency level that is tested by the exam
+    pub level_test: Option<usize>,
+    pub answers: Vec<SessionAnswer>,
+}
+
+impl Session {
+    pub fn new(questions_count: u32, start_time: i64, is_exam: bool) -> Session {
+        Session {
+            questions_count,
+            start_time,
+            is_exam,
+            level_test: None,
+            answers: Vec::new(),
+        }
+    }
+
+    pub fn record_answer(&mut self, answer: SessionAnswer) {
+        self.answers.push(answer);
+    }
+
+    pub fn get_answered_count(&self) -> u32 {
+        self.answers.len() as u32
+    }
+
+    pub fn get_correct_count(&self) -> u32 {
+        self.answers
+            .iter()
+            .filter(|answer| answer.is_correct)
+            .count() as u32
+    }
+
+    pub fn get_percent(&self) -> u32 {
+        if self.answers.is_empty() {
+            return 0;
+        }
+        self.get_correct_count() * 100 / self.get_answered_count()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.get_answered_count() >= self.questions_count
+    }
+
+    pub fn get_missed_answers(&self) -> impl Iterator<Item = &SessionAnswer> {
+        self.answers.iter().filter(|answer| !answer.is_correct)
+    }
+
+    // the number of mistakes of each type, the most common first
+    pub fn get_mistake_counts(&self) -> Vec<(Option<MistakeType>, u32)> {
+        let mut counts: Vec<(Option<MistakeType>, u32)> = Vec::new();
+        for missed_answer in self.get_missed_answers() {
+            match counts
+                .iter_mut()
+                .find(|(mistake, _)| *mistake == missed_answer.mistake)
+            {
+                Some((_, count)) => *count += 1,
+                None => counts.push((missed_answer.mistake, 1)),
+            }
+        }
+        // the sort is stable, so equal counts keep the order of the first mistake
+        counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answer(han: u8, fu: u8, is_correct: bool, mistake: Option<MistakeType>) -> SessionAnswer {
+        SessionAnswer {
+            hand: HandScoreData {
+                han,
+                fu,
+                honba: 0,
+                ron: true,
+                is_dealer: false,
+            },
+            is_correct,
+            mistake,
+            answer_text: None,
+        }
+    }
+
+    #[test]
+    fn test_session_finishes_after_the_questions_count() {
+        let mut session = Session::new(3, 1000, false);
+        session.record_answer(answer(1, 30, true, None));
+        session.record_answer(answer(3, 40, false, Some(MistakeType::ForgotRoundingUp)));
+        assert!(!session.is_finished());
+        session.record_answer(answer(2, 30, true, None));
+
+        assert!(session.is_finished());
+        assert_eq!(session.get_correct_count(), 2);
+        assert_eq!(session.get_percent(), 66);
+        let missed_hands: Vec<HandScoreData> = session
+            .get_missed_answers()
+            .map(|answer| answer.hand)
+            .collect();
+        assert_eq!(missed_hands, vec![answer(3, 40, false, None).hand]);
+    }
+
+    #[test]
+    fn test_mistakes_are_grouped_by_type() {
+        let mut session = Session::new(5, 0, false);
+        session.record_answer(answer(1, 30, false, None));
+        session.record_answer(answer(3, 40, false, Some(MistakeType::WrongFu)));
+        session.record_answer(answer(4, 40, false, Some(MistakeType::WrongFu)));
+        session.record_answer(answer(2, 30, true, None));
+        session.record_answer(answer(2, 30, false, Some(MistakeType::ForgotHonba)));
+
+        assert_eq!(
+            session.get_mistake_counts(),
+            vec![
+                (Some(MistakeType::WrongFu), 2),
+                (None, 1),
+                (Some(MistakeType::ForgotHonba), 1),
+            ]
+        );
+    }
+}
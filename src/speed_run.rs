@@ -0,0 +1,133 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::generation_filter::get_default_generation_filter;
+use crate::hand_score::ScoringSettings;
+use serde::{Deserialize, Serialize};
+
+pub const TIME_LIMIT_SECONDS: i64 = 60;
+pub const QUESTIONS_COUNT: u32 = 10;
+pub const WRONG_ANSWER_PENALTY_SECONDS: i64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SpeedRunKind {
+    // as many correct answers as possible in TIME_LIMIT_SECONDS
+    TimeLimit,
+    // QUESTIONS_COUNT questions as fast as possible
+    QuestionCount,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SpeedRun {
+    pub kind: SpeedRunKind,
+    // unix timestamp of the message that started the run
+    pub start_time: i64,
+    pub answered_count: u32,
+    pub correct_count: u32,
+}
+
+impl SpeedRunKind {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            SpeedRunKind::TimeLimit => "time",
+            SpeedRunKind::QuestionCount => "count",
+        }
+    }
+
+    // correct answers for the time limit, seconds for the question count
+    pub fn format_result(&self, result: u32) -> String {
+        match self {
+            SpeedRunKind::TimeLimit => format!("{} correct answers", result),
+            SpeedRunKind::QuestionCount => format!("{} seconds", result),
+        }
+    }
+
+    pub fn is_better_result(&self, result: u32, previous_best: u32) -> bool {
+        match self {
+            SpeedRunKind::TimeLimit => result > previous_best,
+            SpeedRunKind::QuestionCount => result < previous_best,
+        }
+    }
+}
+
+// speed runs always ask all hands, so the records depend only on the scoring rules
+pub fn get_speed_run_rules(scoring_settings: ScoringSettings) -> ScoringSettings {
+    ScoringSettings {
+        generation_filter: get_default_generation_filter(),
+        ..scoring_settings
+    }
+}
+
+impl SpeedRun {
+    pub fn new(kind: SpeedRunKind, start_time: i64) -> SpeedRun {
+        SpeedRun {
+            kind,
+            start_time,
+            answered_count: 0,
+            correct_count: 0,
+        }
+    }
+
+    // answers sent after the time is up are not counted
+    pub fn is_time_up(&self, time: i64) -> bool {
+        self.kind == SpeedRunKind::TimeLimit && time - self.start_time >= TIME_LIMIT_SECONDS
+    }
+
+    pub fn record_answer(&mut self, is_correct: bool) {
+        self.answered_count += 1;
+        if is_correct {
+            self.correct_count += 1;
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.kind == SpeedRunKind::QuestionCount && self.answered_count >= QUESTIONS_COUNT
+    }
+
+    pub fn get_result(&self, finish_time: i64) -> u32 {
+        match self.kind {
+            SpeedRunKind::TimeLimit => self.correct_count,
+            SpeedRunKind::QuestionCount => {
+                let wrong_count = (self.answered_count - self.correct_count) as i64;
+                let elapsed_time = (finish_time - self.start_time).max(0);
+                (elapsed_time + wrong_count * WRONG_ANSWER_PENALTY_SECONDS) as u32
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_limit_run_counts_correct_answers() {
+        let mut run = SpeedRun::new(SpeedRunKind::TimeLimit, 1000);
+        run.record_answer(true);
+        run.record_answer(false);
+        run.record_answer(true);
+
+        assert!(!run.is_finished());
+        assert!(!run.is_time_up(1000 + TIME_LIMIT_SECONDS - 1));
+        assert!(run.is_time_up(1000 + TIME_LIMIT_SECONDS));
+        assert_eq!(run.get_result(1100), 2);
+        assert!(SpeedRunKind::TimeLimit.is_better_result(3, 2));
+    }
+
+    #[test]
+    fn test_question_count_run_adds_penalty_for_wrong_answers() {
+        let mut run = SpeedRun::new(SpeedRunKind::QuestionCount, 1000);
+        for index in 0..QUESTIONS_COUNT {
+            assert!(!run.is_finished());
+            run.record_answer(index != 0);
+        }
+
+        assert!(run.is_finished());
+        assert!(!run.is_time_up(5000));
+        assert_eq!(
+            run.get_result(1045),
+            45 + WRONG_ANSWER_PENALTY_SECONDS as u32
+        );
+        assert!(SpeedRunKind::QuestionCount.is_better_result(40, 55));
+    }
+}
@@ -1,17 +1,53 @@
 // Copyright (C) Pavel Grebnev 2024
 // Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
 
-use crate::hand_score::HandScoreData;
+mod modes;
+
+use crate::achievements::{award_new_achievements, ALL_ACHIEVEMENTS};
+use crate::adaptive_difficulty::*;
+use crate::answer_parser::{is_official_answer_form, AnswerParseError, ParsedAnswer};
+use crate::daily_challenge::*;
+use crate::generation_distribution::{
+    read_generation_distribution_from_file, set_generation_distribution,
+};
+use crate::generation_filter::*;
+use crate::group_game::{DEFAULT_QUESTIONS_COUNT, MAX_QUESTIONS_COUNT};
+use crate::hand_score::{
+    HandScoreData, HandScoreTotals, KazoeYakumanRule, LimitHand, ScoringSettings, SeededHands,
+};
+use crate::mistake_classifier::{classify_mistake, MistakeType};
+use crate::proficiency::*;
+use crate::review_schedule::{get_current_timestamp, HandCombination};
+use crate::session::*;
+use crate::speed_run::*;
 use crate::translations::*;
 use crate::user_settings::*;
 use crate::user_state::*;
+use crate::user_statistics::{get_han_band, Accuracy, AnswerRecord, HAN_BANDS};
+use modes::choice::*;
+use modes::daily::*;
+use modes::drill::*;
+use modes::exam::*;
+use modes::fu::*;
+use modes::group::*;
+use modes::level_test::*;
+use modes::reverse::*;
+use modes::score::*;
+use modes::session::*;
+use modes::speed_run::*;
+use modes::yaku::*;
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 use teloxide::prelude::*;
+use teloxide::types::InlineKeyboardMarkup;
 
 static USER_STATES_PATH: &str = "./data/user_states.json";
+static GENERATION_DISTRIBUTION_PATH: &str = "./data/generation_distribution.json";
+// changed states are written to the disk in batches, not after every answer
+const SAVE_INTERVAL_SECONDS: u64 = 30;
 
 fn read_telegram_token() -> String {
     fs::read_to_string("./telegramApiToken.txt")
@@ -19,6 +55,7 @@ fn read_telegram_token() -> String {
 }
 
 struct StaticData {
+    #[allow(dead_code)]
     translations: Translations,
 }
 
@@ -26,26 +63,83 @@ struct StaticData {
 struct Response {
     text: String,
     image: Option<teloxide::types::InputFile>,
+    keyboard: Option<InlineKeyboardMarkup>,
 }
 
 fn text_response(text: &str) -> Vec<Response> {
     [Response {
         text: text.to_string(),
         image: None,
+        keyboard: None,
     }]
     .to_vec()
 }
 
-fn generate_new_hand_text(
-    hand_score: &mut Option<HandScoreData>,
-    settings: &UserSettings,
-) -> String {
-    *hand_score = Some(HandScoreData::generate_winning_hand(
-        settings.scoring_settings,
-    ));
-    let score = hand_score.as_ref().unwrap();
+fn get_level_test_index(user_state: &UserState) -> Option<usize> {
+    user_state
+        .session
+        .as_ref()
+        .and_then(|session| session.level_test)
+}
+
+// seeded hands, speed runs, daily challenges and level tests should not depend on the progress
+// of the user
+fn is_adaptive_difficulty_used(user_state: &UserState) -> bool {
+    user_state.settings.use_adaptive_difficulty
+        && user_state.seeded_hands.is_none()
+        && user_state.speed_run.is_none()
+        && user_state.daily_challenge.is_none()
+        && get_level_test_index(user_state).is_none()
+        && matches!(
+            user_state.settings.game_mode,
+            GameMode::Score | GameMode::MultipleChoice
+        )
+}
+
+// the rules of the user with the hands limited by the difficulty level if it is used,
+// level tests and speed runs ask all hands regardless of the drills
+fn get_generation_settings(user_state: &UserState) -> ScoringSettings {
+    let scoring_settings = user_state.settings.scoring_settings;
+    if let Some(level_index) = get_level_test_index(user_state) {
+        ScoringSettings {
+            generation_filter: get_default_generation_filter(),
+            use_honba: PROFICIENCY_LEVELS[level_index].is_honba_tested(),
+            ..scoring_settings
+        }
+    } else if user_state.speed_run.is_some() {
+        get_speed_run_rules(scoring_settings)
+    } else if is_adaptive_difficulty_used(user_state) {
+        user_state
+            .statistics
+            .adaptive_difficulty
+            .get_scoring_settings(scoring_settings)
+    } else {
+        scoring_settings
+    }
+}
+
+fn generate_new_hand_text(user_state: &mut UserState) -> String {
+    let generation_settings = get_generation_settings(user_state);
+    let seeded_hands = match get_level_test_index(user_state) {
+        Some(_) => None,
+        None => user_state.seeded_hands.as_mut(),
+    };
+    let score = match seeded_hands {
+        Some(seeded_hands) => seeded_hands.generate_next_hand(generation_settings),
+        None => HandScoreData::generate_winning_hand(&mut rand::thread_rng(), generation_settings),
+    };
+    user_state.hand_score = Some(score);
+    user_state.question_time = Some(get_current_timestamp());
+    get_hand_score_question_text(&score, is_limit_name_asked(&score, &user_state.settings))
+}
+
+fn is_limit_name_asked(score: &HandScoreData, settings: &UserSettings) -> bool {
+    settings.ask_limit_names && score.get_limit(settings.scoring_settings).is_some()
+}
+
+fn get_hand_score_question_text(score: &HandScoreData, is_limit_name_asked: bool) -> String {
     format!(
-        "{} han{}\n{}\n{}{}",
+        "{} han{}\n{}\n{}{}{}",
         score.han,
         if score.han >= 5 {
             "".to_string()
@@ -62,51 +156,612 @@ fn generate_new_hand_text(
             format!("\n{} honba", score.honba)
         } else {
             "".to_string()
+        },
+        if is_limit_name_asked {
+            "\n\nName the limit"
+        } else {
+            ""
         }
     )
 }
 
+fn generate_new_score_hand_text(user_state: &mut UserState) -> String {
+    user_state.question_time = Some(get_current_timestamp());
+    // reviews would change the hands that are expected from the seed or the level test
+    if user_state.settings.use_spaced_repetition
+        && user_state.seeded_hands.is_none()
+        && get_level_test_index(user_state).is_none()
+    {
+        let due_combination = user_state
+            .review_schedule
+            .get_due_combination(get_current_timestamp());
+        if let Some(combination) = due_combination {
+            let mut hand_score = HandScoreData::generate_winning_hand(
+                &mut rand::thread_rng(),
+                get_generation_settings(user_state),
+            );
+            combination.apply_to_hand(&mut hand_score);
+            user_state.hand_score = Some(hand_score);
+            return "Review of a hand you missed before:\n".to_string()
+                + &get_hand_score_question_text(
+                    &hand_score,
+                    is_limit_name_asked(&hand_score, &user_state.settings),
+                );
+        }
+    }
+    generate_new_hand_text(user_state)
+}
+
+// the part of the answer tracking that is shared by all games, including the ones played
+// with hands that were not chosen for the user
+fn record_answer_statistics(
+    user_state: &mut UserState,
+    hand_score: &HandScoreData,
+    limit: Option<LimitHand>,
+    is_correct: bool,
+    mistake: Option<MistakeType>,
+    answer_seconds: Option<u32>,
+) {
+    let time = get_current_timestamp();
+    let combination = HandCombination::from_hand(hand_score);
+
+    user_state.statistics.record_answer(AnswerRecord {
+        combination,
+        honba: hand_score.honba,
+        is_correct,
+        mistake,
+        limit,
+        answer_seconds,
+        time,
+    });
+    user_state.statistics_unsaved = true;
+    user_state
+        .new_achievements
+        .extend(award_new_achievements(&mut user_state.statistics, time));
+
+    if user_state.settings.use_spaced_repetition {
+        user_state
+            .review_schedule
+            .record_answer(combination, is_correct, time);
+        user_state.review_schedule_unsaved = true;
+    }
+}
+
+fn take_answer_seconds(user_state: &mut UserState) -> Option<u32> {
+    let time = get_current_timestamp();
+    user_state
+        .question_time
+        .take()
+        .map(|question_time| (time - question_time).max(0) as u32)
+}
+
+// everything that needs to be tracked about an answered score question
+fn record_score_answer(
+    user_state: &mut UserState,
+    hand_score: &HandScoreData,
+    is_correct: bool,
+    mistake: Option<MistakeType>,
+) {
+    let answer_seconds = take_answer_seconds(user_state);
+    record_answer_statistics(
+        user_state,
+        hand_score,
+        hand_score
+            .calculate_totals(user_state.settings.scoring_settings)
+            .limit,
+        is_correct,
+        mistake,
+        answer_seconds,
+    );
+
+    // exam answers are recorded together with the answer text when they are given
+    if let Some(session) = &mut user_state.session {
+        if !session.is_exam {
+            session.record_answer(SessionAnswer {
+                hand: *hand_score,
+                is_correct,
+                mistake,
+                answer_text: None,
+            });
+        }
+    }
+
+    if is_adaptive_difficulty_used(user_state) {
+        let statistics = &mut user_state.statistics;
+        let change = statistics
+            .adaptive_difficulty
+            .record_answer(&statistics.answers);
+        if change.is_some() {
+            user_state.difficulty_change = change;
+        }
+    }
+}
+
+// records the result of the answered score question and returns the text that follows the result,
+// the next question or the summary of the finished session
+fn finish_score_question(
+    user_state: &mut UserState,
+    hand_score: &HandScoreData,
+    is_correct: bool,
+    mistake: Option<MistakeType>,
+) -> String {
+    record_score_answer(user_state, hand_score, is_correct, mistake);
+    continue_after_score_question(user_state)
+}
+
+// finishes the session if it was the last question, otherwise asks the next one
+fn continue_after_score_question(user_state: &mut UserState) -> String {
+    if user_state
+        .session
+        .as_ref()
+        .is_some_and(Session::is_finished)
+    {
+        return "\n\n".to_string() + &finish_session(user_state);
+    }
+    "\n\nNext hand:\n".to_string() + &generate_next_question_text(user_state)
+}
+
+// the question that follows an answered score question in the current mode
+fn generate_next_question_text(user_state: &mut UserState) -> String {
+    match user_state.settings.game_mode {
+        GameMode::Yaku => generate_new_yaku_question_text(user_state),
+        _ => generate_new_score_hand_text(user_state),
+    }
+}
+
+fn get_game_mode_name(game_mode: GameMode) -> &'static str {
+    match game_mode {
+        GameMode::Score => "score",
+        GameMode::Fu => "fu",
+        GameMode::Yaku => "yaku",
+        GameMode::Reverse => "reverse",
+        GameMode::MultipleChoice => "choice",
+    }
+}
+
+fn format_totals(hand_score: &HandScoreData, totals: &HandScoreTotals) -> String {
+    let score = if hand_score.ron {
+        format!("{}", totals.others)
+    } else if hand_score.is_dealer {
+        format!("{} all", totals.others)
+    } else {
+        format!("{}/{}", totals.others, totals.dealer)
+    };
+
+    if let Some(limit) = totals.limit {
+        format!("{} ({})", score, limit.get_name())
+    } else {
+        score
+    }
+}
+
+fn get_answer_parse_error_text(error: &AnswerParseError) -> String {
+    const FORMAT_HINT: &str = "the format is 1000, 1000/2000 or 2000 all";
+    match error {
+        AnswerParseError::Empty => format!("No score found, {}", FORMAT_HINT),
+        AnswerParseError::InvalidNumber { text } => {
+            format!("Failed to parse \"{}\" as a score, {}", text, FORMAT_HINT)
+        }
+        AnswerParseError::TooManyParts { count } => {
+            format!(
+                "Expected one or two scores but got {}, {}",
+                count, FORMAT_HINT
+            )
+        }
+        AnswerParseError::AllWithTwoParts => {
+            "\"all\" should follow a single score, e.g. 2000 all".to_string()
+        }
+    }
+}
+
+fn is_correct_score_answer(
+    answer: &ParsedAnswer,
+    hand_score: &HandScoreData,
+    totals: &HandScoreTotals,
+    settings: &UserSettings,
+    is_limit_name_asked: bool,
+) -> bool {
+    // when the question asks to name the limit, the score alone is not enough
+    if is_limit_name_asked {
+        return matches!(answer, ParsedAnswer::Limit(limit) if Some(*limit) == totals.limit);
+    }
+
+    if settings.strict_answer_order && !is_official_answer_form(answer, hand_score) {
+        return false;
+    }
+
+    let (answered_first, answered_second) = match answer {
+        ParsedAnswer::Single(score) | ParsedAnswer::All(score) => (*score, 0),
+        ParsedAnswer::Split(first, second) => (*first, *second),
+        ParsedAnswer::Limit(limit) => {
+            let limit_totals = hand_score.calculate_totals_for_limit(*limit);
+            (limit_totals.others, limit_totals.dealer)
+        }
+    };
+
+    let is_swapped = answered_first == totals.dealer && answered_second == totals.others;
+    answered_first == totals.others && answered_second == totals.dealer
+        || is_swapped && !settings.strict_answer_order
+}
+
+// the mistake of a wrong answer for the games where the answer is checked only once
+fn classify_answer_mistake(
+    answer: &ParsedAnswer,
+    hand_score: &HandScoreData,
+    settings: &UserSettings,
+    is_limit_name_asked: bool,
+) -> Option<MistakeType> {
+    match *answer {
+        _ if is_limit_name_asked => Some(MistakeType::WrongLimit),
+        ParsedAnswer::Single(score) | ParsedAnswer::All(score) => {
+            classify_mistake(hand_score, settings.scoring_settings, score, 0, false)
+        }
+        ParsedAnswer::Split(first, second) => classify_mistake(
+            hand_score,
+            settings.scoring_settings,
+            first,
+            second,
+            !settings.strict_answer_order,
+        ),
+        ParsedAnswer::Limit(_) => None,
+    }
+}
+
+fn get_score_instructions_text(settings: &UserSettings) -> &'static str {
+    if settings.strict_answer_order {
+        "\n\nAnswer with the score in the official order:\n1000 for ron\n1000/2000 for non-dealer tsumo (non-dealers pay first, then the dealer)\n2000 all for dealer tsumo"
+    } else {
+        "\n\nAnswer with the score in format 1000, 1000/2000 or 2000 all\n(you can also use - or space instead of /)"
+    }
+}
+
+// only one of the games with results can run at a time, and their hands can't be known from a seed
+fn stop_timed_games(user_state: &mut UserState) {
+    user_state.retried_payment = None;
+    user_state.speed_run = None;
+    user_state.session = None;
+    user_state.daily_challenge = None;
+    user_state.seeded_hands = None;
+    user_state.choice_options.clear();
+}
+
+fn get_mistake_text(mistake: Option<MistakeType>, hand_score: &HandScoreData) -> String {
+    let Some(mistake) = mistake else {
+        return "".to_string();
+    };
+
+    let description = match mistake {
+        MistakeType::ForgotRoundingUp => "forgot to round the payments up to 100",
+        MistakeType::RoundedBasePoints => {
+            "rounded the base points instead of rounding the final payments"
+        }
+        MistakeType::WrongSeatMultiplier => {
+            if hand_score.is_dealer {
+                "used the non-dealer multiplier for the dealer"
+            } else {
+                "used the dealer multiplier for a non-dealer"
+            }
+        }
+        MistakeType::ForgotHonba => "forgot to add honba",
+        MistakeType::WrongHonbaAmount => {
+            if hand_score.ron {
+                "counted 100 per honba instead of 300 for ron"
+            } else {
+                "added 300 per honba to each payment instead of 100 for tsumo"
+            }
+        }
+        MistakeType::MissedKiriageMangan => "missed kiriage mangan",
+        MistakeType::AppliedKiriageMangan => "applied kiriage mangan, but it is turned off",
+        MistakeType::MissedManganCap => "didn't cap the score at mangan",
+        MistakeType::WrongLimit => "mixed up the limit",
+        MistakeType::WrongFu => "counted a different fu value",
+        MistakeType::WrongHan => "counted a different han value",
+    };
+
+    format!("Looks like you {}.\n", description)
+}
+
+fn get_mistake_type_name(mistake: MistakeType) -> &'static str {
+    match mistake {
+        MistakeType::ForgotRoundingUp => "not rounding up",
+        MistakeType::RoundedBasePoints => "rounding base points",
+        MistakeType::WrongSeatMultiplier => "dealer/non-dealer multiplier",
+        MistakeType::ForgotHonba => "forgetting honba",
+        MistakeType::WrongHonbaAmount => "wrong honba amount",
+        MistakeType::MissedKiriageMangan => "missing kiriage mangan",
+        MistakeType::AppliedKiriageMangan => "applying kiriage mangan",
+        MistakeType::MissedManganCap => "missing the mangan cap",
+        MistakeType::WrongLimit => "wrong limit",
+        MistakeType::WrongFu => "wrong fu",
+        MistakeType::WrongHan => "wrong han",
+    }
+}
+
+fn format_accuracy(name: &str, accuracy: &Accuracy) -> String {
+    let time_text = match accuracy.get_average_answer_seconds() {
+        Some(seconds) => format!(", {}s per answer", seconds),
+        None => "".to_string(),
+    };
+    format!(
+        "{}: {}% ({}/{}){}",
+        name,
+        accuracy.get_percent(),
+        accuracy.correct_count,
+        accuracy.total_count,
+        time_text
+    )
+}
+
+fn get_statistics_text(user_state: &UserState) -> String {
+    const TREND_WEEKS_COUNT: usize = 4;
+    let statistics = &user_state.statistics;
+
+    let overall = statistics.get_accuracy(|_| true);
+    if overall.total_count == 0 {
+        return "No answers yet, send /start to start a new game".to_string();
+    }
+
+    let mut lines = vec![
+        get_proficiency_text(user_state),
+        "".to_string(),
+        format_accuracy("Overall", &overall),
+        format!(
+            "Current streak: {}, best streak: {}",
+            statistics.current_streak, statistics.best_streak
+        ),
+    ];
+
+    lines.push("\nBy han:".to_string());
+    for (band, accuracy) in
+        statistics.get_accuracy_by(|record| Some(get_han_band(record.combination.han)))
+    {
+        let name = match band {
+            (min_han, _) if band == HAN_BANDS[HAN_BANDS.len() - 1] => format!("{}+ han", min_han),
+            (min_han, max_han) if min_han == max_han => format!("{} han", min_han),
+            (min_han, max_han) => format!("{}-{} han", min_han, max_han),
+        };
+        lines.push(format_accuracy(&name, &accuracy));
+    }
+
+    // fu doesn't matter for hands with 5 or more han
+    let by_fu = statistics
+        .get_accuracy_by(|record| (record.combination.han < 5).then_some(record.combination.fu));
+    if !by_fu.is_empty() {
+        lines.push("\nBy fu (up to 4 han):".to_string());
+        for (fu, accuracy) in by_fu {
+            lines.push(format_accuracy(&format!("{} fu", fu), &accuracy));
+        }
+    }
+
+    lines.push("".to_string());
+    lines.push(format_accuracy(
+        "Dealer",
+        &statistics.get_accuracy(|record| record.combination.is_dealer),
+    ));
+    lines.push(format_accuracy(
+        "Non-dealer",
+        &statistics.get_accuracy(|record| !record.combination.is_dealer),
+    ));
+    lines.push(format_accuracy(
+        "Ron",
+        &statistics.get_accuracy(|record| record.combination.ron),
+    ));
+    lines.push(format_accuracy(
+        "Tsumo",
+        &statistics.get_accuracy(|record| !record.combination.ron),
+    ));
+
+    lines.push("\nLast weeks:".to_string());
+    let weeks = statistics.get_weekly_accuracy(get_current_timestamp(), TREND_WEEKS_COUNT);
+    for (index, accuracy) in weeks.iter().enumerate() {
+        let name = match index {
+            0 => "This week".to_string(),
+            1 => "1 week ago".to_string(),
+            _ => format!("{} weeks ago", index),
+        };
+        if accuracy.total_count == 0 {
+            lines.push(format!("{}: no answers", name));
+        } else {
+            lines.push(format_accuracy(&name, accuracy));
+        }
+    }
+
+    let mut mistakes: Vec<(MistakeType, u32)> = statistics
+        .mistakes
+        .iter()
+        .map(|(mistake, count)| (*mistake, *count))
+        .collect();
+    if !mistakes.is_empty() {
+        mistakes.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        lines.push("\nMost common mistakes:".to_string());
+        for (mistake, count) in mistakes.iter().take(3) {
+            lines.push(format!(
+                "{} - {} times",
+                get_mistake_type_name(*mistake),
+                count
+            ));
+        }
+    }
+
+    lines.push(format!(
+        "\nAchievements ({}/{}):",
+        statistics.achievements.len(),
+        ALL_ACHIEVEMENTS.len()
+    ));
+    for achievement in ALL_ACHIEVEMENTS {
+        lines.push(format!(
+            "{} {} - {}",
+            if statistics.achievements.contains(&achievement) {
+                "✓"
+            } else {
+                "✗"
+            },
+            achievement.get_name(),
+            achievement.get_description()
+        ));
+    }
+
+    lines.join("\n")
+}
+
+fn get_difficulty_text(user_state: &UserState) -> String {
+    let difficulty = &user_state.statistics.adaptive_difficulty;
+    let level = difficulty.get_level();
+    let mut lines = vec![
+        format!(
+            "Difficulty level {}/{}: {}",
+            difficulty.level_index + 1,
+            DIFFICULTY_LEVELS.len(),
+            level.name
+        ),
+        format!("Hands: {}", level.description),
+    ];
+
+    match difficulty.get_rolling_percent(&user_state.statistics.answers) {
+        Some(percent) => lines.push(format!(
+            "Accuracy at this level: {}% of the last {} answers",
+            percent,
+            difficulty.answers_at_level.min(ROLLING_ANSWERS_COUNT)
+        )),
+        None => lines.push("No answers at this level yet".to_string()),
+    }
+
+    if difficulty.is_last_level() {
+        lines.push("This is the hardest level".to_string());
+    } else {
+        lines.push(format!(
+            "Answer at least {} hands at this level with {}% accuracy or more to reach the next level",
+            ROLLING_ANSWERS_COUNT, PROMOTION_PERCENT
+        ));
+    }
+    if difficulty.level_index > 0 {
+        lines.push(format!(
+            "Below {}% accuracy the level goes back to make it easier",
+            FALL_BACK_PERCENT
+        ));
+    }
+
+    lines.join("\n")
+}
+
+fn get_kazoe_yakuman_rule_name(rule: KazoeYakumanRule) -> &'static str {
+    match rule {
+        KazoeYakumanRule::Yakuman => "yakuman",
+        KazoeYakumanRule::Sanbaiman => "sanbaiman",
+        KazoeYakumanRule::MultipleYakuman => "multiple yakuman",
+    }
+}
+
 fn text_response_str(text: String) -> Vec<Response> {
-    [Response { text, image: None }].to_vec()
+    [Response {
+        text,
+        image: None,
+        keyboard: None,
+    }]
+    .to_vec()
 }
 
+#[allow(dead_code)]
 fn single_text_response_str(text: String) -> Response {
-    Response { text, image: None }
+    Response {
+        text,
+        image: None,
+        keyboard: None,
+    }
 }
 
+#[allow(dead_code)]
 fn single_text_response(text: &str) -> Response {
     Response {
         text: text.to_string(),
         image: None,
+        keyboard: None,
     }
 }
 
 fn process_user_message(
     user_state: &mut UserState,
     message: &Message,
-    static_data: &StaticData,
+    _static_data: &StaticData,
+    daily_rankings: &DailyRankings,
 ) -> Vec<Response> {
     let Some(message_text) = &message.text() else {
         return text_response("No message received");
     };
 
-    const NO_GAME_IN_PROGRESS_MESSAGE: &str =
-        "No game is in progress, send /start to start a new game";
+    // the results come first, then the command is processed as usual
+    let speed_run_responses = finish_expired_speed_run(user_state, message.date.timestamp());
+    if !speed_run_responses.is_empty() {
+        if !message_text.starts_with('/') {
+            return speed_run_responses;
+        }
+        let mut responses = speed_run_responses;
+        responses.extend(process_user_message(
+            user_state,
+            message,
+            _static_data,
+            daily_rankings,
+        ));
+        return responses;
+    }
+
     let settings = &mut user_state.settings;
     let opt_hand_score = &mut user_state.hand_score;
     let mut message_split = message_text.split_whitespace();
 
     match message_split.next() {
         Some("/start") => {
-            return text_response_str(generate_new_hand_text(opt_hand_score, settings) + "\n\nAnswer with the score in format 1000 or 1000/2000\n(you can also use space instead of /)");
+            if user_state.speed_run.take().is_some() {
+                let mut responses =
+                    text_response("The speed run is stopped, its result is not counted");
+                responses.extend(process_user_message(user_state, message, _static_data, daily_rankings));
+                return responses;
+            }
+            user_state.retried_payment = None;
+            user_state.daily_challenge = None;
+            user_state.session = None;
+            user_state.choice_options.clear();
+            if settings.game_mode == GameMode::Yaku {
+                return text_response_str(generate_new_yaku_question_text(user_state));
+            }
+            if settings.game_mode == GameMode::MultipleChoice {
+                return generate_new_choice_question(user_state, String::new());
+            }
+            if settings.game_mode == GameMode::Reverse {
+                return text_response_str(
+                    generate_new_reverse_question_text(opt_hand_score, settings)
+                        + "\n\nAnswer with the han and fu that give this score, e.g. 3 han 40 fu or 3/40\nFor 5 or more han the fu can be omitted",
+                );
+            }
+            if settings.game_mode == GameMode::Fu {
+                return text_response_str(
+                    generate_new_fu_question_text(&mut user_state.fu_question, settings)
+                        + "\n\nAnswer with the number of fu, e.g. 30",
+                );
+            }
+            return text_response_str(
+                generate_new_score_hand_text(user_state)
+                    + get_score_instructions_text(&user_state.settings),
+            );
         }
         Some("/settings") => {
             return text_response(&format!(
                 "
 /toggle_kiriage_mangan - turn {} counting 4 han 30 fu and 3 han 60 fu as mangan
 /toggle_honba - turn {} honba counting
-/toggle_kazoe - turn {} counting kazoe yakuman
-/toggle_less_than_5_han - {} hands with 5 or more han",
+/kazoe_yakuman /kazoe_sanbaiman /kazoe_multiple_yakuman - count 13 or more han as yakuman, sanbaiman or one yakuman per 13 han (now {})
+/toggle_less_than_5_han - {} hands with 5 or more han
+/drill - choose which hands to practice, e.g. /drill non-dealer tsumo 30-50 fu (now {})
+/toggle_double_wind_pair_fu - count a double wind pair as {} fu
+/toggle_open_pinfu_ron_fu - turn {} rounding open pinfu-shape ron up to 30 fu
+/toggle_legacy_chiitoitsu - count chiitoitsu as {}
+/toggle_rinshan_tsumo_fu - turn {} tsumo fu for rinshan kaihou
+/toggle_ask_limit_names - turn {} asking for the name of limit hands
+/toggle_strict_answer_order - turn {} requiring the official order of tsumo payments
+/toggle_retry_wrong_part - turn {} retrying only the wrong payment of a tsumo
+/toggle_spaced_repetition - turn {} asking missed hands again with increasing intervals
+/toggle_adaptive_difficulty - turn {} choosing the hands by how well you answer, replaces /drill and /toggle_honba for generated hands
+/mode score /mode fu /mode yaku /mode reverse /mode choice - practice counting the score, the fu or the yaku of a hand, naming han and fu for a score, or choosing the score from options (now {})",
                 if user_state.settings.scoring_settings.use_kiriage_mangan {
                     "off"
                 } else {
@@ -117,16 +772,71 @@ fn process_user_message(
                 } else {
                     "on"
                 },
-                if user_state.settings.scoring_settings.use_kazoe_yakuman {
+                get_kazoe_yakuman_rule_name(user_state.settings.scoring_settings.kazoe_yakuman),
+                if user_state.settings.scoring_settings.generation_filter.max_han <= 4 {
+                    "turn on"
+                } else {
+                    "turn off"
+                },
+                user_state
+                    .settings
+                    .scoring_settings
+                    .generation_filter
+                    .get_description(),
+                if user_state
+                    .settings
+                    .scoring_settings
+                    .use_4_fu_double_wind_pair
+                {
+                    "2"
+                } else {
+                    "4"
+                },
+                if user_state
+                    .settings
+                    .scoring_settings
+                    .use_30_fu_open_pinfu_ron
+                {
                     "off"
                 } else {
                     "on"
                 },
-                if user_state.settings.scoring_settings.only_less_than_5_han {
-                    "turn on"
+                if user_state.settings.scoring_settings.use_legacy_chiitoitsu {
+                    "25 fu 2 han"
                 } else {
-                    "turn off"
-                }
+                    "50 fu 1 han"
+                },
+                if user_state.settings.scoring_settings.use_rinshan_tsumo_fu {
+                    "off"
+                } else {
+                    "on"
+                },
+                if user_state.settings.ask_limit_names {
+                    "off"
+                } else {
+                    "on"
+                },
+                if user_state.settings.strict_answer_order {
+                    "off"
+                } else {
+                    "on"
+                },
+                if user_state.settings.retry_wrong_part {
+                    "off"
+                } else {
+                    "on"
+                },
+                if user_state.settings.use_spaced_repetition {
+                    "off"
+                } else {
+                    "on"
+                },
+                if user_state.settings.use_adaptive_difficulty {
+                    "off"
+                } else {
+                    "on"
+                },
+                get_game_mode_name(user_state.settings.game_mode)
             ))
         }
         Some("/toggle_kiriage_mangan") => {
@@ -153,108 +863,353 @@ fn process_user_message(
                 }
             ));
         }
-        Some("/toggle_kazoe") => {
-            settings.scoring_settings.use_kazoe_yakuman =
-                !settings.scoring_settings.use_kazoe_yakuman;
+        Some(command @ ("/kazoe_yakuman" | "/kazoe_sanbaiman" | "/kazoe_multiple_yakuman")) => {
+            settings.scoring_settings.kazoe_yakuman = match command {
+                "/kazoe_yakuman" => KazoeYakumanRule::Yakuman,
+                "/kazoe_sanbaiman" => KazoeYakumanRule::Sanbaiman,
+                _ => KazoeYakumanRule::MultipleYakuman,
+            };
             user_state.settings_unsaved = true;
             return text_response_str(format!(
-                "Kazoe yakuman is now {}counted",
-                if settings.scoring_settings.use_kazoe_yakuman {
-                    ""
-                } else {
-                    "not "
-                }
+                "13 or more han are now counted as {}",
+                get_kazoe_yakuman_rule_name(settings.scoring_settings.kazoe_yakuman)
             ));
         }
         Some("/toggle_less_than_5_han") => {
-            settings.scoring_settings.only_less_than_5_han =
-                !settings.scoring_settings.only_less_than_5_han;
+            let filter = &mut settings.scoring_settings.generation_filter;
+            if filter.max_han <= 4 {
+                filter.max_han = MAX_GENERATED_HAN;
+            } else {
+                filter.max_han = 4;
+                filter.min_han = filter.min_han.min(4);
+            }
             user_state.settings_unsaved = true;
             return text_response_str(format!(
                 "Hands with 5 or more han are {}",
-                if settings.scoring_settings.only_less_than_5_han {
+                if filter.max_han <= 4 {
                     "disabled"
                 } else {
                     "enabled"
                 }
             ));
         }
+        Some("/drill") => {
+            return process_drill_command(user_state, message_split.collect());
+        }
+        Some("/toggle_double_wind_pair_fu") => {
+            settings.scoring_settings.use_4_fu_double_wind_pair =
+                !settings.scoring_settings.use_4_fu_double_wind_pair;
+            user_state.settings_unsaved = true;
+            return text_response_str(format!(
+                "A double wind pair is now counted as {} fu",
+                if settings.scoring_settings.use_4_fu_double_wind_pair {
+                    "4"
+                } else {
+                    "2"
+                }
+            ));
+        }
+        Some("/toggle_open_pinfu_ron_fu") => {
+            settings.scoring_settings.use_30_fu_open_pinfu_ron =
+                !settings.scoring_settings.use_30_fu_open_pinfu_ron;
+            user_state.settings_unsaved = true;
+            return text_response_str(format!(
+                "Open pinfu-shape ron is now {}rounded up to 30 fu",
+                if settings.scoring_settings.use_30_fu_open_pinfu_ron {
+                    ""
+                } else {
+                    "not "
+                }
+            ));
+        }
+        Some("/toggle_legacy_chiitoitsu") => {
+            settings.scoring_settings.use_legacy_chiitoitsu =
+                !settings.scoring_settings.use_legacy_chiitoitsu;
+            user_state.settings_unsaved = true;
+            return text_response_str(format!(
+                "Chiitoitsu is now counted as {}",
+                if settings.scoring_settings.use_legacy_chiitoitsu {
+                    "50 fu 1 han"
+                } else {
+                    "25 fu 2 han"
+                }
+            ));
+        }
+        Some("/toggle_rinshan_tsumo_fu") => {
+            settings.scoring_settings.use_rinshan_tsumo_fu =
+                !settings.scoring_settings.use_rinshan_tsumo_fu;
+            user_state.settings_unsaved = true;
+            return text_response_str(format!(
+                "Rinshan kaihou now {}gets tsumo fu",
+                if settings.scoring_settings.use_rinshan_tsumo_fu {
+                    ""
+                } else {
+                    "no longer "
+                }
+            ));
+        }
+        Some("/toggle_ask_limit_names") => {
+            settings.ask_limit_names = !settings.ask_limit_names;
+            user_state.settings_unsaved = true;
+            return text_response_str(format!(
+                "The name of limit hands is now {}asked",
+                if settings.ask_limit_names { "" } else { "not " }
+            ));
+        }
+        Some("/toggle_strict_answer_order") => {
+            settings.strict_answer_order = !settings.strict_answer_order;
+            user_state.settings_unsaved = true;
+            return text_response_str(format!(
+                "Tsumo payments are now {}",
+                if settings.strict_answer_order {
+                    "required in the official order: non-dealers first, then the dealer, and \"X all\" for dealer tsumo"
+                } else {
+                    "accepted in any order"
+                }
+            ));
+        }
+        Some("/toggle_retry_wrong_part") => {
+            settings.retry_wrong_part = !settings.retry_wrong_part;
+            user_state.settings_unsaved = true;
+            return text_response_str(format!(
+                "When only one payment of a tsumo is wrong, you can {}",
+                if settings.retry_wrong_part {
+                    "now retry just that payment"
+                } else {
+                    "no longer retry it"
+                }
+            ));
+        }
+        Some("/toggle_spaced_repetition") => {
+            settings.use_spaced_repetition = !settings.use_spaced_repetition;
+            user_state.settings_unsaved = true;
+            return text_response_str(format!(
+                "Missed hands are {}",
+                if settings.use_spaced_repetition {
+                    "now asked again after 2 minutes, 10 minutes, an hour, a day and 3 days until you answer them correctly"
+                } else {
+                    "no longer asked again"
+                }
+            ));
+        }
+        Some("/toggle_adaptive_difficulty") => {
+            settings.use_adaptive_difficulty = !settings.use_adaptive_difficulty;
+            user_state.settings_unsaved = true;
+            if !user_state.settings.use_adaptive_difficulty {
+                return text_response(
+                    "The hands are no longer chosen by difficulty, your settings and /drill are used again",
+                );
+            }
+            return text_response_str(
+                "The hands now get harder as you answer them correctly and easier when you make many mistakes\n\n".to_string()
+                    + &get_difficulty_text(user_state),
+            );
+        }
+        Some("/difficulty") => {
+            if !user_state.settings.use_adaptive_difficulty {
+                return text_response(
+                    "Adaptive difficulty is off, send /toggle_adaptive_difficulty to turn it on",
+                );
+            }
+            return text_response_str(get_difficulty_text(user_state));
+        }
+        Some("/mode") => {
+            let game_mode = match message_split.next() {
+                Some("score") => GameMode::Score,
+                Some("fu") => GameMode::Fu,
+                Some("yaku") => GameMode::Yaku,
+                Some("reverse") => GameMode::Reverse,
+                Some("choice") => GameMode::MultipleChoice,
+                Some(_) => {
+                    return text_response(
+                        "Unknown mode, use /mode score, /mode fu, /mode yaku, /mode reverse or /mode choice",
+                    )
+                }
+                None => {
+                    return text_response_str(format!(
+                        "The current mode is {}\nUse /mode score, /mode fu, /mode yaku, /mode reverse or /mode choice to change it",
+                        get_game_mode_name(settings.game_mode)
+                    ))
+                }
+            };
+            settings.game_mode = game_mode;
+            user_state.settings_unsaved = true;
+            user_state.choice_options.clear();
+            return text_response_str(format!(
+                "The mode is now {}, send /start to start a new game",
+                get_game_mode_name(game_mode)
+            ));
+        }
+        Some("/session") => {
+            let questions_count = match message_split.next() {
+                None => DEFAULT_SESSION_QUESTIONS_COUNT,
+                Some("stop") => return text_response_str(finish_session(user_state)),
+                Some(text) => match text.parse::<u32>() {
+                    Ok(count @ 1..=MAX_SESSION_QUESTIONS_COUNT) => count,
+                    _ => {
+                        return text_response_str(format!(
+                            "Send /session with the number of hands from 1 to {}, or /session stop to finish the session",
+                            MAX_SESSION_QUESTIONS_COUNT
+                        ))
+                    }
+                },
+            };
+            return start_session(user_state, questions_count, message.date.timestamp());
+        }
+        Some("/exam") => {
+            let questions_count = match message_split.next() {
+                None => DEFAULT_SESSION_QUESTIONS_COUNT,
+                Some("stop") => return text_response_str(finish_session(user_state)),
+                Some(text) => match text.parse::<u32>() {
+                    Ok(count @ 1..=MAX_SESSION_QUESTIONS_COUNT) => count,
+                    _ => {
+                        return text_response_str(format!(
+                            "Send /exam with the number of hands from 1 to {}, or /exam stop to finish the exam",
+                            MAX_SESSION_QUESTIONS_COUNT
+                        ))
+                    }
+                },
+            };
+            let start_text = format!(
+                "An exam of {} hands starts, your answers are checked only after the last one\nSend /exam stop to finish early\n\n",
+                questions_count
+            );
+            return start_exam(
+                user_state,
+                Session::new(questions_count, message.date.timestamp(), true),
+                start_text,
+            );
+        }
+        Some("/level_test") => {
+            return start_level_test(user_state, message.date.timestamp());
+        }
+        Some("/speedrun") => {
+            let kind = match message_split.next() {
+                None | Some("time") => SpeedRunKind::TimeLimit,
+                Some("count") => SpeedRunKind::QuestionCount,
+                Some(_) => return text_response("Unknown speed run, use /speedrun time or /speedrun count"),
+            };
+            return start_speed_run(user_state, kind, message.date.timestamp());
+        }
+        Some("/group") => {
+            if message.chat.is_private() {
+                return text_response(
+                    "Group games can only be played in group chats, add the bot to a group and send /group there",
+                );
+            }
+            let questions_count = match message_split.next() {
+                None => DEFAULT_QUESTIONS_COUNT,
+                Some("stop") => return finish_group_game(user_state, String::new()),
+                Some(text) => match text.parse::<u32>() {
+                    Ok(count @ 1..=MAX_QUESTIONS_COUNT) => count,
+                    _ => {
+                        return text_response_str(format!(
+                            "Send /group with the number of hands from 1 to {}, or /group stop to finish the game",
+                            MAX_QUESTIONS_COUNT
+                        ))
+                    }
+                },
+            };
+            let Some(sender) = &message.from else {
+                return text_response("Group games can only be started by users");
+            };
+            return start_group_game(user_state, questions_count, sender.id);
+        }
+        Some("/daily") => {
+            let Some(sender) = &message.from else {
+                return text_response("The daily challenge can only be taken by users");
+            };
+            return start_daily_challenge(
+                user_state,
+                daily_rankings,
+                sender,
+                get_current_timestamp(),
+            );
+        }
+        Some("/seed") => {
+            let seed = match message_split.next() {
+                None => {
+                    if let Some(seeded_hands) = &user_state.seeded_hands {
+                        return text_response_str(format!(
+                            "The hands are generated from the seed {}\nSend /seed off to get random hands again",
+                            seeded_hands.seed
+                        ));
+                    }
+                    rand::random::<u32>() as u64
+                }
+                Some("off") => {
+                    user_state.seeded_hands = None;
+                    return text_response("The hands are random again");
+                }
+                Some(text) => match text.parse::<u64>() {
+                    Ok(seed) => seed,
+                    Err(_) => {
+                        return text_response(
+                            "The seed should be a number, send /seed to get a new seed or /seed off to get random hands again",
+                        )
+                    }
+                },
+            };
+            user_state.seeded_hands = Some(SeededHands::new(seed));
+            return text_response_str(format!(
+                "The hands are now generated from the seed {}\nEveryone who sends /seed {} and then /start with the same settings gets the same hands\nSend /seed off to get random hands again",
+                seed, seed
+            ));
+        }
+        Some("/stats") => {
+            return text_response_str(get_statistics_text(user_state));
+        }
         Some("/help") => {
-            return text_response("This bot helps training score counting in riichi mahjong.\n\nSend /start to start a new game, then send the score in the format 1000 or 1000/2000 to check if it's correct.\nYou can also use space instead of \"/\".\n\nSend /settings to see and change the settings");
+            return text_response("This bot helps training score counting in riichi mahjong.\n\nSend /start to start a new game, then send the score in the format 1000, 1000/2000 or 2000 all to check if it's correct.\nYou can also use \"-\" or space instead of \"/\", and write scores like 7,700 or 7.7k.\nLimit hands can be answered with their name, e.g. mangan or 満貫.\n\nSend /mode fu or /mode yaku to practice counting fu or yaku of concrete hands instead, or /mode reverse to name the han and fu for a given score.\nSend /mode choice to answer by choosing one of four scores with buttons.\n\nSend /session 20 to answer 20 hands and get a summary of your score, time and mistakes.\nSend /exam 20 to take an exam of 20 hands where the answers are checked only at the end.\n\nSend /speedrun time to answer as many hands as possible in a minute, or /speedrun count to answer 10 hands as fast as possible.\n\nSend /daily to take the daily challenge, the hands are the same for everyone that day.\n\nIn group chats send /group to compete in answering first, /group 20 to play 20 hands or /group stop to finish early.\n\nSend /seed to get hands that can be replayed by sending the same seed, e.g. /seed 123.\n\nSend /toggle_adaptive_difficulty to start with common hands and get harder ones as you improve, and /difficulty to see your level.\n\nSend /stats to see how accurate your answers are and your level from 10 kyu to 1 dan, and /level_test to take the test for the next level.\nSend /settings to see and change the settings");
         }
         Some(_) => {}
         None => {}
     }
 
-    let Some(hand_score) = opt_hand_score else {
-        return text_response(NO_GAME_IN_PROGRESS_MESSAGE);
-    };
-
-    let mut score_parts = message_text.split(|c| c == '/' || c == ' ');
+    if user_state.group_game.is_some() {
+        return process_group_answer(user_state, message_text, message.from.as_ref());
+    }
 
-    let first_part = score_parts.next();
-    let second_part = score_parts.next();
+    if user_state.daily_challenge.is_some() {
+        return process_daily_answer(
+            user_state,
+            daily_rankings,
+            message_text,
+            message.from.as_ref(),
+        );
+    }
 
-    if let Some(others_score) = first_part {
-        let total_others = if let Ok(others_score) = others_score.parse::<u16>() {
-            others_score
-        } else {
-            return text_response("Failed to parse the score, the format is 1000 or 1000/2000");
-        };
+    if user_state
+        .session
+        .as_ref()
+        .is_some_and(|session| session.is_exam)
+    {
+        return process_exam_answer(user_state, message_text);
+    }
 
-        let total_dealer = if let Some(total_dealer) = second_part {
-            if let Ok(total_dealer) = total_dealer.parse::<u16>() {
-                total_dealer
-            } else {
-                return text_response("Failed to parse the score, format is 1000 or 1000/2000");
-            }
-        } else {
-            0
-        };
+    if user_state.speed_run.is_some() {
+        return process_speed_run_answer(user_state, message_text, message.date.timestamp());
+    }
 
-        let totals = hand_score.calculate_totals(settings.scoring_settings);
+    if settings.game_mode == GameMode::Fu {
+        return process_fu_answer(user_state, message_text);
+    }
 
-        if total_others % 100 != 0 || total_dealer % 100 != 0 {
-            return text_response("The score must be divisible by 100.\nTry again");
-        }
+    if settings.game_mode == GameMode::Reverse {
+        return process_reverse_answer(user_state, message_text);
+    }
 
-        if total_others == totals.others && total_dealer == totals.dealer
-            || total_others == totals.dealer && total_dealer == totals.others
-        {
-            text_response_str(
-                "Correct!\n\nNext hand:\n".to_string()
-                    + &generate_new_hand_text(opt_hand_score, settings),
-            )
-        } else {
-            if (totals.dealer == 0) != (total_dealer == 0) {
-                return if hand_score.ron {
-                    text_response("The format is incorrect, did you write tsumo score instead of ron?\nTry again")
-                } else if hand_score.is_dealer {
-                    text_response("The format is incorrect, did you write non-dealer score instead of dealer?\nTry again")
-                } else {
-                    text_response("The format is incorrect, a score in format 1000/2000 is expected\nTry again")
-                }
-            }
+    if settings.game_mode == GameMode::MultipleChoice {
+        return text_response(
+            "Choose one of the scores with the buttons under the question\nSend /start to get a new hand",
+        );
+    }
 
-            if totals.dealer == 0 {
-                text_response_str(
-                    format!(
-                        "Not correct.\nThe score is\n{}\n\nNext hand:\n",
-                        totals.others
-                    ) + &generate_new_hand_text(opt_hand_score, settings),
-                )
-            } else {
-                text_response_str(
-                    format!(
-                        "Not correct.\nThe score is\n{}/{}\n\nNext hand:\n",
-                        totals.others, totals.dealer
-                    ) + &generate_new_hand_text(opt_hand_score, settings),
-                )
-            }
-        }
-    } else {
-        text_response("Failed to parse score, format is 1000 or 1000/2000")
+    if settings.game_mode == GameMode::Yaku && user_state.yaku_question.is_some() {
+        return process_yaku_answer(user_state, message_text);
     }
+
+    process_score_answer(user_state, message_text)
 }
 
 fn load_translations() -> Translations {
@@ -277,6 +1232,169 @@ fn load_translations() -> Translations {
     translations
 }
 
+// the achievements are announced before the reply that earned them
+// announcements would show whether the exam answers were correct, so they wait until the end
+fn is_exam_in_progress(user_state: &UserState) -> bool {
+    user_state
+        .session
+        .as_ref()
+        .is_some_and(|session| session.is_exam)
+}
+
+fn add_difficulty_change_responses(
+    user_state: &mut UserState,
+    responses: Vec<Response>,
+) -> Vec<Response> {
+    if is_exam_in_progress(user_state) {
+        return responses;
+    }
+    let Some(change) = user_state.difficulty_change.take() else {
+        return responses;
+    };
+
+    let level = user_state.statistics.adaptive_difficulty.get_level();
+    let change_text = match change {
+        DifficultyChange::Promoted => format!(
+            "Well done, you reached the next difficulty level: {}\nNew hands: {}",
+            level.name, level.description
+        ),
+        DifficultyChange::FellBack => format!(
+            "The difficulty goes back to the level {} to practice these hands more: {}",
+            level.name, level.description
+        ),
+    };
+
+    let mut result = text_response_str(change_text);
+    result.extend(responses);
+    result
+}
+
+fn add_achievement_responses(
+    user_state: &mut UserState,
+    responses: Vec<Response>,
+) -> Vec<Response> {
+    if user_state.new_achievements.is_empty() || is_exam_in_progress(user_state) {
+        return responses;
+    }
+
+    let achievements_text = user_state
+        .new_achievements
+        .drain(..)
+        .map(|achievement| {
+            format!(
+                "New achievement: {}!\nYou managed to {}",
+                achievement.get_name(),
+                achievement.get_description()
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n\n");
+
+    let mut result = text_response_str(achievements_text);
+    result.extend(responses);
+    result
+}
+
+// in group chats everyone has their own state, only the group game is shared by the whole chat
+fn get_message_user_state_key(user_states: &UserStates, message: &Message) -> UserStateKey {
+    let chat_id = message.chat.id;
+    let Some(sender) = &message.from else {
+        return UserStateKey::Chat(chat_id);
+    };
+    if message.chat.is_private() {
+        return UserStateKey::Chat(chat_id);
+    }
+
+    let text = message.text().unwrap_or("");
+    if text.starts_with("/group") {
+        return UserStateKey::Chat(chat_id);
+    }
+    // during a group game the messages that are not commands are answers to it,
+    // the other commands are still for the member's own practice
+    let is_group_game_in_progress = user_states
+        .states
+        .get(&chat_id)
+        .is_some_and(|chat_state| chat_state.group_game.is_some());
+    if is_group_game_in_progress && !text.starts_with('/') {
+        return UserStateKey::Chat(chat_id);
+    }
+
+    UserStateKey::Member(ChatMember {
+        chat_id,
+        user_id: sender.id,
+    })
+}
+
+fn mark_unsaved_if_changed(user_states: &UserStates, user_state: &mut UserState) {
+    if user_state.settings_unsaved
+        || user_state.statistics_unsaved
+        || user_state.review_schedule_unsaved
+    {
+        user_states.mark_unsaved();
+        user_state.settings_unsaved = false;
+        user_state.statistics_unsaved = false;
+        user_state.review_schedule_unsaved = false;
+    }
+}
+
+async fn send_responses(bot: &Bot, chat_id: ChatId, responses: Vec<Response>) {
+    for response in responses {
+        let send_result = if let Some(image) = response.image {
+            let text = response.text;
+            let mut send_photo = bot.send_photo(chat_id, image);
+            if !text.is_empty() {
+                send_photo.caption = Some(text);
+            }
+            send_photo.send().await
+        } else if let Some(keyboard) = response.keyboard {
+            bot.send_message(chat_id, response.text)
+                .reply_markup(keyboard)
+                .await
+        } else {
+            bot.send_message(chat_id, response.text).await
+        };
+
+        if send_result.is_err() {
+            log::error!("Failed to send photo: {:?}", send_result.err());
+        }
+    }
+}
+
+// returns the responses and the time limited speed run if the message started one
+fn process_message(
+    user_states: &UserStates,
+    static_data: &StaticData,
+    key: UserStateKey,
+    message: &Message,
+) -> (Vec<Response>, Option<SpeedRun>) {
+    let (responses, started_speed_run, group_answers) =
+        update_user_state(user_states, key, |user_state| {
+            let previous_speed_run = user_state.speed_run;
+            let responses = process_user_message(
+                user_state,
+                message,
+                static_data,
+                &user_states.daily_rankings,
+            );
+            let responses = add_difficulty_change_responses(user_state, responses);
+            let responses = add_achievement_responses(user_state, responses);
+            mark_unsaved_if_changed(user_states, user_state);
+            let started_speed_run = user_state.speed_run.filter(|speed_run| {
+                speed_run.kind == SpeedRunKind::TimeLimit
+                    && previous_speed_run.is_none_or(|previous_speed_run| {
+                        previous_speed_run.start_time != speed_run.start_time
+                    })
+            });
+            (
+                responses,
+                started_speed_run,
+                std::mem::take(&mut user_state.group_answers),
+            )
+        });
+    record_group_answers(user_states, message.chat.id, group_answers);
+    (responses, started_speed_run)
+}
+
 pub async fn run_telegram_bot() {
     pretty_env_logger::init();
     log::info!("Starting the bot");
@@ -288,51 +1406,199 @@ pub async fn run_telegram_bot() {
     type SharedUserStates = Arc<UserStates>;
     type SharedStaticData = Arc<StaticData>;
 
+    set_generation_distribution(read_generation_distribution_from_file(Path::new(
+        GENERATION_DISTRIBUTION_PATH,
+    )));
+
     let user_states =
         SharedUserStates::new(read_user_states_from_file(Path::new(USER_STATES_PATH)));
     let static_data = SharedStaticData::new(StaticData {
         translations: load_translations(),
     });
 
-    let handler = Update::filter_message().endpoint(
-        |bot: Bot,
-         user_states: SharedUserStates,
-         static_data: SharedStaticData,
-         message: Message| async move {
-            let user_state: &mut UserState = &mut user_states
-                .states
-                .entry(message.chat.id)
-                .or_insert_with(|| get_default_user_state());
-
-            let responses = process_user_message(user_state, &message, &static_data);
-            if user_state.settings_unsaved {
-                save_single_user_state(Path::new(USER_STATES_PATH), message.chat.id, &user_state);
-                user_state.settings_unsaved = false;
+    tokio::spawn({
+        let user_states = user_states.clone();
+        async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(SAVE_INTERVAL_SECONDS));
+            loop {
+                interval.tick().await;
+                save_user_states_if_changed(&user_states, Path::new(USER_STATES_PATH));
             }
-            for response in responses {
-                let send_result = if let Some(image) = response.image {
-                    let text = response.text;
-                    let mut send_photo = bot.send_photo(message.chat.id, image);
-                    if !text.is_empty() {
-                        send_photo.caption = Some(text);
-                    }
-                    send_photo.send().await
+        }
+    });
+
+    let handler = dptree::entry()
+        .branch(Update::filter_message().endpoint(
+            |bot: Bot,
+             user_states: SharedUserStates,
+             static_data: SharedStaticData,
+             message: Message| async move {
+                let key = get_message_user_state_key(&user_states, &message);
+                let (responses, started_speed_run) =
+                    process_message(&user_states, &static_data, key, &message);
+                send_responses(&bot, message.chat.id, responses).await;
+                if let Some(speed_run) = started_speed_run {
+                    schedule_speed_run_end(
+                        bot,
+                        user_states,
+                        key,
+                        message.chat.id,
+                        speed_run.start_time,
+                    );
+                }
+                respond(())
+            },
+        ))
+        .branch(Update::filter_callback_query().endpoint(
+            |bot: Bot, user_states: SharedUserStates, query: CallbackQuery| async move {
+                if let Err(error) = bot.answer_callback_query(query.id.clone()).await {
+                    log::error!("Failed to answer callback query: {:?}", error);
+                }
+
+                let (Some(callback_data), Some(message)) = (&query.data, &query.message) else {
+                    return respond(());
+                };
+                let chat_id = message.chat().id;
+                let key = if message.chat().is_private() {
+                    UserStateKey::Chat(chat_id)
                 } else {
-                    bot.send_message(message.chat.id, response.text).await
+                    UserStateKey::Member(ChatMember {
+                        chat_id,
+                        user_id: query.from.id,
+                    })
                 };
 
-                if send_result.is_err() {
-                    log::error!("Failed to send photo: {:?}", send_result.err());
-                }
-            }
-            respond(())
-        },
-    );
+                let responses = update_user_state(&user_states, key, |user_state| {
+                    let responses = process_choice_answer(user_state, callback_data);
+                    let responses = add_difficulty_change_responses(user_state, responses);
+                    let responses = add_achievement_responses(user_state, responses);
+                    mark_unsaved_if_changed(&user_states, user_state);
+                    responses
+                });
+                send_responses(&bot, chat_id, responses).await;
+                respond(())
+            },
+        ));
 
     Dispatcher::builder(bot, handler)
         // Pass the shared state to the handler as a dependency.
         .dependencies(dptree::deps![user_states.clone(), static_data.clone()])
+        .enable_ctrlc_handler()
         .build()
         .dispatch()
         .await;
+
+    // the changes made after the last save would be lost otherwise
+    save_user_states_if_changed(&user_states, Path::new(USER_STATES_PATH));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the helpers are shared with the tests of the modes
+    pub const GROUP_CHAT_ID: ChatId = ChatId(-100);
+
+    pub fn group_message(user_id: u64, text: &str) -> Message {
+        serde_json::from_value(serde_json::json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": { "id": GROUP_CHAT_ID.0, "type": "group", "title": "Mahjong club" },
+            "from": { "id": user_id, "is_bot": false, "first_name": "Member" },
+            "text": text,
+        }))
+        .unwrap()
+    }
+
+    pub fn private_message(user_id: u64, text: &str) -> Message {
+        private_message_at(user_id, text, 0)
+    }
+
+    pub fn private_message_at(user_id: u64, text: &str, date: i64) -> Message {
+        serde_json::from_value(serde_json::json!({
+            "message_id": 1,
+            "date": date,
+            "chat": { "id": user_id, "type": "private", "first_name": "Player" },
+            "from": { "id": user_id, "is_bot": false, "first_name": "Player" },
+            "text": text,
+        }))
+        .unwrap()
+    }
+
+    pub fn send_message(user_states: &UserStates, message: &Message) -> Vec<Response> {
+        let key = get_message_user_state_key(user_states, message);
+        let static_data = StaticData {
+            translations: load_translations(),
+        };
+        process_message(user_states, &static_data, key, message).0
+    }
+
+    pub fn set_hand_score(user_states: &UserStates, chat_id: ChatId, hand_score: HandScoreData) {
+        user_states.states.get_mut(&chat_id).unwrap().hand_score = Some(hand_score);
+    }
+
+    pub fn non_dealer_ron_3900() -> HandScoreData {
+        HandScoreData {
+            han: 3,
+            fu: 30,
+            honba: 0,
+            ron: true,
+            is_dealer: false,
+        }
+    }
+
+    #[test]
+    fn test_speed_run_and_daily_answers_are_recorded() {
+        let user_states = get_default_user_states();
+        let chat_id = ChatId(16);
+        send_message(&user_states, &private_message(16, "/speedrun count"));
+        set_hand_score(&user_states, chat_id, non_dealer_ron_3900());
+        send_message(&user_states, &private_message(16, "3900"));
+
+        send_message(&user_states, &private_message(16, "/daily"));
+        send_message(&user_states, &private_message(16, "1000000"));
+
+        let user_state = user_states.states.get(&chat_id).unwrap();
+        let answers = &user_state.statistics.answers;
+        assert_eq!(answers.len(), 2);
+        assert!(answers[0].is_correct);
+        assert!(answers[0].answer_seconds.is_some());
+        assert!(!answers[1].is_correct);
+        assert!(answers[1].answer_seconds.is_some());
+    }
+
+    #[test]
+    fn test_fu_rule_toggles_change_the_settings() {
+        let user_states = get_default_user_states();
+        let responses = send_message(
+            &user_states,
+            &private_message(18, "/toggle_double_wind_pair_fu"),
+        );
+        assert_eq!(
+            responses[0].text,
+            "A double wind pair is now counted as 2 fu"
+        );
+        send_message(
+            &user_states,
+            &private_message(18, "/toggle_open_pinfu_ron_fu"),
+        );
+        let responses = send_message(
+            &user_states,
+            &private_message(18, "/toggle_legacy_chiitoitsu"),
+        );
+        assert_eq!(
+            responses[0].text,
+            "Chiitoitsu is now counted as 50 fu 1 han"
+        );
+
+        let scoring_settings = user_states
+            .states
+            .get(&ChatId(18))
+            .unwrap()
+            .settings
+            .scoring_settings;
+        assert!(!scoring_settings.use_4_fu_double_wind_pair);
+        assert!(!scoring_settings.use_30_fu_open_pinfu_ron);
+        assert!(scoring_settings.use_legacy_chiitoitsu);
+    }
 }
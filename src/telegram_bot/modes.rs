@@ -0,0 +1,16 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+// the message handling of each game mode, the commands shared by all of them stay in telegram_bot
+pub mod choice;
+pub mod daily;
+pub mod drill;
+pub mod exam;
+pub mod fu;
+pub mod group;
+pub mod level_test;
+pub mod reverse;
+pub mod score;
+pub mod session;
+pub mod speed_run;
+pub mod yaku;
@@ -0,0 +1,197 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::hand_score::HandScoreData;
+use crate::mistake_classifier::classify_mistake;
+use crate::multiple_choice::generate_choices;
+use crate::review_schedule::get_current_timestamp;
+use crate::session::Session;
+use crate::telegram_bot::modes::session::finish_session;
+use crate::telegram_bot::{
+    format_totals, get_generation_settings, get_hand_score_question_text, get_mistake_text,
+    is_exam_in_progress, record_score_answer, text_response, text_response_str, Response,
+};
+use crate::user_settings::GameMode;
+use crate::user_state::UserState;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+pub fn generate_new_choice_question(
+    user_state: &mut UserState,
+    prefix_text: String,
+) -> Vec<Response> {
+    let scoring_settings = user_state.settings.scoring_settings;
+    let hand_score = HandScoreData::generate_winning_hand(
+        &mut rand::thread_rng(),
+        get_generation_settings(user_state),
+    );
+    let choices = generate_choices(&mut rand::thread_rng(), &hand_score, scoring_settings);
+
+    // the callback data is the question id and the index of the chosen score
+    let question_id = user_state.choice_question_id.wrapping_add(1);
+    let keyboard =
+        InlineKeyboardMarkup::new(choices.chunks(2).enumerate().map(|(row_index, row)| {
+            row.iter()
+                .enumerate()
+                .map(|(index, totals)| {
+                    InlineKeyboardButton::callback(
+                        format_totals(&hand_score, totals),
+                        format!("{}:{}", question_id, row_index * 2 + index),
+                    )
+                })
+                .collect::<Vec<InlineKeyboardButton>>()
+        }));
+
+    user_state.hand_score = Some(hand_score);
+    user_state.choice_question_id = question_id;
+    user_state.choice_options = choices;
+    user_state.question_time = Some(get_current_timestamp());
+
+    [Response {
+        text: prefix_text
+            + &get_hand_score_question_text(&hand_score, false)
+            + "\n\nChoose the score",
+        image: None,
+        keyboard: Some(keyboard),
+    }]
+    .to_vec()
+}
+
+// returns the question id and the index of the chosen score
+fn parse_choice_callback_data(callback_data: &str) -> Option<(u32, usize)> {
+    let (question_id, index) = callback_data.split_once(':')?;
+    Some((question_id.parse().ok()?, index.parse().ok()?))
+}
+
+// buttons of an old question can still be pressed after switching to another game
+fn is_choice_question_active(user_state: &UserState) -> bool {
+    user_state.settings.game_mode == GameMode::MultipleChoice
+        && user_state.speed_run.is_none()
+        && user_state.daily_challenge.is_none()
+        && user_state.group_game.is_none()
+        && !is_exam_in_progress(user_state)
+}
+
+pub fn process_choice_answer(user_state: &mut UserState, callback_data: &str) -> Vec<Response> {
+    if !is_choice_question_active(user_state) {
+        return text_response("This question is no longer active");
+    }
+
+    let Some(hand_score) = user_state.hand_score else {
+        return text_response("No game is in progress, send /start to start a new game");
+    };
+
+    let Some((question_id, chosen_index)) = parse_choice_callback_data(callback_data) else {
+        return text_response("Unknown answer, send /start to get a new question");
+    };
+    let Some(chosen_totals) = user_state
+        .choice_options
+        .get(chosen_index)
+        .copied()
+        .filter(|_| question_id == user_state.choice_question_id)
+    else {
+        return text_response("This question has already been answered");
+    };
+
+    let scoring_settings = user_state.settings.scoring_settings;
+    let totals = hand_score.calculate_totals(scoring_settings);
+
+    let result_text = if chosen_totals.is_same_payment(&totals) {
+        record_score_answer(user_state, &hand_score, true, None);
+        "Correct!".to_string()
+    } else {
+        let mistake = classify_mistake(
+            &hand_score,
+            scoring_settings,
+            chosen_totals.others,
+            chosen_totals.dealer,
+            false,
+        );
+        if let Some(mistake) = mistake {
+            user_state.statistics.record_mistake(mistake);
+        }
+        record_score_answer(user_state, &hand_score, false, mistake);
+        format!(
+            "Not correct.\n{}The score is\n{}",
+            get_mistake_text(mistake, &hand_score),
+            format_totals(&hand_score, &totals)
+        )
+    };
+
+    if user_state
+        .session
+        .as_ref()
+        .is_some_and(Session::is_finished)
+    {
+        return text_response_str(result_text + "\n\n" + &finish_session(user_state));
+    }
+    generate_new_choice_question(user_state, result_text + "\n\nNext hand:\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telegram_bot::tests::{private_message, send_message};
+    use crate::user_state::{get_default_user_states, update_user_state, UserStateKey};
+    use teloxide::types::ChatId;
+
+    #[test]
+    fn test_choice_answer_is_accepted_only_once() {
+        let user_states = get_default_user_states();
+        let chat_id = ChatId(23);
+        send_message(&user_states, &private_message(23, "/mode choice"));
+        let responses = send_message(&user_states, &private_message(23, "/start"));
+        assert!(responses[0].keyboard.is_some());
+
+        let (callback_data, question_id) = {
+            let user_state = user_states.states.get(&chat_id).unwrap();
+            let hand_score = user_state.hand_score.unwrap();
+            let totals = hand_score.calculate_totals(user_state.settings.scoring_settings);
+            let correct_index = user_state
+                .choice_options
+                .iter()
+                .position(|option| option.is_same_payment(&totals))
+                .unwrap();
+            (
+                format!("{}:{}", user_state.choice_question_id, correct_index),
+                user_state.choice_question_id,
+            )
+        };
+        let answer = |callback_data: &str| {
+            update_user_state(&user_states, UserStateKey::Chat(chat_id), |user_state| {
+                process_choice_answer(user_state, callback_data)
+            })
+        };
+
+        let responses = answer(&callback_data);
+        assert!(responses[0].text.starts_with("Correct!"));
+        assert!(responses[0].keyboard.is_some());
+        let responses = answer(&callback_data);
+        assert_eq!(responses[0].text, "This question has already been answered");
+
+        let user_state = user_states.states.get(&chat_id).unwrap();
+        assert_eq!(user_state.choice_question_id, question_id + 1);
+        assert_eq!(user_state.statistics.answers.len(), 1);
+        assert!(user_state.statistics.answers[0].is_correct);
+    }
+
+    #[test]
+    fn test_old_choice_buttons_are_ignored_in_other_games() {
+        let user_states = get_default_user_states();
+        let chat_id = ChatId(12);
+        send_message(&user_states, &private_message(12, "/mode choice"));
+        send_message(&user_states, &private_message(12, "/start"));
+        let old_question_id = user_states.states.get(&chat_id).unwrap().choice_question_id;
+
+        send_message(&user_states, &private_message(12, "/exam"));
+        let exam_hand = user_states.states.get(&chat_id).unwrap().hand_score;
+        update_user_state(&user_states, UserStateKey::Chat(chat_id), |user_state| {
+            process_choice_answer(user_state, &format!("{}:0", old_question_id))
+        });
+
+        let user_state = user_states.states.get(&chat_id).unwrap();
+        assert!(user_state.choice_options.is_empty());
+        assert_eq!(user_state.hand_score, exam_hand);
+        assert!(user_state.statistics.answers.is_empty());
+        assert!(user_state.session.as_ref().unwrap().answers.is_empty());
+    }
+}
@@ -0,0 +1,203 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::answer_parser::parse_answer;
+use crate::daily_challenge::*;
+use crate::review_schedule::get_current_timestamp;
+use crate::telegram_bot::{
+    classify_answer_mistake, format_totals, get_answer_parse_error_text,
+    get_hand_score_question_text, is_correct_score_answer, record_answer_statistics,
+    stop_timed_games, take_answer_seconds, text_response, text_response_str, Response,
+};
+use crate::user_state::UserState;
+use teloxide::types::{User, UserId};
+
+fn get_daily_question_text(daily_challenge: &DailyChallenge) -> String {
+    match daily_challenge.get_current_hand() {
+        Some(hand_score) => format!(
+            "Hand {}/{}:\n{}",
+            daily_challenge.answered_count + 1,
+            daily_challenge.hands.len(),
+            get_hand_score_question_text(&hand_score, false)
+        ),
+        None => "".to_string(),
+    }
+}
+
+fn get_daily_ranking_text(daily_rankings: &DailyRankings, day: i64, user_id: UserId) -> String {
+    const SHOWN_PLACES_COUNT: usize = 10;
+    let ranking = daily_rankings.get_ranking(day);
+
+    let lines: Vec<String> = ranking
+        .iter()
+        .enumerate()
+        .filter(|(index, result)| index < &SHOWN_PLACES_COUNT || result.user_id == user_id)
+        .map(|(index, result)| {
+            format!(
+                "{}. {} - {}/{} correct, {} seconds{}",
+                index + 1,
+                result.name,
+                result.correct_count,
+                DAILY_QUESTIONS_COUNT,
+                result.seconds,
+                if result.answered_count < DAILY_QUESTIONS_COUNT as u32 {
+                    " (not finished)"
+                } else {
+                    ""
+                }
+            )
+        })
+        .collect();
+
+    format!("Today's ranking:\n{}", lines.join("\n"))
+}
+
+fn record_daily_result(
+    daily_rankings: &DailyRankings,
+    daily_challenge: &DailyChallenge,
+    sender: &User,
+    time: i64,
+) {
+    let result = daily_challenge.get_result(sender.id, sender.full_name(), time);
+    // saved together with the user states
+    daily_rankings.record_result(daily_challenge.day, result);
+}
+
+pub fn start_daily_challenge(
+    user_state: &mut UserState,
+    daily_rankings: &DailyRankings,
+    sender: &User,
+    time: i64,
+) -> Vec<Response> {
+    let day = get_day(time);
+    if let Some(daily_challenge) = &user_state.daily_challenge {
+        if daily_challenge.day == day {
+            return text_response_str(get_daily_question_text(daily_challenge));
+        }
+    }
+
+    if daily_rankings.get_result(day, sender.id).is_some() {
+        return text_response_str(format!(
+            "You have already taken today's challenge, come back tomorrow\n\n{}",
+            get_daily_ranking_text(daily_rankings, day, sender.id)
+        ));
+    }
+
+    let daily_challenge = DailyChallenge::new(time);
+    // the result is recorded right away, so the challenge can't be restarted after seeing the hands
+    record_daily_result(daily_rankings, &daily_challenge, sender, time);
+    let question_text = get_daily_question_text(&daily_challenge);
+    stop_timed_games(user_state);
+    user_state.daily_challenge = Some(daily_challenge);
+    user_state.question_time = Some(get_current_timestamp());
+
+    text_response_str(format!(
+        "Today's challenge: {} hands that are the same for everyone, answer them correctly as fast as you can\nThe challenge uses the default rules\n\n{}",
+        DAILY_QUESTIONS_COUNT, question_text
+    ))
+}
+
+pub fn process_daily_answer(
+    user_state: &mut UserState,
+    daily_rankings: &DailyRankings,
+    message_text: &str,
+    sender: Option<&User>,
+) -> Vec<Response> {
+    let Some(sender) = sender else {
+        return text_response("The daily challenge can only be taken by users");
+    };
+    let Some(daily_challenge) = &mut user_state.daily_challenge else {
+        return text_response("No daily challenge is in progress, send /daily to start one");
+    };
+    let Some(hand_score) = daily_challenge.get_current_hand() else {
+        return text_response("No daily challenge is in progress, send /daily to start one");
+    };
+
+    let answer = match parse_answer(message_text) {
+        Ok(answer) => answer,
+        Err(error) => return text_response_str(get_answer_parse_error_text(&error)),
+    };
+
+    let daily_settings = get_daily_settings();
+    let totals = hand_score.calculate_totals(daily_settings.scoring_settings);
+    let is_correct = is_correct_score_answer(&answer, &hand_score, &totals, &daily_settings, false);
+    daily_challenge.record_answer(is_correct);
+    record_daily_result(
+        daily_rankings,
+        daily_challenge,
+        sender,
+        get_current_timestamp(),
+    );
+
+    // the answer is checked with the daily rules, so the mistake and the limit are too
+    let mistake = if is_correct {
+        None
+    } else {
+        classify_answer_mistake(&answer, &hand_score, &daily_settings, false)
+    };
+    if let Some(mistake) = mistake {
+        user_state.statistics.record_mistake(mistake);
+    }
+    let answer_seconds = take_answer_seconds(user_state);
+    record_answer_statistics(
+        user_state,
+        &hand_score,
+        totals.limit,
+        is_correct,
+        mistake,
+        answer_seconds,
+    );
+    let Some(daily_challenge) = &user_state.daily_challenge else {
+        return Vec::new();
+    };
+
+    let result_text = if is_correct {
+        "Correct!".to_string()
+    } else {
+        format!(
+            "Not correct, the score is {}",
+            format_totals(&hand_score, &totals)
+        )
+    };
+
+    if daily_challenge.is_finished() {
+        let day = daily_challenge.day;
+        let correct_count = daily_challenge.correct_count;
+        user_state.daily_challenge = None;
+        return text_response_str(format!(
+            "{}\n\nThe challenge is over, {}/{} correct\n\n{}\n\nSend /start to continue the normal game",
+            result_text,
+            correct_count,
+            DAILY_QUESTIONS_COUNT,
+            get_daily_ranking_text(daily_rankings, day, sender.id)
+        ));
+    }
+
+    let question_text = get_daily_question_text(daily_challenge);
+    user_state.question_time = Some(get_current_timestamp());
+    text_response_str(format!("{}\n\n{}", result_text, question_text))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::telegram_bot::tests::{private_message, send_message};
+    use crate::user_state::get_default_user_states;
+
+    #[test]
+    fn test_daily_challenge_can_be_taken_once_a_day() {
+        let user_states = get_default_user_states();
+        let responses = send_message(&user_states, &private_message(24, "/daily"));
+        assert!(responses[0].text.starts_with("Today's challenge"));
+
+        // the same question is repeated until it is answered
+        let responses = send_message(&user_states, &private_message(24, "/daily"));
+        assert!(responses[0].text.starts_with("Hand 1/"));
+
+        send_message(&user_states, &private_message(24, "/start"));
+        let responses = send_message(&user_states, &private_message(24, "/daily"));
+        assert!(responses[0]
+            .text
+            .starts_with("You have already taken today's challenge"));
+        assert!(responses[0].text.contains("1. Player - 0/"));
+    }
+}
@@ -0,0 +1,147 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::generation_filter::*;
+use crate::hand_score::HandScoreData;
+use crate::telegram_bot::{text_response, text_response_str, Response};
+use crate::user_state::UserState;
+
+fn get_filter_parse_error_text(error: &FilterParseError) -> String {
+    const FORMAT_HINT: &str =
+        "use han, fu and honba ranges, dealer, non-dealer, ron or tsumo, e.g. 1-4 han 30-50 fu tsumo";
+    match error {
+        FilterParseError::InvalidText { text } => {
+            format!("Unknown word \"{}\", {}", text, FORMAT_HINT)
+        }
+        FilterParseError::InvalidRange { text } => {
+            format!("\"{}\" is not a valid range, {}", text, FORMAT_HINT)
+        }
+        FilterParseError::MissingUnit { text } => {
+            format!("Is \"{}\" han, fu or honba? {}", text, FORMAT_HINT)
+        }
+    }
+}
+
+pub fn process_drill_command(user_state: &mut UserState, arguments: Vec<&str>) -> Vec<Response> {
+    let settings = &mut user_state.settings;
+
+    match arguments.as_slice() {
+        [] => {
+            let drills_text = if settings.drills.is_empty() {
+                "No saved drills".to_string()
+            } else {
+                settings
+                    .drills
+                    .iter()
+                    .map(|drill| {
+                        format!("/drill {} - {}", drill.name, drill.filter.get_description())
+                    })
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            };
+            return text_response_str(format!(
+                "Now practicing: {}\n\n{}\n\n/drill 1-4 han 30-50 fu non-dealer tsumo - practice only such hands, also accepts dealer, ron and honba ranges\n/drill save name - save the current drill\n/drill delete name - delete a saved drill\n/drill off - practice all hands",
+                settings.scoring_settings.generation_filter.get_description(),
+                drills_text
+            ));
+        }
+        ["off"] => {
+            settings.scoring_settings.generation_filter = get_default_generation_filter();
+            user_state.settings_unsaved = true;
+            return text_response("Now practicing all hands");
+        }
+        ["save", name] => {
+            let filter = settings.scoring_settings.generation_filter;
+            match settings.drills.iter_mut().find(|drill| drill.name == *name) {
+                Some(drill) => drill.filter = filter,
+                None => settings.drills.push(DrillPreset {
+                    name: name.to_string(),
+                    filter,
+                }),
+            }
+            user_state.settings_unsaved = true;
+            return text_response_str(format!(
+                "Saved the drill \"{}\" ({}), send /drill {} to practice it again",
+                name,
+                filter.get_description(),
+                name
+            ));
+        }
+        ["delete", name] => {
+            let drills_count = settings.drills.len();
+            settings.drills.retain(|drill| drill.name != *name);
+            if settings.drills.len() == drills_count {
+                return text_response_str(format!("No drill named \"{}\"", name));
+            }
+            user_state.settings_unsaved = true;
+            return text_response_str(format!("Deleted the drill \"{}\"", name));
+        }
+        _ => {}
+    }
+
+    let filter = match settings
+        .drills
+        .iter()
+        .find(|drill| arguments == [drill.name.as_str()])
+    {
+        Some(drill) => drill.filter,
+        None => match parse_generation_filter(&arguments.join(" ")) {
+            Ok(filter) => filter,
+            Err(error) => return text_response_str(get_filter_parse_error_text(&error)),
+        },
+    };
+
+    let mut scoring_settings = settings.scoring_settings;
+    scoring_settings.generation_filter = filter;
+    if !HandScoreData::can_generate_hands(scoring_settings) {
+        return text_response_str(format!(
+            "There are no hands with {}, try a wider range",
+            filter.get_description()
+        ));
+    }
+
+    settings.scoring_settings = scoring_settings;
+    user_state.settings_unsaved = true;
+    text_response_str(format!(
+        "Now practicing: {}\nSend /start to get a new hand",
+        filter.get_description()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::telegram_bot::tests::{private_message, send_message};
+    use crate::user_state::get_default_user_states;
+    use teloxide::types::ChatId;
+
+    #[test]
+    fn test_saved_drill_can_be_practiced_again() {
+        let user_states = get_default_user_states();
+        let get_max_han = || {
+            user_states
+                .states
+                .get(&ChatId(25))
+                .unwrap()
+                .settings
+                .scoring_settings
+                .generation_filter
+                .max_han
+        };
+
+        let responses = send_message(&user_states, &private_message(25, "/drill 1-2 han"));
+        assert!(responses[0].text.starts_with("Now practicing"));
+        assert_eq!(get_max_han(), 2);
+        send_message(&user_states, &private_message(25, "/drill save easy"));
+        send_message(&user_states, &private_message(25, "/drill off"));
+        assert_ne!(get_max_han(), 2);
+
+        send_message(&user_states, &private_message(25, "/drill easy"));
+        assert_eq!(get_max_han(), 2);
+
+        let responses = send_message(&user_states, &private_message(25, "/drill hard"));
+        assert!(responses[0].text.starts_with("Unknown word \"hard\""));
+        send_message(&user_states, &private_message(25, "/drill delete easy"));
+        let responses = send_message(&user_states, &private_message(25, "/drill delete easy"));
+        assert_eq!(responses[0].text, "No drill named \"easy\"");
+    }
+}
@@ -0,0 +1,145 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::answer_parser::parse_answer;
+use crate::session::{Session, SessionAnswer};
+use crate::telegram_bot::modes::session::finish_session;
+use crate::telegram_bot::{
+    classify_answer_mistake, generate_new_score_hand_text, get_answer_parse_error_text,
+    get_score_instructions_text, is_correct_score_answer, is_limit_name_asked, record_score_answer,
+    stop_timed_games, text_response_str, Response,
+};
+use crate::user_state::UserState;
+
+pub fn start_exam(
+    user_state: &mut UserState,
+    session: Session,
+    start_text: String,
+) -> Vec<Response> {
+    stop_timed_games(user_state);
+    user_state.session = Some(session);
+
+    text_response_str(
+        start_text
+            + &generate_new_score_hand_text(user_state)
+            + get_score_instructions_text(&user_state.settings),
+    )
+}
+
+// the answer is only recorded, whether it is correct is shown at the end of the exam
+pub fn process_exam_answer(user_state: &mut UserState, message_text: &str) -> Vec<Response> {
+    let Some(hand_score) = user_state.hand_score else {
+        return text_response_str(finish_session(user_state));
+    };
+
+    let answer = match parse_answer(message_text) {
+        Ok(answer) => answer,
+        Err(error) => return text_response_str(get_answer_parse_error_text(&error)),
+    };
+
+    let scoring_settings = user_state.settings.scoring_settings;
+    let totals = hand_score.calculate_totals(scoring_settings);
+    let is_limit_name_asked = is_limit_name_asked(&hand_score, &user_state.settings);
+    let is_correct = is_correct_score_answer(
+        &answer,
+        &hand_score,
+        &totals,
+        &user_state.settings,
+        is_limit_name_asked,
+    );
+    let mistake = if is_correct {
+        None
+    } else {
+        classify_answer_mistake(
+            &answer,
+            &hand_score,
+            &user_state.settings,
+            is_limit_name_asked,
+        )
+    };
+    if let Some(mistake) = mistake {
+        user_state.statistics.record_mistake(mistake);
+    }
+    record_score_answer(user_state, &hand_score, is_correct, mistake);
+
+    let Some(session) = &mut user_state.session else {
+        return Vec::new();
+    };
+    session.record_answer(SessionAnswer {
+        hand: hand_score,
+        is_correct,
+        mistake,
+        answer_text: Some(message_text.trim().to_string()),
+    });
+    if session.is_finished() {
+        return text_response_str(finish_session(user_state));
+    }
+
+    let progress_text = format!(
+        "Answer recorded ({}/{})\n\nNext hand:\n",
+        session.get_answered_count(),
+        session.questions_count
+    );
+    text_response_str(progress_text + &generate_new_score_hand_text(user_state))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hand_score::HandScoreData;
+    use crate::mistake_classifier::MistakeType;
+    use crate::telegram_bot::tests::{
+        non_dealer_ron_3900, private_message, send_message, set_hand_score,
+    };
+    use crate::user_state::get_default_user_states;
+    use teloxide::types::ChatId;
+
+    #[test]
+    fn test_exam_answers_are_checked_only_at_the_end() {
+        let user_states = get_default_user_states();
+        let chat_id = ChatId(28);
+        send_message(&user_states, &private_message(28, "/exam 2"));
+
+        set_hand_score(&user_states, chat_id, non_dealer_ron_3900());
+        let responses = send_message(&user_states, &private_message(28, "3900"));
+        assert!(responses[0].text.starts_with("Answer recorded (1/2)"));
+        set_hand_score(&user_states, chat_id, non_dealer_ron_3900());
+        let responses = send_message(&user_states, &private_message(28, "2000"));
+
+        let results_text = &responses.last().unwrap().text;
+        assert!(results_text.starts_with("The exam is over, here are your answers:"));
+        assert!(results_text.contains("\n✓ 3900\n"));
+        assert!(results_text.contains("\n✗ 2000, the score is 3900\n"));
+        assert!(results_text.contains("Score: 1/2 correct (50%)"));
+    }
+
+    #[test]
+    fn test_exam_requires_the_limit_name_when_it_is_asked() {
+        let user_states = get_default_user_states();
+        let chat_id = ChatId(10);
+        send_message(
+            &user_states,
+            &private_message(10, "/toggle_ask_limit_names"),
+        );
+        send_message(&user_states, &private_message(10, "/exam"));
+
+        // 5 han non-dealer ron is mangan, 8000 points
+        let mangan_hand = HandScoreData {
+            han: 5,
+            fu: 30,
+            honba: 0,
+            ron: true,
+            is_dealer: false,
+        };
+        set_hand_score(&user_states, chat_id, mangan_hand);
+        send_message(&user_states, &private_message(10, "8000"));
+        set_hand_score(&user_states, chat_id, mangan_hand);
+        send_message(&user_states, &private_message(10, "mangan"));
+
+        let user_state = user_states.states.get(&chat_id).unwrap();
+        let answers = &user_state.session.as_ref().unwrap().answers;
+        assert_eq!(answers.len(), 2);
+        assert!(!answers[0].is_correct);
+        assert_eq!(answers[0].mistake, Some(MistakeType::WrongLimit));
+        assert!(answers[1].is_correct);
+    }
+}
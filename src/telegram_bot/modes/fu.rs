@@ -0,0 +1,86 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::fu_quiz::*;
+use crate::telegram_bot::{text_response, text_response_str, Response};
+use crate::tile_hand::TileHand;
+use crate::user_settings::UserSettings;
+use crate::user_state::UserState;
+
+pub fn generate_new_fu_question_text(
+    fu_question: &mut Option<TileHand>,
+    settings: &UserSettings,
+) -> String {
+    let hand = generate_fu_question_hand(&mut rand::thread_rng(), settings.scoring_settings);
+    let text = get_fu_question_text(&hand);
+    *fu_question = Some(hand);
+    text
+}
+
+fn get_fu_answer_parse_error_text(error: &FuAnswerParseError) -> String {
+    match error {
+        FuAnswerParseError::Empty => "No fu value found, the format is 30 or 30 fu".to_string(),
+        FuAnswerParseError::InvalidNumber { text } => {
+            format!(
+                "Failed to parse \"{}\" as a fu value, the format is 30 or 30 fu",
+                text
+            )
+        }
+    }
+}
+
+pub fn process_fu_answer(user_state: &mut UserState, message_text: &str) -> Vec<Response> {
+    let Some(hand) = &user_state.fu_question else {
+        return text_response("No game is in progress, send /start to start a new game");
+    };
+
+    let answered_fu = match parse_fu_answer(message_text) {
+        Ok(fu) => fu,
+        Err(error) => return text_response_str(get_fu_answer_parse_error_text(&error)),
+    };
+
+    let breakdown = get_fu_breakdown(hand, user_state.settings.scoring_settings);
+    let result_text = if answered_fu == breakdown.total {
+        "Correct!"
+    } else {
+        "Not correct."
+    };
+
+    text_response_str(
+        format!(
+            "{}\n{}\n\nNext hand:\n",
+            result_text,
+            get_fu_breakdown_text(&breakdown)
+        ) + &generate_new_fu_question_text(&mut user_state.fu_question, &user_state.settings),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telegram_bot::tests::{private_message, send_message};
+    use crate::user_state::get_default_user_states;
+    use teloxide::types::ChatId;
+
+    #[test]
+    fn test_fu_answer_is_checked_and_followed_by_the_next_hand() {
+        let user_states = get_default_user_states();
+        send_message(&user_states, &private_message(20, "/mode fu"));
+        send_message(&user_states, &private_message(20, "/start"));
+
+        let responses = send_message(&user_states, &private_message(20, "thirty"));
+        assert!(responses[0].text.starts_with("Failed to parse"));
+
+        let fu = {
+            let user_state = user_states.states.get(&ChatId(20)).unwrap();
+            get_fu_breakdown(
+                user_state.fu_question.as_ref().unwrap(),
+                user_state.settings.scoring_settings,
+            )
+            .total
+        };
+        let responses = send_message(&user_states, &private_message(20, &fu.to_string()));
+        assert!(responses[0].text.starts_with("Correct!"));
+        assert!(responses[0].text.contains("Next hand:"));
+    }
+}
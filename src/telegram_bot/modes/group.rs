@@ -0,0 +1,284 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::answer_parser::parse_answer;
+use crate::group_game::{GroupAnswer, GroupGame};
+use crate::hand_score::HandScoreData;
+use crate::review_schedule::get_current_timestamp;
+use crate::telegram_bot::{
+    classify_answer_mistake, format_totals, get_hand_score_question_text, is_correct_score_answer,
+    mark_unsaved_if_changed, record_answer_statistics, text_response, text_response_str, Response,
+};
+use crate::user_state::*;
+use teloxide::types::{ChatId, User, UserId};
+
+fn get_group_question_text(group_game: &GroupGame) -> String {
+    format!(
+        "Hand {}/{}:\n{}",
+        group_game.question_number,
+        group_game.questions_count,
+        get_hand_score_question_text(&group_game.hand_score, false)
+    )
+}
+
+pub fn start_group_game(
+    user_state: &mut UserState,
+    questions_count: u32,
+    started_by: UserId,
+) -> Vec<Response> {
+    if user_state
+        .group_game
+        .as_ref()
+        .is_some_and(|group_game| group_game.started_by != started_by)
+    {
+        return text_response("A game is already in progress, send /group stop to finish it");
+    }
+
+    let hand_score = HandScoreData::generate_winning_hand(
+        &mut rand::thread_rng(),
+        user_state.settings.scoring_settings,
+    );
+    let group_game = GroupGame::new(
+        hand_score,
+        questions_count,
+        started_by,
+        get_current_timestamp(),
+    );
+    let question_text = get_group_question_text(&group_game);
+    user_state.group_game = Some(group_game);
+    user_state.speed_run = None;
+    user_state.retried_payment = None;
+    user_state.choice_options.clear();
+
+    text_response_str(format!(
+        "A game of {} hands starts! The first one to answer a hand correctly gets a point\nReply to the questions with the score\n\n{}",
+        questions_count, question_text
+    ))
+}
+
+pub fn finish_group_game(user_state: &mut UserState, prefix_text: String) -> Vec<Response> {
+    let Some(group_game) = user_state.group_game.take() else {
+        return text_response("No group game is in progress, send /group to start one");
+    };
+
+    let scoreboard = group_game.get_scoreboard();
+    let scoreboard_text = if scoreboard.is_empty() {
+        "Nobody answered".to_string()
+    } else {
+        scoreboard
+            .iter()
+            .enumerate()
+            .map(|(index, member)| {
+                format!(
+                    "{}. {} - {} points, {} wrong answers",
+                    index + 1,
+                    member.name,
+                    member.points,
+                    member.wrong_answers
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+
+    text_response_str(format!(
+        "{}The game is over!\n\n{}\n\nSend /group to play again",
+        prefix_text, scoreboard_text
+    ))
+}
+
+pub fn process_group_answer(
+    user_state: &mut UserState,
+    message_text: &str,
+    sender: Option<&User>,
+) -> Vec<Response> {
+    let (Some(group_game), Some(sender)) = (&mut user_state.group_game, sender) else {
+        return Vec::new();
+    };
+
+    // other messages in the group are not answers, so they are not replied to
+    let Ok(answer) = parse_answer(message_text) else {
+        return Vec::new();
+    };
+
+    let hand_score = group_game.hand_score;
+    let totals = hand_score.calculate_totals(user_state.settings.scoring_settings);
+    let name = sender.full_name();
+    let is_correct =
+        is_correct_score_answer(&answer, &hand_score, &totals, &user_state.settings, false);
+    user_state.group_answers.push(GroupAnswer {
+        user_id: sender.id,
+        hand_score,
+        limit: totals.limit,
+        is_correct,
+        mistake: if is_correct {
+            None
+        } else {
+            classify_answer_mistake(&answer, &hand_score, &user_state.settings, false)
+        },
+        answer_seconds: (get_current_timestamp() - group_game.question_time).max(0) as u32,
+    });
+
+    if !is_correct {
+        group_game.record_wrong_answer(sender.id, &name);
+        return text_response_str(format!("{}, not correct", name));
+    }
+
+    group_game.record_correct_answer(sender.id, &name);
+    let result_text = format!(
+        "{} gets the point! The score is {}\n\n",
+        name,
+        format_totals(&hand_score, &totals)
+    );
+
+    if group_game.is_last_question() {
+        return finish_group_game(user_state, result_text);
+    }
+
+    group_game.start_next_question(
+        HandScoreData::generate_winning_hand(
+            &mut rand::thread_rng(),
+            user_state.settings.scoring_settings,
+        ),
+        get_current_timestamp(),
+    );
+    text_response_str(result_text + &get_group_question_text(group_game))
+}
+
+// the member states can't be changed while the chat state is locked by the group game
+pub fn record_group_answers(
+    user_states: &UserStates,
+    chat_id: ChatId,
+    group_answers: Vec<GroupAnswer>,
+) {
+    for group_answer in group_answers {
+        let key = UserStateKey::Member(ChatMember {
+            chat_id,
+            user_id: group_answer.user_id,
+        });
+        update_user_state(user_states, key, |member_state| {
+            if let Some(mistake) = group_answer.mistake {
+                member_state.statistics.record_mistake(mistake);
+            }
+            record_answer_statistics(
+                member_state,
+                &group_answer.hand_score,
+                group_answer.limit,
+                group_answer.is_correct,
+                group_answer.mistake,
+                Some(group_answer.answer_seconds),
+            );
+            mark_unsaved_if_changed(user_states, member_state);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::telegram_bot::tests::{
+        group_message, non_dealer_ron_3900, send_message, GROUP_CHAT_ID,
+    };
+    use crate::user_state::*;
+    use teloxide::types::UserId;
+
+    fn get_group_questions_count(user_states: &UserStates) -> Option<u32> {
+        user_states
+            .states
+            .get(&GROUP_CHAT_ID)
+            .and_then(|chat_state| {
+                chat_state
+                    .group_game
+                    .as_ref()
+                    .map(|game| game.questions_count)
+            })
+    }
+
+    #[test]
+    fn test_member_commands_keep_the_group_game() {
+        let user_states = get_default_user_states();
+        send_message(&user_states, &group_message(1, "/group 5"));
+
+        // practice commands of the members go to their own states
+        send_message(&user_states, &group_message(2, "/start"));
+        send_message(&user_states, &group_message(2, "/session"));
+        assert_eq!(get_group_questions_count(&user_states), Some(5));
+
+        // only the member who started the game can restart it
+        send_message(&user_states, &group_message(2, "/group 3"));
+        assert_eq!(get_group_questions_count(&user_states), Some(5));
+        send_message(&user_states, &group_message(1, "/group 3"));
+        assert_eq!(get_group_questions_count(&user_states), Some(3));
+
+        send_message(&user_states, &group_message(2, "/group stop"));
+        assert_eq!(get_group_questions_count(&user_states), None);
+    }
+
+    fn set_group_hand_score(user_states: &UserStates) {
+        user_states
+            .states
+            .get_mut(&GROUP_CHAT_ID)
+            .unwrap()
+            .group_game
+            .as_mut()
+            .unwrap()
+            .hand_score = non_dealer_ron_3900();
+    }
+
+    #[test]
+    fn test_group_answers_are_recorded_to_the_member_statistics() {
+        let user_states = get_default_user_states();
+        send_message(&user_states, &group_message(1, "/group 1"));
+        set_group_hand_score(&user_states);
+
+        send_message(&user_states, &group_message(2, "2000"));
+        send_message(&user_states, &group_message(3, "3900"));
+
+        let get_member_answers = |user_id: u64| {
+            let member = ChatMember {
+                chat_id: GROUP_CHAT_ID,
+                user_id: UserId(user_id),
+            };
+            user_states
+                .member_states
+                .get(&member)
+                .unwrap()
+                .statistics
+                .answers
+                .clone()
+        };
+        let wrong_answers = get_member_answers(2);
+        assert_eq!(wrong_answers.len(), 1);
+        assert!(!wrong_answers[0].is_correct);
+        let correct_answers = get_member_answers(3);
+        assert_eq!(correct_answers.len(), 1);
+        assert!(correct_answers[0].is_correct);
+        assert!(correct_answers[0].answer_seconds.is_some());
+        // the group game itself is not anyone's practice
+        assert!(user_states
+            .states
+            .get(&GROUP_CHAT_ID)
+            .unwrap()
+            .statistics
+            .answers
+            .is_empty());
+    }
+
+    #[test]
+    fn test_first_correct_answer_gets_the_point() {
+        let user_states = get_default_user_states();
+        send_message(&user_states, &group_message(1, "/group 1"));
+        set_group_hand_score(&user_states);
+
+        // other messages in the group are not answers
+        assert!(send_message(&user_states, &group_message(2, "good luck")).is_empty());
+        let responses = send_message(&user_states, &group_message(2, "2000"));
+        assert_eq!(responses[0].text, "Member, not correct");
+
+        let responses = send_message(&user_states, &group_message(3, "3900"));
+        let result_text = &responses.last().unwrap().text;
+        assert!(result_text.starts_with("Member gets the point! The score is 3900"));
+        assert!(result_text.contains("The game is over!"));
+        assert!(result_text.contains("1. Member - 1 points, 0 wrong answers"));
+        assert_eq!(get_group_questions_count(&user_states), None);
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::proficiency::*;
+use crate::review_schedule::get_current_timestamp;
+use crate::session::Session;
+use crate::telegram_bot::modes::exam::start_exam;
+use crate::telegram_bot::{text_response_str, Response};
+use crate::user_state::UserState;
+
+pub fn get_proficiency_text(user_state: &UserState) -> String {
+    let statistics = &user_state.statistics;
+    let performance = get_recent_performance(statistics);
+    let mut lines = vec![format!(
+        "Level: {}",
+        statistics
+            .proficiency_level
+            .map_or("no level yet", |index| PROFICIENCY_LEVELS[index].name)
+    )];
+
+    if let Some(index) = get_testable_level_index(statistics, &performance) {
+        lines.push(format!(
+            "You can take the test for {}, send /level_test",
+            PROFICIENCY_LEVELS[index].name
+        ));
+        return lines.join("\n");
+    }
+
+    let next_index = statistics.proficiency_level.map_or(0, |index| index + 1);
+    match PROFICIENCY_LEVELS.get(next_index) {
+        Some(next_level) => {
+            lines.push(format!("To take the test for {}:", next_level.name));
+            for requirement in next_level.get_missing_requirements(&performance) {
+                lines.push(format!("- {}", requirement));
+            }
+        }
+        None => lines.push("This is the highest level".to_string()),
+    }
+    lines.join("\n")
+}
+
+pub fn start_level_test(user_state: &mut UserState, time: i64) -> Vec<Response> {
+    let performance = get_recent_performance(&user_state.statistics);
+    let Some(level_index) = get_testable_level_index(&user_state.statistics, &performance) else {
+        return text_response_str(get_proficiency_text(user_state));
+    };
+
+    let level = &PROFICIENCY_LEVELS[level_index];
+    let mut session = Session::new(LEVEL_TEST_QUESTIONS_COUNT, time, true);
+    session.level_test = Some(level_index);
+    let start_text = format!(
+        "The test for {} starts: {} hands of all kinds{}, answers are checked only after the last one\nTo pass you need {}\nSend /exam stop to give up\n\n",
+        level.name,
+        LEVEL_TEST_QUESTIONS_COUNT,
+        if level.is_honba_tested() {
+            " with honba"
+        } else {
+            ""
+        },
+        level.get_test_requirements_text()
+    );
+    start_exam(user_state, session, start_text)
+}
+
+// promotes the user if the test is passed
+pub fn get_level_test_result_text(
+    user_state: &mut UserState,
+    session: &Session,
+    level_index: usize,
+) -> String {
+    let level = &PROFICIENCY_LEVELS[level_index];
+    let seconds = get_current_timestamp() - session.start_time;
+    if !level.is_test_passed(
+        session.get_correct_count(),
+        session.get_answered_count(),
+        seconds,
+    ) {
+        return format!(
+            "The test for {} is not passed, it needs {} for all {} hands\nKeep practicing and send /level_test to try again",
+            level.name,
+            level.get_test_requirements_text(),
+            LEVEL_TEST_QUESTIONS_COUNT
+        );
+    }
+
+    user_state.statistics.proficiency_level = Some(level_index);
+    user_state.statistics_unsaved = true;
+    format!(
+        "Congratulations, you passed the test and are promoted to {}!",
+        level.name
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telegram_bot::tests::{
+        non_dealer_ron_3900, private_message, send_message, set_hand_score,
+    };
+    use crate::user_state::get_default_user_states;
+    use teloxide::types::ChatId;
+
+    #[test]
+    fn test_passed_level_test_promotes_the_user() {
+        let user_states = get_default_user_states();
+        let chat_id = ChatId(29);
+        let responses = send_message(&user_states, &private_message(29, "/level_test"));
+        assert!(responses[0].text.starts_with("Level: no level yet"));
+
+        let answer_hands = |count: u32| {
+            let mut responses = Vec::new();
+            for _ in 0..count {
+                set_hand_score(&user_states, chat_id, non_dealer_ron_3900());
+                responses = send_message(&user_states, &private_message(29, "3900"));
+            }
+            responses
+        };
+        // enough correct practice answers for the first level
+        send_message(&user_states, &private_message(29, "/start"));
+        answer_hands(LEVEL_TEST_QUESTIONS_COUNT);
+
+        let responses = send_message(&user_states, &private_message(29, "/level_test"));
+        assert!(responses[0].text.starts_with("The test for 10 kyu starts"));
+        let responses = answer_hands(LEVEL_TEST_QUESTIONS_COUNT);
+
+        assert!(responses
+            .last()
+            .unwrap()
+            .text
+            .contains("Congratulations, you passed the test and are promoted to 10 kyu!"));
+        let user_state = user_states.states.get(&chat_id).unwrap();
+        assert_eq!(user_state.statistics.proficiency_level, Some(0));
+        assert!(user_state.session.is_none());
+    }
+}
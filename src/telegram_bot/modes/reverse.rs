@@ -0,0 +1,100 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::hand_score::HandScoreData;
+use crate::reverse_quiz::*;
+use crate::telegram_bot::{text_response, text_response_str, Response};
+use crate::user_settings::UserSettings;
+use crate::user_state::UserState;
+
+pub fn generate_new_reverse_question_text(
+    hand_score: &mut Option<HandScoreData>,
+    settings: &UserSettings,
+) -> String {
+    let score =
+        HandScoreData::generate_winning_hand(&mut rand::thread_rng(), settings.scoring_settings);
+    *hand_score = Some(score);
+    get_reverse_question_text(&score, &score.calculate_totals(settings.scoring_settings))
+}
+
+fn get_reverse_answer_parse_error_text(error: &ReverseAnswerParseError) -> String {
+    const FORMAT_HINT: &str = "the format is 3 han 40 fu, 3/40 or 6 han";
+    match error {
+        ReverseAnswerParseError::Empty => format!("No han and fu found, {}", FORMAT_HINT),
+        ReverseAnswerParseError::InvalidText { text } => {
+            format!("Failed to parse \"{}\", {}", text, FORMAT_HINT)
+        }
+        ReverseAnswerParseError::MissingHan => format!("The han is missing, {}", FORMAT_HINT),
+    }
+}
+
+pub fn process_reverse_answer(user_state: &mut UserState, message_text: &str) -> Vec<Response> {
+    let Some(hand_score) = user_state.hand_score else {
+        return text_response("No game is in progress, send /start to start a new game");
+    };
+
+    let answer = match parse_reverse_answer(message_text) {
+        Ok(answer) => answer,
+        Err(error) => return text_response_str(get_reverse_answer_parse_error_text(&error)),
+    };
+
+    if let ReverseAnswer::HanFu(han @ 0..=4, None) = answer {
+        return text_response_str(format!(
+            "Hands with {} han also need fu, e.g. {} han 30 fu\nTry again",
+            han, han
+        ));
+    }
+
+    let settings = &user_state.settings;
+    let result_text = if is_correct_reverse_answer(&hand_score, &answer, settings.scoring_settings)
+    {
+        "Correct!"
+    } else {
+        "Not correct."
+    };
+
+    text_response_str(
+        format!(
+            "{}\nPossible combinations: {}\n\nNext hand:\n",
+            result_text,
+            format_combinations(&get_matching_combinations(
+                &hand_score,
+                settings.scoring_settings
+            ))
+        ) + &generate_new_reverse_question_text(&mut user_state.hand_score, &user_state.settings),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::telegram_bot::tests::{
+        non_dealer_ron_3900, private_message, send_message, set_hand_score,
+    };
+    use crate::user_state::get_default_user_states;
+    use teloxide::types::ChatId;
+
+    #[test]
+    fn test_reverse_answer_is_checked_against_all_combinations() {
+        let user_states = get_default_user_states();
+        let chat_id = ChatId(22);
+        send_message(&user_states, &private_message(22, "/mode reverse"));
+        send_message(&user_states, &private_message(22, "/start"));
+
+        set_hand_score(&user_states, chat_id, non_dealer_ron_3900());
+        let responses = send_message(&user_states, &private_message(22, "3 han"));
+        assert!(responses[0]
+            .text
+            .starts_with("Hands with 3 han also need fu"));
+        let responses = send_message(&user_states, &private_message(22, "3 han 4 han"));
+        assert!(responses[0].text.starts_with("Failed to parse"));
+
+        // 2 han 60 fu gives the same score as 3 han 30 fu
+        let responses = send_message(&user_states, &private_message(22, "2 han 60 fu"));
+        assert!(responses[0].text.starts_with("Correct!"));
+        assert!(responses[0].text.contains("3 han 30 fu"));
+
+        set_hand_score(&user_states, chat_id, non_dealer_ron_3900());
+        let responses = send_message(&user_states, &private_message(22, "3/40"));
+        assert!(responses[0].text.starts_with("Not correct."));
+    }
+}
@@ -0,0 +1,366 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::answer_parser::{is_official_answer_form, parse_answer, ParsedAnswer};
+use crate::hand_score::{HandScoreTotals, TsumoPayment};
+use crate::mistake_classifier::{classify_mistake, MistakeType};
+use crate::telegram_bot::{
+    continue_after_score_question, finish_score_question, format_totals,
+    get_answer_parse_error_text, get_mistake_text, record_score_answer, text_response,
+    text_response_str, Response,
+};
+use crate::user_settings::UserSettings;
+use crate::user_state::UserState;
+
+pub fn process_score_answer(user_state: &mut UserState, message_text: &str) -> Vec<Response> {
+    const NO_GAME_IN_PROGRESS_MESSAGE: &str =
+        "No game is in progress, send /start to start a new game";
+    let settings = &user_state.settings;
+
+    let Some(hand_score) = user_state.hand_score else {
+        return text_response(NO_GAME_IN_PROGRESS_MESSAGE);
+    };
+
+    let totals = hand_score.calculate_totals(settings.scoring_settings);
+
+    let answer = match parse_answer(message_text) {
+        Ok(answer) => answer,
+        Err(error) => return text_response_str(get_answer_parse_error_text(&error)),
+    };
+
+    if let Some(payment) = user_state.retried_payment {
+        let ParsedAnswer::Single(answered_payment) = answer else {
+            return text_response_str(format!("Send only the {}\nTry again", payment.get_name()));
+        };
+        user_state.retried_payment = None;
+
+        // the answer was already recorded as not correct, the retry is only a feedback
+        return text_response_str(
+            format!(
+                "{}\nThe score is\n{}",
+                if answered_payment == totals.get_payment(payment) {
+                    "Now it is correct, but the first answer still counts as a mistake."
+                } else {
+                    "Not correct."
+                },
+                format_totals(&hand_score, &totals)
+            ) + &continue_after_score_question(user_state),
+        );
+    }
+
+    if let (true, Some(limit)) = (settings.ask_limit_names, totals.limit) {
+        let ParsedAnswer::Limit(answered_limit) = answer else {
+            return text_response(
+                "Answer with the name of the limit: mangan, haneman, baiman, sanbaiman or yakuman",
+            );
+        };
+
+        return if answered_limit == limit {
+            text_response_str(
+                format!(
+                    "Correct!\nThe score is\n{}",
+                    format_totals(&hand_score, &totals)
+                ) + &finish_score_question(user_state, &hand_score, true, None),
+            )
+        } else {
+            user_state
+                .statistics
+                .record_mistake(MistakeType::WrongLimit);
+            user_state.statistics_unsaved = true;
+            text_response_str(
+                format!(
+                    "Not correct.\nThe score is\n{}",
+                    format_totals(&hand_score, &totals)
+                ) + &finish_score_question(
+                    user_state,
+                    &hand_score,
+                    false,
+                    Some(MistakeType::WrongLimit),
+                ),
+            )
+        };
+    }
+
+    let (total_others, total_dealer) = match answer {
+        ParsedAnswer::Single(score) => (score, 0),
+        ParsedAnswer::Split(first, second) => (first, second),
+        ParsedAnswer::All(score) => {
+            if hand_score.ron || !hand_score.is_dealer {
+                return text_response(
+                    "The format is incorrect, \"all\" is only used for dealer tsumo\nTry again",
+                );
+            }
+            (score, 0)
+        }
+        ParsedAnswer::Limit(answered_limit) => {
+            let answered_totals = hand_score.calculate_totals_for_limit(answered_limit);
+
+            return if answered_totals.others == totals.others
+                && answered_totals.dealer == totals.dealer
+            {
+                text_response_str(
+                    format!(
+                        "Correct!\nThe score is\n{}",
+                        format_totals(&hand_score, &totals)
+                    ) + &finish_score_question(user_state, &hand_score, true, None),
+                )
+            } else {
+                let mistake = classify_mistake(
+                    &hand_score,
+                    settings.scoring_settings,
+                    answered_totals.others,
+                    answered_totals.dealer,
+                    false,
+                );
+                if let Some(mistake) = mistake {
+                    user_state.statistics.record_mistake(mistake);
+                    user_state.statistics_unsaved = true;
+                }
+                text_response_str(
+                    format!(
+                        "Not correct.\n{}{} would be {}\nThe score is\n{}",
+                        get_mistake_text(mistake, &hand_score),
+                        answered_limit.get_name(),
+                        format_totals(&hand_score, &answered_totals),
+                        format_totals(&hand_score, &totals)
+                    ) + &finish_score_question(user_state, &hand_score, false, mistake),
+                )
+            };
+        }
+    };
+
+    if total_others % 100 != 0 || total_dealer % 100 != 0 {
+        return text_response("The score must be divisible by 100.\nTry again");
+    }
+
+    let is_swapped = total_others == totals.dealer
+        && total_dealer == totals.others
+        && total_others != total_dealer;
+
+    if settings.strict_answer_order && !is_official_answer_form(&answer, &hand_score) {
+        return if hand_score.is_dealer && !hand_score.ron {
+            text_response("Dealer tsumo is called as \"X all\", e.g. 2000 all\nTry again")
+        } else {
+            text_response("Only dealer tsumo is called as \"X all\"\nTry again")
+        };
+    }
+
+    if settings.strict_answer_order && !hand_score.ron && !hand_score.is_dealer && is_swapped {
+        return text_response("The order is incorrect, non-dealers' payment goes first, then the dealer's, e.g. 1000/2000\nTry again");
+    }
+
+    if total_others == totals.others && total_dealer == totals.dealer || is_swapped {
+        let limit_text = if let Some(limit) = totals.limit {
+            format!(" That's {}.", limit.get_name())
+        } else {
+            "".to_string()
+        };
+        text_response_str(
+            format!("Correct!{}", limit_text)
+                + &finish_score_question(user_state, &hand_score, true, None),
+        )
+    } else {
+        if (totals.dealer == 0) != (total_dealer == 0) {
+            return if hand_score.ron {
+                text_response(
+                    "The format is incorrect, did you write tsumo score instead of ron?\nTry again",
+                )
+            } else if hand_score.is_dealer {
+                text_response("The format is incorrect, did you write non-dealer score instead of dealer?\nTry again")
+            } else {
+                text_response(
+                    "The format is incorrect, a score in format 1000/2000 is expected\nTry again",
+                )
+            };
+        }
+
+        let mistake = classify_mistake(
+            &hand_score,
+            settings.scoring_settings,
+            total_others,
+            total_dealer,
+            !settings.strict_answer_order,
+        );
+        if let Some(mistake) = mistake {
+            user_state.statistics.record_mistake(mistake);
+            user_state.statistics_unsaved = true;
+        }
+
+        if let Some((correct_payment, wrong_payment)) =
+            get_partially_correct_payments(total_others, total_dealer, &totals, settings)
+        {
+            let partial_text = format!(
+                "The {} is correct, but the {} is not.",
+                correct_payment.get_name(),
+                wrong_payment.get_name()
+            );
+
+            if settings.retry_wrong_part {
+                user_state.retried_payment = Some(wrong_payment);
+                record_score_answer(user_state, &hand_score, false, mistake);
+                return text_response_str(format!(
+                    "{}\nSend the {}",
+                    partial_text,
+                    wrong_payment.get_name()
+                ));
+            }
+
+            return text_response_str(
+                format!(
+                    "Not correct.\n{}\n{}The score is\n{}",
+                    partial_text,
+                    get_mistake_text(mistake, &hand_score),
+                    format_totals(&hand_score, &totals)
+                ) + &finish_score_question(user_state, &hand_score, false, mistake),
+            );
+        }
+
+        text_response_str(
+            format!(
+                "Not correct.\n{}The score is\n{}",
+                get_mistake_text(mistake, &hand_score),
+                format_totals(&hand_score, &totals)
+            ) + &finish_score_question(user_state, &hand_score, false, mistake),
+        )
+    }
+}
+
+// for a non-dealer tsumo answer with exactly one correct payment returns (correct, wrong) payments
+fn get_partially_correct_payments(
+    answered_first: u32,
+    answered_second: u32,
+    totals: &HandScoreTotals,
+    settings: &UserSettings,
+) -> Option<(TsumoPayment, TsumoPayment)> {
+    if totals.dealer == 0 {
+        return None;
+    }
+
+    let matches_in_order =
+        |non_dealer: u32, dealer: u32| (non_dealer == totals.others, dealer == totals.dealer);
+
+    // in the official order only, otherwise the order that matches better
+    let (non_dealer_correct, dealer_correct) = {
+        let in_order = matches_in_order(answered_first, answered_second);
+        let swapped = matches_in_order(answered_second, answered_first);
+        if settings.strict_answer_order || in_order.0 || in_order.1 {
+            in_order
+        } else {
+            swapped
+        }
+    };
+
+    match (non_dealer_correct, dealer_correct) {
+        (true, false) => Some((TsumoPayment::NonDealer, TsumoPayment::Dealer)),
+        (false, true) => Some((TsumoPayment::Dealer, TsumoPayment::NonDealer)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hand_score::HandScoreData;
+    use crate::mistake_classifier::MistakeType;
+    use crate::telegram_bot::tests::{
+        non_dealer_ron_3900, private_message, send_message, set_hand_score,
+    };
+    use crate::user_state::get_default_user_states;
+    use teloxide::types::ChatId;
+
+    #[test]
+    fn test_score_answer_in_a_wrong_format_can_be_retried() {
+        let user_states = get_default_user_states();
+        let chat_id = ChatId(30);
+        send_message(&user_states, &private_message(30, "/start"));
+        set_hand_score(&user_states, chat_id, non_dealer_ron_3900());
+
+        let responses = send_message(&user_states, &private_message(30, "1000/2000"));
+        assert_eq!(
+            responses[0].text,
+            "The format is incorrect, did you write tsumo score instead of ron?\nTry again"
+        );
+        let responses = send_message(&user_states, &private_message(30, "3950"));
+        assert_eq!(
+            responses[0].text,
+            "The score must be divisible by 100.\nTry again"
+        );
+        let responses = send_message(&user_states, &private_message(30, "3900"));
+        assert!(responses.last().unwrap().text.starts_with("Correct!"));
+        assert!(responses.last().unwrap().text.contains("Next hand:"));
+
+        let user_state = user_states.states.get(&chat_id).unwrap();
+        assert_eq!(user_state.statistics.answers.len(), 1);
+        assert!(user_state.statistics.answers[0].is_correct);
+    }
+
+    #[test]
+    fn test_limit_hand_is_answered_with_its_name_when_it_is_asked() {
+        let user_states = get_default_user_states();
+        let chat_id = ChatId(31);
+        send_message(
+            &user_states,
+            &private_message(31, "/toggle_ask_limit_names"),
+        );
+        send_message(&user_states, &private_message(31, "/start"));
+
+        // 6 han non-dealer ron is haneman, 12000 points
+        set_hand_score(
+            &user_states,
+            chat_id,
+            HandScoreData {
+                han: 6,
+                fu: 30,
+                honba: 0,
+                ron: true,
+                is_dealer: false,
+            },
+        );
+        let responses = send_message(&user_states, &private_message(31, "12000"));
+        assert!(responses[0]
+            .text
+            .starts_with("Answer with the name of the limit"));
+        let responses = send_message(&user_states, &private_message(31, "mangan"));
+        assert!(responses
+            .last()
+            .unwrap()
+            .text
+            .starts_with("Not correct.\nThe score is\n12000 (haneman)"));
+
+        let user_state = user_states.states.get(&chat_id).unwrap();
+        assert_eq!(
+            user_state.statistics.answers[0].mistake,
+            Some(MistakeType::WrongLimit)
+        );
+    }
+
+    #[test]
+    fn test_retried_payment_is_recorded_as_a_mistake() {
+        let user_states = get_default_user_states();
+        let chat_id = ChatId(11);
+        send_message(
+            &user_states,
+            &private_message(11, "/toggle_retry_wrong_part"),
+        );
+
+        // 3 han 30 fu non-dealer tsumo is 1000/2000
+        set_hand_score(
+            &user_states,
+            chat_id,
+            HandScoreData {
+                han: 3,
+                fu: 30,
+                honba: 0,
+                ron: false,
+                is_dealer: false,
+            },
+        );
+        send_message(&user_states, &private_message(11, "1000/2600"));
+        send_message(&user_states, &private_message(11, "2000"));
+
+        let user_state = user_states.states.get(&chat_id).unwrap();
+        assert_eq!(user_state.retried_payment, None);
+        assert_eq!(user_state.statistics.answers.len(), 1);
+        assert!(!user_state.statistics.answers[0].is_correct);
+        assert_eq!(user_state.statistics.current_streak, 0);
+    }
+}
@@ -0,0 +1,200 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::hand_score::HandScoreData;
+use crate::review_schedule::get_current_timestamp;
+use crate::session::Session;
+use crate::telegram_bot::modes::choice::generate_new_choice_question;
+use crate::telegram_bot::modes::level_test::get_level_test_result_text;
+use crate::telegram_bot::{
+    format_totals, generate_new_score_hand_text, get_hand_score_question_text, get_mistake_text,
+    get_mistake_type_name, get_score_instructions_text, stop_timed_games, text_response,
+    text_response_str, Response,
+};
+use crate::user_settings::GameMode;
+use crate::user_state::UserState;
+
+pub fn start_session(user_state: &mut UserState, questions_count: u32, time: i64) -> Vec<Response> {
+    if !matches!(
+        user_state.settings.game_mode,
+        GameMode::Score | GameMode::MultipleChoice
+    ) {
+        return text_response(
+            "Sessions can be played in the score and choice modes, send /mode score or /mode choice first",
+        );
+    }
+
+    stop_timed_games(user_state);
+    user_state.session = Some(Session::new(questions_count, time, false));
+
+    let start_text = format!(
+        "A session of {} hands starts, you get a summary after the last one\nSend /session stop to finish early\n\n",
+        questions_count
+    );
+    if user_state.settings.game_mode == GameMode::MultipleChoice {
+        return generate_new_choice_question(user_state, start_text);
+    }
+    text_response_str(
+        start_text
+            + &generate_new_score_hand_text(user_state)
+            + get_score_instructions_text(&user_state.settings),
+    )
+}
+
+fn format_duration(seconds: i64) -> String {
+    if seconds < 60 {
+        format!("{} sec", seconds)
+    } else {
+        format!("{} min {} sec", seconds / 60, seconds % 60)
+    }
+}
+
+fn format_hand(hand_score: &HandScoreData) -> String {
+    get_hand_score_question_text(hand_score, false).replace('\n', ", ")
+}
+
+// ends the session and returns its summary
+pub fn finish_session(user_state: &mut UserState) -> String {
+    let Some(session) = user_state.session.take() else {
+        return "No session or exam is in progress, send /session or /exam to start one"
+            .to_string();
+    };
+    user_state.hand_score = None;
+    user_state.choice_options.clear();
+    user_state.retried_payment = None;
+    user_state.question_time = None;
+
+    let scoring_settings = user_state.settings.scoring_settings;
+    let mut lines = Vec::new();
+
+    if session.is_exam {
+        lines.push("The exam is over, here are your answers:".to_string());
+        for (index, answer) in session.answers.iter().enumerate() {
+            let totals = answer.hand.calculate_totals(scoring_settings);
+            let answer_text = answer.answer_text.as_deref().unwrap_or("");
+            lines.push(format!("\n{}. {}", index + 1, format_hand(&answer.hand)));
+            if answer.is_correct {
+                lines.push(format!("✓ {}", answer_text));
+            } else {
+                lines.push(format!(
+                    "✗ {}, the score is {}",
+                    answer_text,
+                    format_totals(&answer.hand, &totals)
+                ));
+                if answer.mistake.is_some() {
+                    lines.push(
+                        get_mistake_text(answer.mistake, &answer.hand)
+                            .trim_end()
+                            .to_string(),
+                    );
+                }
+            }
+        }
+        lines.push("".to_string());
+    } else {
+        lines.push("The session is over".to_string());
+    }
+
+    lines.extend([
+        format!(
+            "Score: {}/{} correct ({}%)",
+            session.get_correct_count(),
+            session.get_answered_count(),
+            session.get_percent()
+        ),
+        format!(
+            "Time: {}",
+            format_duration((get_current_timestamp() - session.start_time).max(0))
+        ),
+    ]);
+
+    let mistake_counts = session.get_mistake_counts();
+    if !mistake_counts.is_empty() {
+        lines.push("\nMistakes:".to_string());
+        for (mistake, count) in mistake_counts {
+            lines.push(format!(
+                "{} - {}",
+                mistake.map_or("unrecognized mistake", get_mistake_type_name),
+                count
+            ));
+        }
+
+        // the exam results already list every hand
+        if !session.is_exam {
+            lines.push("\nMissed hands:".to_string());
+            for missed_answer in session.get_missed_answers() {
+                lines.push(format!(
+                    "{} - {}",
+                    format_hand(&missed_answer.hand),
+                    format_totals(
+                        &missed_answer.hand,
+                        &missed_answer.hand.calculate_totals(scoring_settings)
+                    )
+                ));
+            }
+        }
+    }
+
+    if let Some(level_index) = session.level_test {
+        lines.push(format!(
+            "\n{}",
+            get_level_test_result_text(user_state, &session, level_index)
+        ));
+        lines.push("\nSend /start to continue the normal game".to_string());
+        return lines.join("\n");
+    }
+
+    lines.push(if session.is_exam {
+        "\nSend /exam to take another exam or /start to continue the normal game".to_string()
+    } else {
+        "\nSend /session to start another session or /start to continue without one".to_string()
+    });
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::telegram_bot::tests::{
+        non_dealer_ron_3900, private_message, send_message, set_hand_score,
+    };
+    use crate::user_state::get_default_user_states;
+    use teloxide::types::ChatId;
+
+    #[test]
+    fn test_session_ends_with_a_summary() {
+        let user_states = get_default_user_states();
+        let chat_id = ChatId(26);
+        let responses = send_message(&user_states, &private_message(26, "/session stop"));
+        assert!(responses[0]
+            .text
+            .starts_with("No session or exam is in progress"));
+
+        send_message(&user_states, &private_message(26, "/session 2"));
+        set_hand_score(&user_states, chat_id, non_dealer_ron_3900());
+        send_message(&user_states, &private_message(26, "3900"));
+        set_hand_score(&user_states, chat_id, non_dealer_ron_3900());
+        let responses = send_message(&user_states, &private_message(26, "2000"));
+
+        let summary_text = &responses.last().unwrap().text;
+        assert!(summary_text.contains("The session is over"));
+        assert!(summary_text.contains("Score: 1/2 correct (50%)"));
+        assert!(summary_text.contains("Missed hands:\n3 han, 30 fu, non-dealer, ron - 3900"));
+        assert!(user_states.states.get(&chat_id).unwrap().session.is_none());
+    }
+
+    #[test]
+    fn test_session_is_not_started_in_the_fu_mode() {
+        let user_states = get_default_user_states();
+        send_message(&user_states, &private_message(27, "/mode fu"));
+        let responses = send_message(&user_states, &private_message(27, "/session"));
+        assert!(responses[0]
+            .text
+            .starts_with("Sessions can be played in the score and choice modes"));
+        assert!(user_states
+            .states
+            .get(&ChatId(27))
+            .unwrap()
+            .session
+            .is_none());
+    }
+}
@@ -0,0 +1,298 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::answer_parser::parse_answer;
+use crate::review_schedule::get_current_timestamp;
+use crate::speed_run::*;
+use crate::telegram_bot::{
+    classify_answer_mistake, format_totals, generate_new_hand_text, get_answer_parse_error_text,
+    is_correct_score_answer, is_limit_name_asked, mark_unsaved_if_changed, record_score_answer,
+    send_responses, stop_timed_games, text_response, text_response_str, Response,
+};
+use crate::user_state::*;
+use std::sync::Arc;
+use std::time::Duration;
+use teloxide::prelude::*;
+
+pub fn start_speed_run(
+    user_state: &mut UserState,
+    kind: SpeedRunKind,
+    start_time: i64,
+) -> Vec<Response> {
+    stop_timed_games(user_state);
+    user_state.speed_run = Some(SpeedRun::new(kind, start_time));
+
+    let rules_text = match kind {
+        SpeedRunKind::TimeLimit => format!(
+            "Answer as many hands as you can in {} seconds",
+            TIME_LIMIT_SECONDS
+        ),
+        SpeedRunKind::QuestionCount => format!(
+            "Answer {} hands as fast as you can, every wrong answer adds {} seconds",
+            QUESTIONS_COUNT, WRONG_ANSWER_PENALTY_SECONDS
+        ),
+    };
+    let best_text = match user_state.statistics.get_speed_run_best(
+        kind,
+        get_speed_run_rules(user_state.settings.scoring_settings),
+    ) {
+        Some(best) => format!(
+            "\nYour personal best with these rules: {}",
+            kind.format_result(best)
+        ),
+        None => "".to_string(),
+    };
+
+    text_response_str(
+        format!("{}{}\n\n", rules_text, best_text) + &generate_new_hand_text(user_state),
+    )
+}
+
+fn finish_speed_run(
+    user_state: &mut UserState,
+    speed_run: SpeedRun,
+    finish_time: i64,
+    prefix_text: String,
+) -> Vec<Response> {
+    let kind = speed_run.kind;
+    let result = speed_run.get_result(finish_time);
+    let scoring_settings = get_speed_run_rules(user_state.settings.scoring_settings);
+
+    let previous_best = user_state
+        .statistics
+        .get_speed_run_best(kind, scoring_settings);
+    let is_new_best = user_state
+        .statistics
+        .record_speed_run_result(kind, scoring_settings, result);
+    if is_new_best {
+        user_state.statistics_unsaved = true;
+    }
+    user_state.speed_run = None;
+    user_state.hand_score = None;
+
+    let best_text = match (is_new_best, previous_best) {
+        (true, Some(previous_best)) => format!(
+            "New personal best! The previous one was {}",
+            kind.format_result(previous_best)
+        ),
+        (true, None) => "New personal best!".to_string(),
+        (false, Some(previous_best)) => {
+            format!("Personal best: {}", kind.format_result(previous_best))
+        }
+        (false, None) => "".to_string(),
+    };
+
+    text_response_str(format!(
+        "{}The run is over: {} answered, {} correct\nResult: {}\n{}\n\nSend /speedrun {} to try again or /start to continue the normal game",
+        prefix_text,
+        speed_run.answered_count,
+        speed_run.correct_count,
+        kind.format_result(result),
+        best_text,
+        kind.get_name()
+    ))
+}
+
+// the time limited run ends at the first message after the time is up, or when it is checked
+// by the timer, whichever comes first
+pub fn finish_expired_speed_run(user_state: &mut UserState, time: i64) -> Vec<Response> {
+    match user_state.speed_run {
+        Some(speed_run) if speed_run.is_time_up(time) => {
+            finish_speed_run(user_state, speed_run, time, "Time is up!\n".to_string())
+        }
+        _ => Vec::new(),
+    }
+}
+
+pub fn process_speed_run_answer(
+    user_state: &mut UserState,
+    message_text: &str,
+    answer_time: i64,
+) -> Vec<Response> {
+    let (Some(mut speed_run), Some(hand_score)) = (user_state.speed_run, user_state.hand_score)
+    else {
+        return text_response("No game is in progress, send /start to start a new game");
+    };
+
+    let answer = match parse_answer(message_text) {
+        Ok(answer) => answer,
+        Err(error) => return text_response_str(get_answer_parse_error_text(&error)),
+    };
+
+    let totals = hand_score.calculate_totals(user_state.settings.scoring_settings);
+    let is_limit_name_asked = is_limit_name_asked(&hand_score, &user_state.settings);
+    let is_correct = is_correct_score_answer(
+        &answer,
+        &hand_score,
+        &totals,
+        &user_state.settings,
+        is_limit_name_asked,
+    );
+    speed_run.record_answer(is_correct);
+    user_state.speed_run = Some(speed_run);
+
+    let mistake = if is_correct {
+        None
+    } else {
+        classify_answer_mistake(
+            &answer,
+            &hand_score,
+            &user_state.settings,
+            is_limit_name_asked,
+        )
+    };
+    if let Some(mistake) = mistake {
+        user_state.statistics.record_mistake(mistake);
+    }
+    record_score_answer(user_state, &hand_score, is_correct, mistake);
+
+    let result_text = if is_correct {
+        "Correct!".to_string()
+    } else {
+        format!(
+            "Not correct, the score is {}",
+            format_totals(&hand_score, &totals)
+        )
+    };
+
+    if speed_run.is_finished() {
+        return finish_speed_run(
+            user_state,
+            speed_run,
+            answer_time,
+            format!("{}\n\n", result_text),
+        );
+    }
+
+    text_response_str(format!("{}\n\n", result_text) + &generate_new_hand_text(user_state))
+}
+
+// sends the results of a time limited run when the time is up, even if the user doesn't answer
+pub fn schedule_speed_run_end(
+    bot: Bot,
+    user_states: Arc<UserStates>,
+    key: UserStateKey,
+    chat_id: ChatId,
+    start_time: i64,
+) {
+    tokio::spawn(async move {
+        let end_time = start_time + TIME_LIMIT_SECONDS;
+        let delay_seconds = (end_time - get_current_timestamp()).max(0) as u64;
+        tokio::time::sleep(Duration::from_secs(delay_seconds)).await;
+
+        let responses = update_user_state(&user_states, key, |user_state| {
+            // the run can be already finished or replaced by a new one
+            if user_state
+                .speed_run
+                .is_none_or(|speed_run| speed_run.start_time != start_time)
+            {
+                return Vec::new();
+            }
+            let responses = finish_expired_speed_run(user_state, end_time);
+            mark_unsaved_if_changed(&user_states, user_state);
+            responses
+        });
+        send_responses(&bot, chat_id, responses).await;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telegram_bot::tests::{
+        non_dealer_ron_3900, private_message_at, send_message, set_hand_score,
+    };
+
+    #[test]
+    fn test_speed_run_ends_at_any_message_after_the_time_is_up() {
+        let user_states = get_default_user_states();
+        let chat_id = ChatId(13);
+        send_message(
+            &user_states,
+            &private_message_at(13, "/speedrun time", 1000),
+        );
+
+        let responses = send_message(&user_states, &private_message_at(13, "/stats", 1070));
+
+        assert!(responses[0].text.starts_with("Time is up!"));
+        assert!(responses.len() > 1);
+        let user_state = user_states.states.get(&chat_id).unwrap();
+        assert!(user_state.speed_run.is_none());
+        assert_eq!(
+            user_state.statistics.get_speed_run_best(
+                SpeedRunKind::TimeLimit,
+                get_speed_run_rules(user_state.settings.scoring_settings)
+            ),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_start_reports_the_stopped_speed_run() {
+        let user_states = get_default_user_states();
+        send_message(
+            &user_states,
+            &private_message_at(14, "/speedrun count", 1000),
+        );
+
+        let responses = send_message(&user_states, &private_message_at(14, "/start", 1010));
+
+        assert_eq!(
+            responses[0].text,
+            "The speed run is stopped, its result is not counted"
+        );
+        let user_state = user_states.states.get(&ChatId(14)).unwrap();
+        assert!(user_state.speed_run.is_none());
+        assert!(user_state.hand_score.is_some());
+    }
+
+    #[test]
+    fn test_speed_run_best_does_not_depend_on_the_drills() {
+        let user_states = get_default_user_states();
+        let chat_id = ChatId(15);
+        send_message(&user_states, &private_message_at(15, "/drill 1-4 han", 0));
+        send_message(
+            &user_states,
+            &private_message_at(15, "/speedrun time", 1000),
+        );
+        send_message(&user_states, &private_message_at(15, "/start", 1070));
+        send_message(&user_states, &private_message_at(15, "/drill off", 1080));
+
+        let user_state = user_states.states.get(&chat_id).unwrap();
+        assert_eq!(
+            user_state.statistics.get_speed_run_best(
+                SpeedRunKind::TimeLimit,
+                get_speed_run_rules(user_state.settings.scoring_settings)
+            ),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_question_count_run_adds_the_penalty_for_wrong_answers() {
+        let user_states = get_default_user_states();
+        let chat_id = ChatId(17);
+        send_message(
+            &user_states,
+            &private_message_at(17, "/speedrun count", 1000),
+        );
+
+        let mut responses = Vec::new();
+        for index in 1..=QUESTIONS_COUNT as i64 {
+            set_hand_score(&user_states, chat_id, non_dealer_ron_3900());
+            let answer = if index == 1 { "2000" } else { "3900" };
+            responses = send_message(&user_states, &private_message_at(17, answer, 1000 + index));
+        }
+
+        let result_text = &responses.last().unwrap().text;
+        assert!(result_text.contains("The run is over: 10 answered, 9 correct"));
+        assert!(result_text.contains("Result: 20 seconds"));
+        assert!(result_text.contains("New personal best!"));
+        assert!(user_states
+            .states
+            .get(&chat_id)
+            .unwrap()
+            .speed_run
+            .is_none());
+    }
+}
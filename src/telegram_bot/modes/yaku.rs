@@ -0,0 +1,128 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::review_schedule::get_current_timestamp;
+use crate::telegram_bot::{
+    get_hand_score_question_text, is_limit_name_asked, text_response, text_response_str, Response,
+};
+use crate::user_state::UserState;
+use crate::yaku::Yaku;
+use crate::yaku_quiz::*;
+
+pub fn generate_new_yaku_question_text(user_state: &mut UserState) -> String {
+    let question = generate_yaku_question(
+        &mut rand::thread_rng(),
+        user_state.settings.scoring_settings,
+    );
+    let text = get_yaku_question_text(&question);
+    user_state.yaku_question = Some(question);
+    // the score is asked only after the yaku are answered
+    user_state.hand_score = None;
+    text
+}
+
+fn get_yaku_answer_parse_error_text(error: &YakuAnswerParseError) -> String {
+    match error {
+        YakuAnswerParseError::Empty => {
+            "No yaku found, list the yaku separated by commas or send the total han".to_string()
+        }
+        YakuAnswerParseError::UnknownYaku { name } => {
+            format!("Unknown yaku \"{}\"\nTry again", name)
+        }
+    }
+}
+
+fn format_yaku_list(yaku: &[Yaku]) -> String {
+    yaku.iter()
+        .map(|yaku| yaku.get_name())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+pub fn process_yaku_answer(user_state: &mut UserState, message_text: &str) -> Vec<Response> {
+    let Some(question) = &user_state.yaku_question else {
+        return text_response("No game is in progress, send /start to start a new game");
+    };
+
+    let answer = match parse_yaku_answer(message_text) {
+        Ok(answer) => answer,
+        Err(error) => return text_response_str(get_yaku_answer_parse_error_text(&error)),
+    };
+
+    let result_text = match answer {
+        YakuAnswer::Han(han) => {
+            if han == question.evaluation.get_total_han() {
+                "Correct!".to_string()
+            } else {
+                "Not correct.".to_string()
+            }
+        }
+        YakuAnswer::Yaku(answered_yaku) => {
+            let (missed, extra) = compare_yaku(&question.evaluation.yaku, &answered_yaku);
+            let mut result_text = if missed.is_empty() && extra.is_empty() {
+                "Correct!".to_string()
+            } else {
+                "Not correct.".to_string()
+            };
+            if !missed.is_empty() {
+                result_text += &format!("\nMissed: {}", format_yaku_list(&missed));
+            }
+            if !extra.is_empty() {
+                result_text += &format!("\nThe hand doesn't have: {}", format_yaku_list(&extra));
+            }
+            result_text
+        }
+    };
+
+    let yaku_list_text = get_yaku_list_text(question, user_state.settings.scoring_settings);
+    let hand_score = question.get_hand_score();
+    user_state.yaku_question = None;
+    user_state.hand_score = Some(hand_score);
+    user_state.question_time = Some(get_current_timestamp());
+
+    text_response_str(format!(
+        "{}\n{}\n\nNow count the score of this hand:\n{}",
+        result_text,
+        yaku_list_text,
+        get_hand_score_question_text(
+            &hand_score,
+            is_limit_name_asked(&hand_score, &user_state.settings)
+        )
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::telegram_bot::tests::{private_message, send_message};
+    use crate::user_state::get_default_user_states;
+    use teloxide::types::ChatId;
+
+    #[test]
+    fn test_yaku_answer_is_followed_by_the_score_question() {
+        let user_states = get_default_user_states();
+        send_message(&user_states, &private_message(21, "/mode yaku"));
+        send_message(&user_states, &private_message(21, "/start"));
+
+        let responses = send_message(&user_states, &private_message(21, "not a yaku"));
+        assert!(responses[0].text.starts_with("Unknown yaku"));
+
+        let han = user_states
+            .states
+            .get(&ChatId(21))
+            .unwrap()
+            .yaku_question
+            .as_ref()
+            .unwrap()
+            .evaluation
+            .get_total_han();
+        let responses = send_message(&user_states, &private_message(21, &han.to_string()));
+        assert!(responses[0].text.starts_with("Correct!"));
+        assert!(responses[0]
+            .text
+            .contains("Now count the score of this hand"));
+
+        let user_state = user_states.states.get(&ChatId(21)).unwrap();
+        assert!(user_state.yaku_question.is_none());
+        assert!(user_state.hand_score.is_some());
+    }
+}
@@ -0,0 +1,514 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::fu_calculator::{
+    calculate_fu, FuBreakdown, FuHandComposition, FuMeld, MeldKind, WaitKind,
+};
+use crate::hand_score::ScoringSettings;
+use crate::tiles::*;
+use rand::Rng;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HandMeld {
+    pub kind: MeldKind,
+    // the lowest tile of the meld
+    pub tile: Tile,
+    pub is_open: bool,
+}
+
+// a complete winning hand as it is seen on the table
+#[derive(Debug, Clone)]
+pub struct TileHand {
+    // called melds and closed quads
+    pub declared_melds: Vec<HandMeld>,
+    // tiles in the hand without the winning tile
+    pub concealed_tiles: Vec<Tile>,
+    pub winning_tile: Tile,
+    pub is_ron: bool,
+    pub is_rinshan: bool,
+    pub seat_wind: Tile,
+    pub round_wind: Tile,
+}
+
+// one of the possible ways to split a winning hand into melds
+#[derive(Debug, Clone)]
+pub struct HandInterpretation {
+    // all melds of the hand including the declared ones, empty for chiitoitsu
+    pub melds: Vec<HandMeld>,
+    // one pair for normal hands, seven pairs for chiitoitsu
+    pub pairs: Vec<Tile>,
+    // index of the meld that was completed by the winning tile, None if it was the pair
+    pub winning_meld_index: Option<usize>,
+    pub wait: WaitKind,
+}
+
+impl HandMeld {
+    pub fn get_tiles(&self) -> Vec<Tile> {
+        match self.kind {
+            MeldKind::Sequence => vec![self.tile, self.tile.get_next(1), self.tile.get_next(2)],
+            MeldKind::Triplet => vec![self.tile; 3],
+            MeldKind::Quad => vec![self.tile; 4],
+        }
+    }
+
+    fn to_fu_meld(self) -> FuMeld {
+        FuMeld {
+            kind: self.kind,
+            is_open: self.is_open,
+            is_terminal_or_honor: self.tile.is_terminal_or_honor(),
+        }
+    }
+}
+
+impl HandInterpretation {
+    pub fn is_chiitoitsu(&self) -> bool {
+        self.pairs.len() == 7
+    }
+}
+
+impl TileHand {
+    pub fn get_all_tiles(&self) -> Vec<Tile> {
+        let mut tiles = self.concealed_tiles.clone();
+        tiles.push(self.winning_tile);
+        for meld in &self.declared_melds {
+            tiles.extend(meld.get_tiles());
+        }
+        tiles
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.declared_melds.iter().all(|meld| !meld.is_open)
+    }
+
+    fn get_concealed_tile_counts(&self) -> [u8; TILE_KINDS_COUNT] {
+        let mut counts = [0; TILE_KINDS_COUNT];
+        for tile in self
+            .concealed_tiles
+            .iter()
+            .chain([self.winning_tile].iter())
+        {
+            counts[tile.get_index()] += 1;
+        }
+        counts
+    }
+
+    pub fn get_interpretations(&self) -> Vec<HandInterpretation> {
+        let mut counts = self.get_concealed_tile_counts();
+        let mut interpretations = Vec::new();
+
+        if self.declared_melds.is_empty() && counts.iter().filter(|count| **count == 2).count() == 7
+        {
+            interpretations.push(HandInterpretation {
+                melds: Vec::new(),
+                pairs: (0..TILE_KINDS_COUNT)
+                    .filter(|index| counts[*index] == 2)
+                    .map(Tile::from_index)
+                    .collect(),
+                winning_meld_index: None,
+                wait: WaitKind::Tanki,
+            });
+        }
+
+        let sets_needed = 4 - self.declared_melds.len();
+        for pair_index in 0..TILE_KINDS_COUNT {
+            if counts[pair_index] < 2 {
+                continue;
+            }
+            counts[pair_index] -= 2;
+            let mut decompositions = Vec::new();
+            find_concealed_sets(
+                &mut counts,
+                sets_needed,
+                &mut Vec::new(),
+                &mut decompositions,
+            );
+            counts[pair_index] += 2;
+
+            let pair = Tile::from_index(pair_index);
+            for concealed_sets in decompositions {
+                let mut melds = self.declared_melds.clone();
+                let first_concealed_index = melds.len();
+                melds.extend(concealed_sets);
+
+                if pair == self.winning_tile {
+                    interpretations.push(HandInterpretation {
+                        melds: melds.clone(),
+                        pairs: vec![pair],
+                        winning_meld_index: None,
+                        wait: WaitKind::Tanki,
+                    });
+                }
+
+                for meld_index in first_concealed_index..melds.len() {
+                    if let Some(wait) = get_wait_kind(&melds[meld_index], self.winning_tile) {
+                        interpretations.push(HandInterpretation {
+                            melds: melds.clone(),
+                            pairs: vec![pair],
+                            winning_meld_index: Some(meld_index),
+                            wait,
+                        });
+                    }
+                }
+            }
+        }
+
+        interpretations
+    }
+
+    // 0 for a simple pair, 1 for a dragon or a seat/round wind, 2 for a double wind
+    fn get_pair_yakuhai_count(&self, pair: Tile) -> u8 {
+        if pair.is_dragon() {
+            1
+        } else {
+            (pair == self.seat_wind) as u8 + (pair == self.round_wind) as u8
+        }
+    }
+
+    pub fn get_fu_composition(&self, interpretation: &HandInterpretation) -> FuHandComposition {
+        FuHandComposition {
+            melds: interpretation
+                .melds
+                .iter()
+                .map(|meld| meld.to_fu_meld())
+                .collect(),
+            winning_meld_index: interpretation.winning_meld_index,
+            pair_yakuhai_count: if interpretation.is_chiitoitsu() {
+                0
+            } else {
+                self.get_pair_yakuhai_count(interpretation.pairs[0])
+            },
+            wait: interpretation.wait,
+            is_ron: self.is_ron,
+            is_rinshan: self.is_rinshan,
+            is_chiitoitsu: interpretation.is_chiitoitsu(),
+        }
+    }
+
+    // returns fu breakdowns of every possible interpretation of the hand
+    pub fn calculate_possible_fu(&self, settings: ScoringSettings) -> Vec<FuBreakdown> {
+        self.get_interpretations()
+            .iter()
+            .map(|interpretation| calculate_fu(&self.get_fu_composition(interpretation), settings))
+            .collect()
+    }
+
+    pub fn format_hand(&self) -> String {
+        let mut result = format_tiles(&self.concealed_tiles);
+        for meld in &self.declared_melds {
+            let tiles = format_tiles(&meld.get_tiles());
+            if meld.is_open {
+                result += &format!(" [{}]", tiles);
+            } else {
+                result += &format!(" ({})", tiles);
+            }
+        }
+        result += &format!(
+            "\n{} {}",
+            if self.is_ron { "Ron on" } else { "Tsumo" },
+            self.winning_tile.get_name()
+        );
+        if self.is_rinshan {
+            result += " (rinshan kaihou)";
+        }
+        result
+    }
+}
+
+fn get_wait_kind(meld: &HandMeld, winning_tile: Tile) -> Option<WaitKind> {
+    match meld.kind {
+        MeldKind::Triplet if meld.tile == winning_tile => Some(WaitKind::Shanpon),
+        MeldKind::Sequence if meld.tile.suit == winning_tile.suit => {
+            match winning_tile.value as i32 - meld.tile.value as i32 {
+                // waiting on 3 with 12 or on 7 with 89
+                0 if meld.tile.value == 7 => Some(WaitKind::Penchan),
+                2 if meld.tile.value == 1 => Some(WaitKind::Penchan),
+                0 | 2 => Some(WaitKind::Ryanmen),
+                1 => Some(WaitKind::Kanchan),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn find_concealed_sets(
+    counts: &mut [u8; TILE_KINDS_COUNT],
+    sets_needed: usize,
+    current_sets: &mut Vec<HandMeld>,
+    result: &mut Vec<Vec<HandMeld>>,
+) {
+    // the lowest remaining tile has to be the start of a set
+    let Some(index) = counts.iter().position(|count| *count > 0) else {
+        if sets_needed == 0 {
+            result.push(current_sets.clone());
+        }
+        return;
+    };
+    if sets_needed == 0 {
+        return;
+    }
+
+    let tile = Tile::from_index(index);
+
+    if counts[index] >= 3 {
+        counts[index] -= 3;
+        current_sets.push(HandMeld {
+            kind: MeldKind::Triplet,
+            tile,
+            is_open: false,
+        });
+        find_concealed_sets(counts, sets_needed - 1, current_sets, result);
+        current_sets.pop();
+        counts[index] += 3;
+    }
+
+    if !tile.is_honor() && tile.value <= 7 && counts[index + 1] > 0 && counts[index + 2] > 0 {
+        for offset in 0..3 {
+            counts[index + offset] -= 1;
+        }
+        current_sets.push(HandMeld {
+            kind: MeldKind::Sequence,
+            tile,
+            is_open: false,
+        });
+        find_concealed_sets(counts, sets_needed - 1, current_sets, result);
+        current_sets.pop();
+        for offset in 0..3 {
+            counts[index + offset] += 1;
+        }
+    }
+}
+
+fn generate_random_meld<R: Rng>(rng: &mut R, is_open_hand: bool) -> HandMeld {
+    let kind_roll = rng.gen::<f32>();
+    let kind = if kind_roll < 0.6 {
+        MeldKind::Sequence
+    } else if kind_roll < 0.93 {
+        MeldKind::Triplet
+    } else {
+        MeldKind::Quad
+    };
+
+    let tile = if kind == MeldKind::Sequence {
+        let suit = [Suit::Man, Suit::Pin, Suit::Sou][rng.gen_range(0..3)];
+        Tile::new(suit, rng.gen_range(1..=7))
+    } else {
+        Tile::from_index(rng.gen_range(0..TILE_KINDS_COUNT))
+    };
+
+    HandMeld {
+        kind,
+        tile,
+        is_open: is_open_hand && rng.gen_bool(0.5),
+    }
+}
+
+fn try_generate_standard_hand<R: Rng>(rng: &mut R) -> Option<TileHand> {
+    let is_open_hand = rng.gen_bool(0.4);
+    let melds: Vec<HandMeld> = (0..4)
+        .map(|_| generate_random_meld(rng, is_open_hand))
+        .collect();
+    let pair = Tile::from_index(rng.gen_range(0..TILE_KINDS_COUNT));
+
+    let mut counts = [0u8; TILE_KINDS_COUNT];
+    for tile in melds.iter().flat_map(|meld| meld.get_tiles()) {
+        counts[tile.get_index()] += 1;
+    }
+    counts[pair.get_index()] += 2;
+    if counts.iter().any(|count| *count > 4) {
+        return None;
+    }
+
+    // quads are always declared, even if they are closed
+    let (declared_melds, concealed_melds): (Vec<HandMeld>, Vec<HandMeld>) = melds
+        .into_iter()
+        .partition(|meld| meld.is_open || meld.kind == MeldKind::Quad);
+
+    // the pair is represented by None
+    let winning_meld_index = rng.gen_range(0..=concealed_melds.len());
+    let winning_tile = match concealed_melds.get(winning_meld_index) {
+        Some(meld) => meld.get_tiles()[rng.gen_range(0..3)],
+        None => pair,
+    };
+
+    let mut concealed_tiles: Vec<Tile> = concealed_melds
+        .iter()
+        .flat_map(|meld| meld.get_tiles())
+        .collect();
+    concealed_tiles.extend([pair, pair]);
+    let winning_tile_position = concealed_tiles
+        .iter()
+        .position(|tile| *tile == winning_tile)?;
+    concealed_tiles.remove(winning_tile_position);
+
+    let is_ron = rng.gen_bool(0.5);
+    let has_quad = declared_melds
+        .iter()
+        .any(|meld| meld.kind == MeldKind::Quad);
+
+    Some(TileHand {
+        declared_melds,
+        concealed_tiles,
+        winning_tile,
+        is_ron,
+        is_rinshan: !is_ron && has_quad && rng.gen_bool(0.3),
+        seat_wind: Tile::new(Suit::Honor, rng.gen_range(1..=4)),
+        round_wind: Tile::new(Suit::Honor, rng.gen_range(1..=2)),
+    })
+}
+
+fn generate_chiitoitsu_hand<R: Rng>(rng: &mut R) -> TileHand {
+    let mut pair_indices: Vec<usize> = Vec::new();
+    while pair_indices.len() < 7 {
+        let index = rng.gen_range(0..TILE_KINDS_COUNT);
+        if !pair_indices.contains(&index) {
+            pair_indices.push(index);
+        }
+    }
+
+    let winning_tile = Tile::from_index(pair_indices[rng.gen_range(0..7)]);
+    let mut concealed_tiles: Vec<Tile> = pair_indices
+        .iter()
+        .flat_map(|index| [Tile::from_index(*index); 2])
+        .collect();
+    let winning_tile_position = concealed_tiles
+        .iter()
+        .position(|tile| *tile == winning_tile)
+        .unwrap();
+    concealed_tiles.remove(winning_tile_position);
+
+    TileHand {
+        declared_melds: Vec::new(),
+        concealed_tiles,
+        winning_tile,
+        is_ron: rng.gen_bool(0.5),
+        is_rinshan: false,
+        seat_wind: Tile::new(Suit::Honor, rng.gen_range(1..=4)),
+        round_wind: Tile::new(Suit::Honor, rng.gen_range(1..=2)),
+    }
+}
+
+// generates a random winning hand that has only one possible fu value
+pub fn generate_random_tile_hand<R: Rng>(rng: &mut R, settings: ScoringSettings) -> TileHand {
+    loop {
+        let hand = if rng.gen_bool(0.05) {
+            Some(generate_chiitoitsu_hand(rng))
+        } else {
+            try_generate_standard_hand(rng)
+        };
+
+        let Some(hand) = hand else {
+            continue;
+        };
+
+        let possible_fu = hand.calculate_possible_fu(settings);
+        if let Some(first_fu) = possible_fu.first() {
+            if possible_fu.iter().all(|fu| fu.total == first_fu.total) {
+                return hand;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user_settings::get_default_settings;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn closed_hand(concealed: &str, winning_tile: &str, is_ron: bool) -> TileHand {
+        TileHand {
+            declared_melds: Vec::new(),
+            concealed_tiles: parse_tiles(concealed).unwrap(),
+            winning_tile: parse_tiles(winning_tile).unwrap()[0],
+            is_ron,
+            is_rinshan: false,
+            seat_wind: Tile::new(Suit::Honor, 2),
+            round_wind: Tile::new(Suit::Honor, 1),
+        }
+    }
+
+    fn get_possible_fu_totals(hand: &TileHand) -> Vec<u8> {
+        let mut totals: Vec<u8> = hand
+            .calculate_possible_fu(get_default_settings().scoring_settings)
+            .iter()
+            .map(|fu| fu.total)
+            .collect();
+        totals.sort();
+        totals.dedup();
+        totals
+    }
+
+    #[test]
+    fn test_pinfu_hand_fu() {
+        assert_eq!(
+            get_possible_fu_totals(&closed_hand("23m456p789s123s55p", "4m", false)),
+            vec![20]
+        );
+        assert_eq!(
+            get_possible_fu_totals(&closed_hand("23m456p789s123s55p", "4m", true)),
+            vec![30]
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_wait_has_several_fu_values() {
+        // 5m can complete a ryanmen 34m or a kanchan 46m
+        assert_eq!(
+            get_possible_fu_totals(&closed_hand("34456m456p789s55p", "5m", false)),
+            vec![20, 30]
+        );
+    }
+
+    #[test]
+    fn test_chiitoitsu_is_recognized() {
+        let hand = closed_hand("1133m2255p4477s1z", "1z", true);
+        let interpretations = hand.get_interpretations();
+        assert_eq!(interpretations.len(), 1);
+        assert!(interpretations[0].is_chiitoitsu());
+        assert_eq!(get_possible_fu_totals(&hand), vec![25]);
+    }
+
+    #[test]
+    fn test_declared_melds_are_counted() {
+        let mut hand = closed_hand("23m456p55p", "4m", true);
+        hand.declared_melds = vec![
+            HandMeld {
+                kind: MeldKind::Triplet,
+                tile: Tile::new(Suit::Honor, 5),
+                is_open: true,
+            },
+            HandMeld {
+                kind: MeldKind::Quad,
+                tile: Tile::new(Suit::Sou, 2),
+                is_open: false,
+            },
+        ];
+        // 20 + 4 open honor triplet + 16 closed simple quad
+        assert_eq!(get_possible_fu_totals(&hand), vec![40]);
+    }
+
+    #[test]
+    fn test_generated_hands_are_complete_and_unambiguous() {
+        let settings = get_default_settings().scoring_settings;
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..1000 {
+            let hand = generate_random_tile_hand(&mut rng, settings);
+            let tiles = hand.get_all_tiles();
+            let quads_count = hand
+                .declared_melds
+                .iter()
+                .filter(|meld| meld.kind == MeldKind::Quad)
+                .count();
+            assert_eq!(tiles.len(), 14 + quads_count);
+
+            let mut counts = [0; TILE_KINDS_COUNT];
+            for tile in tiles {
+                counts[tile.get_index()] += 1;
+            }
+            assert!(counts.iter().all(|count| *count <= 4));
+            assert_eq!(get_possible_fu_totals(&hand).len(), 1);
+        }
+    }
+}
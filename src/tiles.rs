@@ -0,0 +1,209 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Suit {
+    Man,
+    Pin,
+    Sou,
+    Honor,
+}
+
+// honors are numbered as in the mpsz notation:
+// 1-4 are east, south, west and north winds, 5-7 are white, green and red dragons
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Tile {
+    pub suit: Suit,
+    pub value: u8,
+}
+
+pub const TILE_KINDS_COUNT: usize = 34;
+
+impl Tile {
+    pub fn new(suit: Suit, value: u8) -> Tile {
+        Tile { suit, value }
+    }
+
+    pub fn from_index(index: usize) -> Tile {
+        match index {
+            0..=8 => Tile::new(Suit::Man, index as u8 + 1),
+            9..=17 => Tile::new(Suit::Pin, index as u8 - 8),
+            18..=26 => Tile::new(Suit::Sou, index as u8 - 17),
+            _ => Tile::new(Suit::Honor, index as u8 - 26),
+        }
+    }
+
+    pub fn get_index(&self) -> usize {
+        let suit_offset = match self.suit {
+            Suit::Man => 0,
+            Suit::Pin => 9,
+            Suit::Sou => 18,
+            Suit::Honor => 27,
+        };
+        suit_offset + self.value as usize - 1
+    }
+
+    pub fn is_honor(&self) -> bool {
+        self.suit == Suit::Honor
+    }
+
+    pub fn is_terminal_or_honor(&self) -> bool {
+        self.is_honor() || self.value == 1 || self.value == 9
+    }
+
+    pub fn is_dragon(&self) -> bool {
+        self.is_honor() && self.value >= 5
+    }
+
+    pub fn is_wind(&self) -> bool {
+        self.is_honor() && self.value <= 4
+    }
+
+    // the tile that is the given number of steps higher in the same suit
+    pub fn get_next(&self, steps: u8) -> Tile {
+        Tile::new(self.suit, self.value + steps)
+    }
+
+    // winds and dragons are looped separately, e.g. north indicates east
+    pub fn get_dora_from_indicator(&self) -> Tile {
+        let value = match (self.suit, self.value) {
+            (Suit::Honor, 4) => 1,
+            (Suit::Honor, 7) => 5,
+            (Suit::Honor, value) => value + 1,
+            (_, value) => value % 9 + 1,
+        };
+        Tile::new(self.suit, value)
+    }
+
+    pub fn get_suit_letter(&self) -> char {
+        get_suit_letter(self.suit)
+    }
+
+    pub fn get_name(&self) -> String {
+        format!("{}{}", self.value, self.get_suit_letter())
+    }
+
+    pub fn get_honor_name(&self) -> &'static str {
+        match (self.suit, self.value) {
+            (Suit::Honor, 1) => "east",
+            (Suit::Honor, 2) => "south",
+            (Suit::Honor, 3) => "west",
+            (Suit::Honor, 4) => "north",
+            (Suit::Honor, 5) => "white dragon",
+            (Suit::Honor, 6) => "green dragon",
+            (Suit::Honor, 7) => "red dragon",
+            _ => "",
+        }
+    }
+}
+
+fn get_suit_letter(suit: Suit) -> char {
+    match suit {
+        Suit::Man => 'm',
+        Suit::Pin => 'p',
+        Suit::Sou => 's',
+        Suit::Honor => 'z',
+    }
+}
+
+// formats tiles in the mpsz notation, e.g. 123m456p77z
+pub fn format_tiles(tiles: &[Tile]) -> String {
+    let mut sorted_tiles = tiles.to_vec();
+    sorted_tiles.sort();
+
+    let mut result = String::new();
+    for (index, tile) in sorted_tiles.iter().enumerate() {
+        result.push((b'0' + tile.value) as char);
+        let is_last_of_suit = sorted_tiles
+            .get(index + 1)
+            .is_none_or(|next_tile| next_tile.suit != tile.suit);
+        if is_last_of_suit {
+            result.push(tile.get_suit_letter());
+        }
+    }
+    result
+}
+
+// explains which honor tiles are used among the given tiles, e.g. "1z - east, 5z - white dragon"
+pub fn get_honor_legend_text(tiles: &[Tile]) -> String {
+    let mut honors: Vec<Tile> = tiles
+        .iter()
+        .copied()
+        .filter(|tile| tile.is_honor())
+        .collect();
+    honors.sort();
+    honors.dedup();
+    honors
+        .iter()
+        .map(|tile| format!("{} - {}", tile.get_name(), tile.get_honor_name()))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+// parses tiles in the mpsz notation, e.g. 123m456p77z
+#[cfg(test)]
+pub fn parse_tiles(text: &str) -> Option<Vec<Tile>> {
+    let mut tiles = Vec::new();
+    let mut pending_values = Vec::new();
+
+    for c in text.chars().filter(|c| !c.is_whitespace()) {
+        if let Some(value) = c.to_digit(10) {
+            pending_values.push(value as u8);
+            continue;
+        }
+
+        let suit = match c {
+            'm' => Suit::Man,
+            'p' => Suit::Pin,
+            's' => Suit::Sou,
+            'z' => Suit::Honor,
+            _ => return None,
+        };
+        let max_value = if suit == Suit::Honor { 7 } else { 9 };
+        if pending_values.is_empty()
+            || pending_values
+                .iter()
+                .any(|value| *value < 1 || *value > max_value)
+        {
+            return None;
+        }
+        tiles.extend(pending_values.drain(..).map(|value| Tile::new(suit, value)));
+    }
+
+    if pending_values.is_empty() {
+        Some(tiles)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tile_index_roundtrip() {
+        for index in 0..TILE_KINDS_COUNT {
+            assert_eq!(Tile::from_index(index).get_index(), index);
+        }
+    }
+
+    #[test]
+    fn test_format_and_parse_tiles() {
+        let tiles = parse_tiles("312m 55p 7z").unwrap();
+        assert_eq!(format_tiles(&tiles), "123m55p7z");
+        assert_eq!(parse_tiles("123"), None);
+        assert_eq!(parse_tiles("8z"), None);
+        assert_eq!(parse_tiles("0m"), None);
+    }
+
+    #[test]
+    fn test_dora_from_indicator() {
+        let indicators = parse_tiles("9m5p4z7z3z").unwrap();
+        let dora: Vec<Tile> = indicators
+            .iter()
+            .map(|tile| tile.get_dora_from_indicator())
+            .collect();
+        assert_eq!(dora, parse_tiles("1m6p1z5z4z").unwrap());
+    }
+}
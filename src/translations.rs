@@ -6,6 +6,7 @@ use std::collections::HashMap;
 
 pub type Translations = HashMap<String, HashMap<&'static str, &'static str>>;
 
+#[allow(dead_code)]
 pub fn translate(
     key: &str,
     translations: &Translations,
@@ -1,13 +1,30 @@
 // Copyright (C) Pavel Grebnev 2024
 // Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
 
-use crate::hand_score::ScoringSettings;
+use crate::generation_filter::{get_default_generation_filter, DrillPreset};
+use crate::hand_score::{KazoeYakumanRule, ScoringSettings};
 use serde::{Deserialize, Serialize};
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GameMode {
+    Score,
+    Fu,
+    Yaku,
+    Reverse,
+    MultipleChoice,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct UserSettings {
     pub scoring_settings: ScoringSettings,
     pub language_key: String,
+    pub ask_limit_names: bool,
+    pub strict_answer_order: bool,
+    pub retry_wrong_part: bool,
+    pub game_mode: GameMode,
+    pub use_spaced_repetition: bool,
+    pub use_adaptive_difficulty: bool,
+    pub drills: Vec<DrillPreset>,
 }
 
 pub fn get_default_settings() -> UserSettings {
@@ -15,9 +32,20 @@ pub fn get_default_settings() -> UserSettings {
         scoring_settings: ScoringSettings {
             use_kiriage_mangan: false,
             use_honba: false,
-            use_kazoe_yakuman: true,
-            only_less_than_5_han: false,
+            kazoe_yakuman: KazoeYakumanRule::Yakuman,
+            generation_filter: get_default_generation_filter(),
+            use_4_fu_double_wind_pair: true,
+            use_30_fu_open_pinfu_ron: true,
+            use_legacy_chiitoitsu: false,
+            use_rinshan_tsumo_fu: true,
         },
         language_key: "en".to_string(),
+        ask_limit_names: false,
+        strict_answer_order: false,
+        retry_wrong_part: false,
+        game_mode: GameMode::Score,
+        use_spaced_repetition: false,
+        use_adaptive_difficulty: false,
+        drills: Vec::new(),
     }
 }
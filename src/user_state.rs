@@ -4,12 +4,23 @@
 use crate::json_file_updater::*;
 use crate::user_settings::*;
 use crate::user_state_updaters;
+use crate::user_statistics::*;
 use dashmap::DashMap;
 use std::path::Path;
-use teloxide::types::ChatId;
+use std::sync::atomic::{AtomicBool, Ordering};
+use teloxide::types::{ChatId, UserId};
 
-use crate::hand_score::HandScoreData;
+use crate::achievements::Achievement;
+use crate::adaptive_difficulty::DifficultyChange;
+use crate::daily_challenge::*;
+use crate::group_game::{GroupAnswer, GroupGame};
+use crate::hand_score::{HandScoreData, HandScoreTotals, SeededHands, TsumoPayment};
+use crate::review_schedule::*;
+use crate::session::Session;
+use crate::speed_run::SpeedRun;
+use crate::tile_hand::TileHand;
 use crate::user_state_updaters::update_user_states_to_the_latest_version;
+use crate::yaku_quiz::YakuQuestion;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 #[derive(Serialize, Deserialize)]
@@ -18,14 +29,108 @@ pub struct UserStates {
     // should not be changed after deserialized
     version: String,
 
+    // states of private chats, and shared states of group chats that are used for group games
     pub states: DashMap<ChatId, UserState>,
+    // states of members of group chats
+    pub member_states: DashMap<ChatMember, UserState>,
+    pub daily_rankings: DailyRankings,
+
+    // set when a state has changed since the file was written last time
+    #[serde(skip)]
+    unsaved: AtomicBool,
+}
+
+impl UserStates {
+    pub fn mark_unsaved(&self) {
+        self.unsaved.store(true, Ordering::Release);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChatMember {
+    pub chat_id: ChatId,
+    pub user_id: UserId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserStateKey {
+    Chat(ChatId),
+    Member(ChatMember),
+}
+
+// saved as "chat_id:user_id", since json keys can only be strings
+impl Serialize for ChatMember {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&format!("{}:{}", self.chat_id.0, self.user_id.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for ChatMember {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let text = String::deserialize(deserializer)?;
+        let parsed = text.split_once(':').and_then(|(chat_id, user_id)| {
+            Some(ChatMember {
+                chat_id: ChatId(chat_id.parse().ok()?),
+                user_id: UserId(user_id.parse().ok()?),
+            })
+        });
+        parsed.ok_or_else(|| serde::de::Error::custom(format!("Invalid chat member \"{}\"", text)))
+    }
 }
 
 #[derive(Clone)]
 pub struct UserState {
     pub hand_score: Option<HandScoreData>,
+    // the payment of a non-dealer tsumo that the user is retrying after getting only it wrong
+    pub retried_payment: Option<TsumoPayment>,
+    pub fu_question: Option<TileHand>,
+    // the hand which yaku are asked before asking its score
+    pub yaku_question: Option<YakuQuestion>,
+    // the scores offered as buttons in the multiple choice mode
+    pub choice_options: Vec<HandScoreTotals>,
+    // changes with every multiple choice question, so buttons of older questions are ignored
+    pub choice_question_id: u32,
+    pub speed_run: Option<SpeedRun>,
+    pub session: Option<Session>,
+    pub group_game: Option<GroupGame>,
+    pub daily_challenge: Option<DailyChallenge>,
+    // set when the score questions are generated from a seed
+    pub seeded_hands: Option<SeededHands>,
+    // unix timestamp of when the current score question was asked
+    pub question_time: Option<i64>,
+    // achievements earned with the last answer that are not announced yet
+    pub new_achievements: Vec<Achievement>,
+    // a change of the adaptive difficulty level that is not announced yet
+    pub difficulty_change: Option<DifficultyChange>,
+    // answers of the group game members that are not recorded to their own states yet
+    pub group_answers: Vec<GroupAnswer>,
     pub settings: UserSettings,
     pub settings_unsaved: bool,
+    pub statistics: UserStatistics,
+    pub statistics_unsaved: bool,
+    pub review_schedule: ReviewSchedule,
+    pub review_schedule_unsaved: bool,
+}
+
+// the part of the user state that is saved to the disk
+#[derive(Serialize)]
+struct SavedUserStateRef<'a> {
+    settings: &'a UserSettings,
+    statistics: &'a UserStatistics,
+    review_schedule: &'a ReviewSchedule,
+}
+
+#[derive(Deserialize)]
+struct SavedUserState {
+    settings: UserSettings,
+    statistics: UserStatistics,
+    review_schedule: ReviewSchedule,
 }
 
 impl Serialize for UserState {
@@ -33,7 +138,12 @@ impl Serialize for UserState {
     where
         S: Serializer,
     {
-        self.settings.serialize(serializer)
+        SavedUserStateRef {
+            settings: &self.settings,
+            statistics: &self.statistics,
+            review_schedule: &self.review_schedule,
+        }
+        .serialize(serializer)
     }
 }
 
@@ -42,17 +152,38 @@ impl<'de> Deserialize<'de> for UserState {
     where
         D: Deserializer<'de>,
     {
+        let saved_user_state = SavedUserState::deserialize(deserializer)?;
         let mut user_state = get_default_user_state();
-        user_state.settings = UserSettings::deserialize(deserializer)?;
-        return Ok(user_state);
+        user_state.settings = saved_user_state.settings;
+        user_state.statistics = saved_user_state.statistics;
+        user_state.review_schedule = saved_user_state.review_schedule;
+        Ok(user_state)
     }
 }
 
 pub fn get_default_user_state() -> UserState {
     UserState {
         hand_score: None,
+        retried_payment: None,
+        fu_question: None,
+        yaku_question: None,
+        choice_options: Vec::new(),
+        choice_question_id: 0,
+        speed_run: None,
+        session: None,
+        group_game: None,
+        daily_challenge: None,
+        seeded_hands: None,
+        question_time: None,
+        new_achievements: Vec::new(),
+        difficulty_change: None,
+        group_answers: Vec::new(),
         settings: get_default_settings(),
         settings_unsaved: false,
+        statistics: get_default_statistics(),
+        statistics_unsaved: false,
+        review_schedule: get_default_review_schedule(),
+        review_schedule_unsaved: false,
     }
 }
 
@@ -60,9 +191,52 @@ pub fn get_default_user_states() -> UserStates {
     UserStates {
         version: user_state_updaters::LATEST_SAVE_VERSION.to_string(),
         states: DashMap::new(),
+        member_states: DashMap::new(),
+        daily_rankings: get_default_daily_rankings(),
+        unsaved: AtomicBool::new(false),
+    }
+}
+
+// a new member of a group chat starts with the rules that the group used before
+fn get_new_member_state(user_states: &UserStates, chat_id: ChatId) -> UserState {
+    let mut user_state = get_default_user_state();
+    if let Some(chat_state) = user_states.states.get(&chat_id) {
+        user_state.settings = chat_state.settings.clone();
     }
+    user_state
 }
 
+// calls the function with the state for the key, the state is created if it doesn't exist
+pub fn update_user_state<R, F: FnOnce(&mut UserState) -> R>(
+    user_states: &UserStates,
+    key: UserStateKey,
+    update: F,
+) -> R {
+    match key {
+        UserStateKey::Chat(chat_id) => update(
+            &mut user_states
+                .states
+                .entry(chat_id)
+                .or_insert_with(get_default_user_state),
+        ),
+        UserStateKey::Member(member) => {
+            // the chat state is read before locking the member state
+            let new_state = if user_states.member_states.contains_key(&member) {
+                None
+            } else {
+                Some(get_new_member_state(user_states, member.chat_id))
+            };
+            update(
+                &mut user_states
+                    .member_states
+                    .entry(member)
+                    .or_insert_with(|| new_state.unwrap_or_else(get_default_user_state)),
+            )
+        }
+    }
+}
+
+#[allow(clippy::needless_return, clippy::needless_borrows_for_generic_args)]
 pub fn read_user_states_from_file(user_states_file_path: &Path) -> UserStates {
     // create default file that will be used if the file doesn't exist
     let default_states = get_default_user_states();
@@ -141,6 +315,7 @@ pub fn read_user_states_from_file(user_states_file_path: &Path) -> UserStates {
     return user_states;
 }
 
+#[allow(clippy::needless_borrows_for_generic_args)]
 pub fn save_user_states_to_file(user_states: &UserStates, user_states_file_path: &Path) {
     let data = serde_json::to_string(&user_states);
     let data = match data {
@@ -156,10 +331,57 @@ pub fn save_user_states_to_file(user_states: &UserStates, user_states_file_path:
     }
 }
 
-pub fn save_single_user_state(file_path: &Path, chat_id: ChatId, user_state: &UserState) {
-    // this is quite terrible, but we need to do that in order to not lock states of other users
-    // this should ideally be replaced by  sqlite database
-    let user_states = read_user_states_from_file(file_path);
-    user_states.states.insert(chat_id, user_state.clone());
-    save_user_states_to_file(&user_states, file_path);
+// the states in memory are the only ones that are changed, so the whole file is written from them
+pub fn save_user_states_if_changed(user_states: &UserStates, user_states_file_path: &Path) {
+    // the daily rankings are in the same file, both flags are reset before writing it
+    let is_changed = user_states.unsaved.swap(false, Ordering::AcqRel);
+    if user_states.daily_rankings.take_unsaved() || is_changed {
+        save_user_states_to_file(user_states, user_states_file_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_member_states_are_saved_with_chat_and_user_ids() {
+        let user_states = get_default_user_states();
+        let member = ChatMember {
+            chat_id: ChatId(-100123),
+            user_id: UserId(456),
+        };
+        user_states
+            .member_states
+            .insert(member, get_default_user_state());
+
+        let user_states_json = serde_json::to_value(&user_states).unwrap();
+        assert!(user_states_json["member_states"]["-100123:456"].is_object());
+
+        let loaded_user_states: UserStates = serde_json::from_value(user_states_json).unwrap();
+        assert!(loaded_user_states.member_states.contains_key(&member));
+    }
+
+    #[test]
+    fn test_user_states_are_saved_only_after_changes() {
+        let file_path =
+            std::env::temp_dir().join("test_user_states_are_saved_only_after_changes.json");
+        let _ = std::fs::remove_file(&file_path);
+        let user_states = get_default_user_states();
+
+        save_user_states_if_changed(&user_states, &file_path);
+        assert!(!file_path.exists());
+
+        user_states
+            .states
+            .insert(ChatId(123), get_default_user_state());
+        user_states.mark_unsaved();
+        save_user_states_if_changed(&user_states, &file_path);
+        let loaded_user_states = read_user_states_from_file(&file_path);
+        assert!(loaded_user_states.states.contains_key(&ChatId(123)));
+
+        std::fs::remove_file(&file_path).unwrap();
+        save_user_states_if_changed(&user_states, &file_path);
+        assert!(!file_path.exists());
+    }
 }
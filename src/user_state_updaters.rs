@@ -5,7 +5,7 @@ use crate::json_file_updater::{JsonFileUpdater, UpdateResult};
 use serde_json::Value as JsonValue;
 
 static VERSION_FIELD_NAME: &str = "version";
-pub static LATEST_SAVE_VERSION: &str = "0.3.0";
+pub static LATEST_SAVE_VERSION: &str = "0.19.0";
 
 pub fn update_user_states_to_the_latest_version(user_states_json: &mut JsonValue) -> UpdateResult {
     let version = user_states_json[VERSION_FIELD_NAME].as_str();
@@ -26,6 +26,22 @@ fn register_json_updaters() -> JsonFileUpdater {
     json_file_updater.add_update_function("0.1.0", |_|{});
     json_file_updater.add_update_function("0.2.0", v0_2_0_rename_4_30_mangan_to_kiriage_mangan);
     json_file_updater.add_update_function("0.3.0", v0_3_0_add_only_less_than_5_han_setting);
+    json_file_updater.add_update_function("0.4.0", v0_4_0_add_fu_rule_settings);
+    json_file_updater.add_update_function("0.5.0", v0_5_0_add_kazoe_rule_and_limit_name_settings);
+    json_file_updater.add_update_function("0.6.0", v0_6_0_add_strict_answer_order_setting);
+    json_file_updater.add_update_function("0.7.0", v0_7_0_add_retry_wrong_part_setting);
+    json_file_updater.add_update_function("0.8.0", v0_8_0_move_settings_and_add_statistics);
+    json_file_updater.add_update_function("0.9.0", v0_9_0_add_game_mode_setting);
+    json_file_updater.add_update_function("0.10.0", v0_10_0_add_speed_run_records);
+    json_file_updater.add_update_function("0.11.0", v0_11_0_add_review_schedule);
+    json_file_updater.add_update_function("0.12.0", v0_12_0_add_answer_history);
+    json_file_updater.add_update_function("0.13.0", v0_13_0_add_streaks_and_achievements);
+    json_file_updater.add_update_function("0.14.0", v0_14_0_add_member_states);
+    json_file_updater.add_update_function("0.15.0", v0_15_0_add_daily_rankings);
+    json_file_updater.add_update_function("0.16.0", v0_16_0_replace_less_than_5_han_with_filter);
+    json_file_updater.add_update_function("0.17.0", v0_17_0_add_adaptive_difficulty);
+    json_file_updater.add_update_function("0.18.0", v0_18_0_add_proficiency_levels);
+    json_file_updater.add_update_function("0.19.0", v0_19_0_merge_speed_run_records_by_rules);
     // add update functions above this line
     // don't forget to update LATEST_SAVE_VERSION at the beginning of the file
 
@@ -48,3 +64,471 @@ fn v0_3_0_add_only_less_than_5_han_setting(user_states_json: &mut JsonValue) {
         scoring_settings.insert("only_less_than_5_han".to_string(), JsonValue::Bool(false));
     }
 }
+
+fn v0_4_0_add_fu_rule_settings(user_states_json: &mut JsonValue) {
+    for user_state in user_states_json["states"]
+        .as_object_mut()
+        .unwrap()
+        .values_mut()
+    {
+        let user_state = user_state.as_object_mut().unwrap();
+        let scoring_settings = user_state
+            .get_mut("scoring_settings")
+            .unwrap()
+            .as_object_mut()
+            .unwrap();
+        scoring_settings.insert(
+            "use_4_fu_double_wind_pair".to_string(),
+            JsonValue::Bool(true),
+        );
+        scoring_settings.insert(
+            "use_30_fu_open_pinfu_ron".to_string(),
+            JsonValue::Bool(true),
+        );
+        scoring_settings.insert("use_legacy_chiitoitsu".to_string(), JsonValue::Bool(false));
+        scoring_settings.insert("use_rinshan_tsumo_fu".to_string(), JsonValue::Bool(true));
+    }
+}
+
+fn v0_5_0_add_kazoe_rule_and_limit_name_settings(user_states_json: &mut JsonValue) {
+    for user_state in user_states_json["states"]
+        .as_object_mut()
+        .unwrap()
+        .values_mut()
+    {
+        let user_state = user_state.as_object_mut().unwrap();
+        let scoring_settings = user_state
+            .get_mut("scoring_settings")
+            .unwrap()
+            .as_object_mut()
+            .unwrap();
+        let use_kazoe_yakuman = scoring_settings
+            .remove("use_kazoe_yakuman")
+            .unwrap()
+            .as_bool()
+            .unwrap();
+        scoring_settings.insert(
+            "kazoe_yakuman".to_string(),
+            JsonValue::String(
+                if use_kazoe_yakuman {
+                    "Yakuman"
+                } else {
+                    "Sanbaiman"
+                }
+                .to_string(),
+            ),
+        );
+        user_state.insert("ask_limit_names".to_string(), JsonValue::Bool(false));
+    }
+}
+
+fn v0_6_0_add_strict_answer_order_setting(user_states_json: &mut JsonValue) {
+    for user_state in user_states_json["states"]
+        .as_object_mut()
+        .unwrap()
+        .values_mut()
+    {
+        let user_state = user_state.as_object_mut().unwrap();
+        user_state.insert("strict_answer_order".to_string(), JsonValue::Bool(false));
+    }
+}
+
+fn v0_7_0_add_retry_wrong_part_setting(user_states_json: &mut JsonValue) {
+    for user_state in user_states_json["states"]
+        .as_object_mut()
+        .unwrap()
+        .values_mut()
+    {
+        let user_state = user_state.as_object_mut().unwrap();
+        user_state.insert("retry_wrong_part".to_string(), JsonValue::Bool(false));
+    }
+}
+
+fn v0_8_0_move_settings_and_add_statistics(user_states_json: &mut JsonValue) {
+    for user_state in user_states_json["states"]
+        .as_object_mut()
+        .unwrap()
+        .values_mut()
+    {
+        let settings = user_state.take();
+        *user_state = serde_json::json!({
+            "settings": settings,
+            "statistics": {
+                "mistakes": {},
+            },
+        });
+    }
+}
+
+fn v0_9_0_add_game_mode_setting(user_states_json: &mut JsonValue) {
+    for user_state in user_states_json["states"]
+        .as_object_mut()
+        .unwrap()
+        .values_mut()
+    {
+        let settings = user_state["settings"].as_object_mut().unwrap();
+        settings.insert(
+            "game_mode".to_string(),
+            JsonValue::String("Score".to_string()),
+        );
+    }
+}
+
+fn v0_10_0_add_speed_run_records(user_states_json: &mut JsonValue) {
+    for user_state in user_states_json["states"]
+        .as_object_mut()
+        .unwrap()
+        .values_mut()
+    {
+        let statistics = user_state["statistics"].as_object_mut().unwrap();
+        statistics.insert(
+            "speed_run_records".to_string(),
+            JsonValue::Array(Vec::new()),
+        );
+    }
+}
+
+fn v0_11_0_add_review_schedule(user_states_json: &mut JsonValue) {
+    for user_state in user_states_json["states"]
+        .as_object_mut()
+        .unwrap()
+        .values_mut()
+    {
+        let settings = user_state["settings"].as_object_mut().unwrap();
+        settings.insert("use_spaced_repetition".to_string(), JsonValue::Bool(false));
+
+        let user_state = user_state.as_object_mut().unwrap();
+        user_state.insert(
+            "review_schedule".to_string(),
+            serde_json::json!({
+                "items": [],
+            }),
+        );
+    }
+}
+
+fn v0_12_0_add_answer_history(user_states_json: &mut JsonValue) {
+    for user_state in user_states_json["states"]
+        .as_object_mut()
+        .unwrap()
+        .values_mut()
+    {
+        let statistics = user_state["statistics"].as_object_mut().unwrap();
+        statistics.insert("answers".to_string(), JsonValue::Array(Vec::new()));
+    }
+}
+
+fn v0_13_0_add_streaks_and_achievements(user_states_json: &mut JsonValue) {
+    for user_state in user_states_json["states"]
+        .as_object_mut()
+        .unwrap()
+        .values_mut()
+    {
+        let statistics = user_state["statistics"].as_object_mut().unwrap();
+
+        let answers = statistics["answers"].as_array_mut().unwrap();
+        for answer in answers.iter_mut() {
+            answer
+                .as_object_mut()
+                .unwrap()
+                .insert("limit".to_string(), JsonValue::Null);
+        }
+        // the history is the best guess that we have for the answers given before
+        let correct_answers_count = answers
+            .iter()
+            .filter(|answer| answer["is_correct"].as_bool() == Some(true))
+            .count();
+
+        statistics.insert(
+            "correct_answers_count".to_string(),
+            JsonValue::from(correct_answers_count),
+        );
+        statistics.insert("current_streak".to_string(), JsonValue::from(0));
+        statistics.insert("best_streak".to_string(), JsonValue::from(0));
+        statistics.insert("correct_limits".to_string(), JsonValue::Array(Vec::new()));
+        statistics.insert("achievements".to_string(), JsonValue::Array(Vec::new()));
+    }
+}
+
+fn v0_14_0_add_member_states(user_states_json: &mut JsonValue) {
+    // states of group chats are kept as they are, they are used for group games
+    // and as the initial settings of the members
+    user_states_json
+        .as_object_mut()
+        .unwrap()
+        .insert("member_states".to_string(), serde_json::json!({}));
+}
+
+fn v0_15_0_add_daily_rankings(user_states_json: &mut JsonValue) {
+    user_states_json.as_object_mut().unwrap().insert(
+        "daily_rankings".to_string(),
+        serde_json::json!({
+            "days": {},
+        }),
+    );
+}
+
+fn v0_16_0_replace_less_than_5_han_with_filter(user_states_json: &mut JsonValue) {
+    fn update_scoring_settings(scoring_settings: &mut JsonValue) {
+        let scoring_settings = scoring_settings.as_object_mut().unwrap();
+        let only_less_than_5_han = scoring_settings
+            .remove("only_less_than_5_han")
+            .unwrap()
+            .as_bool()
+            .unwrap();
+        scoring_settings.insert(
+            "generation_filter".to_string(),
+            serde_json::json!({
+                "min_han": 1,
+                "max_han": if only_less_than_5_han { 4 } else { 20 },
+                "min_fu": 20,
+                "max_fu": 110,
+                "dealer": null,
+                "ron": null,
+                "min_honba": 0,
+                "max_honba": 12,
+            }),
+        );
+    }
+
+    for states_field_name in ["states", "member_states"] {
+        for user_state in user_states_json[states_field_name]
+            .as_object_mut()
+            .unwrap()
+            .values_mut()
+        {
+            let settings = user_state["settings"].as_object_mut().unwrap();
+            update_scoring_settings(&mut settings["scoring_settings"]);
+            settings.insert("drills".to_string(), JsonValue::Array(Vec::new()));
+
+            // speed run records are kept separately for each set of rules
+            for record in user_state["statistics"]["speed_run_records"]
+                .as_array_mut()
+                .unwrap()
+            {
+                update_scoring_settings(&mut record["scoring_settings"]);
+            }
+        }
+    }
+}
+
+fn v0_17_0_add_adaptive_difficulty(user_states_json: &mut JsonValue) {
+    for states_field_name in ["states", "member_states"] {
+        for user_state in user_states_json[states_field_name]
+            .as_object_mut()
+            .unwrap()
+            .values_mut()
+        {
+            user_state["settings"].as_object_mut().unwrap().insert(
+                "use_adaptive_difficulty".to_string(),
+                JsonValue::Bool(false),
+            );
+            user_state["statistics"].as_object_mut().unwrap().insert(
+                "adaptive_difficulty".to_string(),
+                serde_json::json!({
+                    "level_index": 0,
+                    "answers_at_level": 0,
+                }),
+            );
+        }
+    }
+}
+
+fn v0_18_0_add_proficiency_levels(user_states_json: &mut JsonValue) {
+    for states_field_name in ["states", "member_states"] {
+        for user_state in user_states_json[states_field_name]
+            .as_object_mut()
+            .unwrap()
+            .values_mut()
+        {
+            let statistics = user_state["statistics"].as_object_mut().unwrap();
+            // honba of the old answers are not known, they don't count for the levels
+            for answer in statistics["answers"].as_array_mut().unwrap() {
+                answer
+                    .as_object_mut()
+                    .unwrap()
+                    .insert("honba".to_string(), JsonValue::from(0));
+            }
+            statistics.insert("proficiency_level".to_string(), JsonValue::Null);
+        }
+    }
+}
+
+fn v0_19_0_merge_speed_run_records_by_rules(user_states_json: &mut JsonValue) {
+    let default_filter = serde_json::json!({
+        "min_han": 1,
+        "max_han": 20,
+        "min_fu": 20,
+        "max_fu": 110,
+        "dealer": null,
+        "ron": null,
+        "min_honba": 0,
+        "max_honba": 12,
+    });
+
+    for states_field_name in ["states", "member_states"] {
+        for user_state in user_states_json[states_field_name]
+            .as_object_mut()
+            .unwrap()
+            .values_mut()
+        {
+            let records = user_state["statistics"]["speed_run_records"]
+                .as_array_mut()
+                .unwrap();
+            let mut merged_records: Vec<JsonValue> = Vec::new();
+            for mut record in records.drain(..) {
+                record["scoring_settings"]["generation_filter"] = default_filter.clone();
+                let existing_record = merged_records.iter_mut().find(|merged_record| {
+                    merged_record["kind"] == record["kind"]
+                        && merged_record["scoring_settings"] == record["scoring_settings"]
+                });
+                let Some(existing_record) = existing_record else {
+                    merged_records.push(record);
+                    continue;
+                };
+
+                // keep the best of the results, more correct answers or less seconds
+                let result = record["result"].as_u64().unwrap();
+                let existing_result = existing_record["result"].as_u64().unwrap();
+                existing_record["result"] = JsonValue::from(if record["kind"] == "TimeLimit" {
+                    result.max(existing_result)
+                } else {
+                    result.min(existing_result)
+                });
+            }
+            *records = merged_records;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user_state::UserStates;
+    use serde_json::json;
+
+    #[test]
+    fn test_oldest_user_states_are_updated_to_the_latest_version() {
+        let mut user_states_json = json!({
+            "states": {
+                "123": {
+                    "scoring_settings": {
+                        "use_4_30_mangan": true,
+                        "use_honba": true,
+                        "use_kazoe_yakuman": false,
+                    },
+                    "language_key": "en",
+                },
+            },
+        });
+
+        let result = update_user_states_to_the_latest_version(&mut user_states_json);
+
+        assert_eq!(result, UpdateResult::Updated);
+        assert_eq!(
+            user_states_json[VERSION_FIELD_NAME],
+            json!(LATEST_SAVE_VERSION)
+        );
+        let user_states: UserStates = serde_json::from_value(user_states_json).unwrap();
+        assert_eq!(user_states.states.len(), 1);
+    }
+
+    #[test]
+    fn test_less_than_5_han_setting_is_converted_to_generation_filter() {
+        let scoring_settings = json!({
+            "only_less_than_5_han": true,
+        });
+        let mut user_states_json = json!({
+            "states": {
+                "123": {
+                    "settings": {
+                        "scoring_settings": scoring_settings,
+                    },
+                    "statistics": {
+                        "speed_run_records": [{
+                            "scoring_settings": scoring_settings,
+                        }],
+                    },
+                },
+            },
+            "member_states": {},
+        });
+
+        v0_16_0_replace_less_than_5_han_with_filter(&mut user_states_json);
+
+        let user_state = &user_states_json["states"]["123"];
+        assert_eq!(
+            user_state["settings"]["scoring_settings"]["generation_filter"]["max_han"],
+            json!(4)
+        );
+        assert!(user_state["settings"]["scoring_settings"]
+            .get("only_less_than_5_han")
+            .is_none());
+        assert_eq!(
+            user_state["statistics"]["speed_run_records"][0]["scoring_settings"]
+                ["generation_filter"]["max_han"],
+            json!(4)
+        );
+    }
+
+    #[test]
+    fn test_speed_run_records_are_merged_by_rules() {
+        let scoring_settings_with_filter = |max_han: u8| {
+            json!({
+                "use_kiriage_mangan": false,
+                "generation_filter": {
+                    "min_han": 1,
+                    "max_han": max_han,
+                    "min_fu": 20,
+                    "max_fu": 110,
+                    "dealer": null,
+                    "ron": null,
+                    "min_honba": 0,
+                    "max_honba": 12,
+                },
+            })
+        };
+        let mut user_states_json = json!({
+            "states": {
+                "123": {
+                    "statistics": {
+                        "speed_run_records": [
+                            {
+                                "kind": "TimeLimit",
+                                "scoring_settings": scoring_settings_with_filter(4),
+                                "result": 12,
+                            },
+                            {
+                                "kind": "TimeLimit",
+                                "scoring_settings": scoring_settings_with_filter(20),
+                                "result": 9,
+                            },
+                            {
+                                "kind": "QuestionCount",
+                                "scoring_settings": scoring_settings_with_filter(4),
+                                "result": 40,
+                            },
+                            {
+                                "kind": "QuestionCount",
+                                "scoring_settings": scoring_settings_with_filter(20),
+                                "result": 55,
+                            },
+                        ],
+                    },
+                },
+            },
+            "member_states": {},
+        });
+
+        v0_19_0_merge_speed_run_records_by_rules(&mut user_states_json);
+
+        let records = &user_states_json["states"]["123"]["statistics"]["speed_run_records"];
+        assert_eq!(records.as_array().unwrap().len(), 2);
+        assert_eq!(records[0]["result"], json!(12));
+        assert_eq!(records[1]["result"], json!(40));
+        assert_eq!(
+            records[0]["scoring_settings"],
+            scoring_settings_with_filter(20)
+        );
+    }
+}
@@ -0,0 +1,295 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::achievements::Achievement;
+use crate::adaptive_difficulty::{get_default_adaptive_difficulty, AdaptiveDifficulty};
+use crate::hand_score::{LimitHand, ScoringSettings};
+use crate::mistake_classifier::MistakeType;
+use crate::review_schedule::HandCombination;
+use crate::speed_run::SpeedRunKind;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+// older answers are dropped to keep the size of the saved state reasonable
+const MAX_ANSWER_HISTORY_SIZE: usize = 5000;
+const SECONDS_IN_WEEK: i64 = 7 * 24 * 60 * 60;
+
+// inclusive han ranges that are shown separately in the statistics
+pub const HAN_BANDS: [(u8, u8); 7] = [
+    (1, 2),
+    (3, 4),
+    (5, 5),
+    (6, 7),
+    (8, 10),
+    (11, 12),
+    (13, u8::MAX),
+];
+
+// the best result of a speed run with the given rules
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SpeedRunRecord {
+    pub kind: SpeedRunKind,
+    pub scoring_settings: ScoringSettings,
+    pub result: u32,
+}
+
+// a single answered score question
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AnswerRecord {
+    pub combination: HandCombination,
+    pub honba: u8,
+    pub is_correct: bool,
+    pub mistake: Option<MistakeType>,
+    pub limit: Option<LimitHand>,
+    // None if it is not known when the question was asked
+    pub answer_seconds: Option<u32>,
+    // unix timestamp of the answer
+    pub time: i64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Accuracy {
+    pub correct_count: u32,
+    pub total_count: u32,
+    timed_count: u32,
+    total_answer_seconds: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct UserStatistics {
+    pub mistakes: HashMap<MistakeType, u32>,
+    pub speed_run_records: Vec<SpeedRunRecord>,
+    pub answers: Vec<AnswerRecord>,
+    // unlike the answer history these are never trimmed
+    pub correct_answers_count: u32,
+    pub current_streak: u32,
+    pub best_streak: u32,
+    // limit hands that were answered correctly at least once
+    pub correct_limits: Vec<LimitHand>,
+    pub achievements: Vec<Achievement>,
+    pub adaptive_difficulty: AdaptiveDifficulty,
+    // index of the passed proficiency level, None if no level test was passed yet
+    pub proficiency_level: Option<usize>,
+}
+
+impl Accuracy {
+    fn add(&mut self, record: &AnswerRecord) {
+        self.total_count += 1;
+        if record.is_correct {
+            self.correct_count += 1;
+        }
+        if let Some(answer_seconds) = record.answer_seconds {
+            self.timed_count += 1;
+            self.total_answer_seconds += answer_seconds;
+        }
+    }
+
+    pub fn get_percent(&self) -> u32 {
+        if self.total_count == 0 {
+            return 0;
+        }
+        self.correct_count * 100 / self.total_count
+    }
+
+    pub fn get_average_answer_seconds(&self) -> Option<u32> {
+        if self.timed_count == 0 {
+            return None;
+        }
+        Some(self.total_answer_seconds / self.timed_count)
+    }
+}
+
+pub fn get_han_band(han: u8) -> (u8, u8) {
+    HAN_BANDS
+        .iter()
+        .copied()
+        .find(|(min_han, max_han)| (*min_han..=*max_han).contains(&han))
+        .unwrap_or(HAN_BANDS[0])
+}
+
+impl UserStatistics {
+    pub fn record_answer(&mut self, record: AnswerRecord) {
+        if record.is_correct {
+            self.correct_answers_count += 1;
+            self.current_streak += 1;
+            self.best_streak = self.best_streak.max(self.current_streak);
+            if let Some(limit) = record.limit {
+                if !self.correct_limits.contains(&limit) {
+                    self.correct_limits.push(limit);
+                }
+            }
+        } else {
+            self.current_streak = 0;
+        }
+
+        self.answers.push(record);
+        if self.answers.len() > MAX_ANSWER_HISTORY_SIZE {
+            let extra_count = self.answers.len() - MAX_ANSWER_HISTORY_SIZE;
+            self.answers.drain(..extra_count);
+        }
+    }
+
+    pub fn get_accuracy<F: Fn(&AnswerRecord) -> bool>(&self, filter: F) -> Accuracy {
+        let mut accuracy = Accuracy::default();
+        for record in self.answers.iter().filter(|record| filter(record)) {
+            accuracy.add(record);
+        }
+        accuracy
+    }
+
+    // accuracy for each group of answers, ordered by the group key
+    pub fn get_accuracy_by<K: Ord, F: Fn(&AnswerRecord) -> Option<K>>(
+        &self,
+        get_key: F,
+    ) -> Vec<(K, Accuracy)> {
+        let mut groups: BTreeMap<K, Accuracy> = BTreeMap::new();
+        for record in &self.answers {
+            if let Some(key) = get_key(record) {
+                groups.entry(key).or_default().add(record);
+            }
+        }
+        groups.into_iter().collect()
+    }
+
+    // accuracy for each of the last weeks, the current week first
+    pub fn get_weekly_accuracy(&self, time: i64, weeks_count: usize) -> Vec<Accuracy> {
+        let mut weeks = vec![Accuracy::default(); weeks_count];
+        for record in &self.answers {
+            let week_index = (time - record.time).max(0) / SECONDS_IN_WEEK;
+            if let Some(week) = weeks.get_mut(week_index as usize) {
+                week.add(record);
+            }
+        }
+        weeks
+    }
+
+    pub fn record_mistake(&mut self, mistake: MistakeType) {
+        *self.mistakes.entry(mistake).or_insert(0) += 1;
+    }
+
+    pub fn get_speed_run_best(
+        &self,
+        kind: SpeedRunKind,
+        scoring_settings: ScoringSettings,
+    ) -> Option<u32> {
+        self.speed_run_records
+            .iter()
+            .find(|record| record.kind == kind && record.scoring_settings == scoring_settings)
+            .map(|record| record.result)
+    }
+
+    // returns true if the result is a new personal best
+    pub fn record_speed_run_result(
+        &mut self,
+        kind: SpeedRunKind,
+        scoring_settings: ScoringSettings,
+        result: u32,
+    ) -> bool {
+        let existing_record = self
+            .speed_run_records
+            .iter_mut()
+            .find(|record| record.kind == kind && record.scoring_settings == scoring_settings);
+
+        match existing_record {
+            Some(record) if kind.is_better_result(result, record.result) => {
+                record.result = result;
+                true
+            }
+            Some(_) => false,
+            None => {
+                self.speed_run_records.push(SpeedRunRecord {
+                    kind,
+                    scoring_settings,
+                    result,
+                });
+                true
+            }
+        }
+    }
+}
+
+pub fn get_default_statistics() -> UserStatistics {
+    UserStatistics {
+        mistakes: HashMap::new(),
+        speed_run_records: Vec::new(),
+        answers: Vec::new(),
+        correct_answers_count: 0,
+        current_streak: 0,
+        best_streak: 0,
+        correct_limits: Vec::new(),
+        achievements: Vec::new(),
+        adaptive_difficulty: get_default_adaptive_difficulty(),
+        proficiency_level: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(han: u8, fu: u8, is_correct: bool, time: i64) -> AnswerRecord {
+        AnswerRecord {
+            combination: HandCombination {
+                han,
+                fu,
+                is_dealer: false,
+                ron: true,
+            },
+            honba: 0,
+            is_correct,
+            mistake: None,
+            limit: None,
+            answer_seconds: Some(10),
+            time,
+        }
+    }
+
+    #[test]
+    fn test_accuracy_is_grouped_by_han_band() {
+        let mut statistics = get_default_statistics();
+        statistics.record_answer(record(1, 30, true, 0));
+        statistics.record_answer(record(2, 40, false, 0));
+        statistics.record_answer(record(4, 30, true, 0));
+        statistics.record_answer(record(13, 30, true, 0));
+
+        let overall = statistics.get_accuracy(|_| true);
+        assert_eq!(overall.correct_count, 3);
+        assert_eq!(overall.get_percent(), 75);
+        assert_eq!(overall.get_average_answer_seconds(), Some(10));
+
+        let by_han_band =
+            statistics.get_accuracy_by(|record| Some(get_han_band(record.combination.han)));
+        let counts: Vec<((u8, u8), u32, u32)> = by_han_band
+            .iter()
+            .map(|(band, accuracy)| (*band, accuracy.correct_count, accuracy.total_count))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![((1, 2), 1, 2), ((3, 4), 1, 1), ((13, u8::MAX), 1, 1)]
+        );
+    }
+
+    #[test]
+    fn test_weekly_accuracy() {
+        let mut statistics = get_default_statistics();
+        statistics.record_answer(record(1, 30, true, SECONDS_IN_WEEK * 3));
+        statistics.record_answer(record(1, 30, false, SECONDS_IN_WEEK * 3 - 1));
+        statistics.record_answer(record(1, 30, true, 0));
+
+        let weeks = statistics.get_weekly_accuracy(SECONDS_IN_WEEK * 3, 2);
+        assert_eq!(weeks[0].total_count, 2);
+        assert_eq!(weeks[0].correct_count, 1);
+        // the oldest answer is outside of the requested weeks
+        assert_eq!(weeks[1].total_count, 0);
+    }
+
+    #[test]
+    fn test_answer_history_is_limited() {
+        let mut statistics = get_default_statistics();
+        for index in 0..MAX_ANSWER_HISTORY_SIZE + 10 {
+            statistics.record_answer(record(1, 30, true, index as i64));
+        }
+        assert_eq!(statistics.answers.len(), MAX_ANSWER_HISTORY_SIZE);
+        assert_eq!(statistics.answers[0].time, 10);
+    }
+}
@@ -0,0 +1,584 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::fu_calculator::{calculate_fu, MeldKind, WaitKind};
+use crate::hand_score::ScoringSettings;
+use crate::tile_hand::{HandInterpretation, HandMeld, TileHand};
+use crate::tiles::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Yaku {
+    Riichi,
+    Ippatsu,
+    MenzenTsumo,
+    Pinfu,
+    Iipeikou,
+    Tanyao,
+    Haku,
+    Hatsu,
+    Chun,
+    SeatWind,
+    RoundWind,
+    RinshanKaihou,
+    Chanta,
+    Ittsu,
+    SanshokuDoujun,
+    SanshokuDoukou,
+    Toitoi,
+    Sanankou,
+    Sankantsu,
+    Chiitoitsu,
+    Honroutou,
+    Shousangen,
+    Honitsu,
+    Junchan,
+    Ryanpeikou,
+    Chinitsu,
+}
+
+pub const ALL_YAKU: [Yaku; 26] = [
+    Yaku::Riichi,
+    Yaku::Ippatsu,
+    Yaku::MenzenTsumo,
+    Yaku::Pinfu,
+    Yaku::Iipeikou,
+    Yaku::Tanyao,
+    Yaku::Haku,
+    Yaku::Hatsu,
+    Yaku::Chun,
+    Yaku::SeatWind,
+    Yaku::RoundWind,
+    Yaku::RinshanKaihou,
+    Yaku::Chanta,
+    Yaku::Ittsu,
+    Yaku::SanshokuDoujun,
+    Yaku::SanshokuDoukou,
+    Yaku::Toitoi,
+    Yaku::Sanankou,
+    Yaku::Sankantsu,
+    Yaku::Chiitoitsu,
+    Yaku::Honroutou,
+    Yaku::Shousangen,
+    Yaku::Honitsu,
+    Yaku::Junchan,
+    Yaku::Ryanpeikou,
+    Yaku::Chinitsu,
+];
+
+// the parts of the situation that can't be seen from the tiles of the hand
+#[derive(Debug, Clone)]
+pub struct WinContext {
+    pub is_riichi: bool,
+    pub is_ippatsu: bool,
+    pub dora_indicators: Vec<Tile>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HandEvaluation {
+    pub yaku: Vec<Yaku>,
+    // han from yaku only
+    pub yaku_han: u8,
+    pub dora: u8,
+    pub fu: u8,
+}
+
+impl HandEvaluation {
+    pub fn get_total_han(&self) -> u8 {
+        self.yaku_han + self.dora
+    }
+}
+
+impl Yaku {
+    // the first name is romaji that is used to display the yaku, the second is English
+    pub fn get_names(&self) -> &'static [&'static str] {
+        match self {
+            Yaku::Riichi => &["riichi", "reach", "richi"],
+            Yaku::Ippatsu => &["ippatsu", "one shot"],
+            Yaku::MenzenTsumo => &[
+                "menzen tsumo",
+                "fully concealed hand",
+                "tsumo",
+                "menzenchin tsumohou",
+            ],
+            Yaku::Pinfu => &["pinfu", "all sequences", "no points hand"],
+            Yaku::Iipeikou => &["iipeikou", "pure double sequence", "iipeiko"],
+            Yaku::Tanyao => &["tanyao", "all simples", "tanyaochuu"],
+            Yaku::Haku => &["haku", "white dragon"],
+            Yaku::Hatsu => &["hatsu", "green dragon"],
+            Yaku::Chun => &["chun", "red dragon"],
+            Yaku::SeatWind => &["jikaze", "seat wind"],
+            Yaku::RoundWind => &["bakaze", "round wind", "prevalent wind"],
+            Yaku::RinshanKaihou => &["rinshan kaihou", "after a kan", "rinshan"],
+            Yaku::Chanta => &["chanta", "half outside hand", "outside hand"],
+            Yaku::Ittsu => &["ittsu", "pure straight", "ikkitsuukan", "straight"],
+            Yaku::SanshokuDoujun => &["sanshoku doujun", "mixed triple sequence", "sanshoku"],
+            Yaku::SanshokuDoukou => &["sanshoku doukou", "triple triplets"],
+            Yaku::Toitoi => &["toitoi", "all triplets", "toitoihou"],
+            Yaku::Sanankou => &["sanankou", "three concealed triplets"],
+            Yaku::Sankantsu => &["sankantsu", "three quads"],
+            Yaku::Chiitoitsu => &["chiitoitsu", "seven pairs", "chiitoi"],
+            Yaku::Honroutou => &["honroutou", "all terminals and honors"],
+            Yaku::Shousangen => &["shousangen", "little three dragons"],
+            Yaku::Honitsu => &["honitsu", "half flush"],
+            Yaku::Junchan => &["junchan", "fully outside hand"],
+            Yaku::Ryanpeikou => &["ryanpeikou", "twice pure double sequence"],
+            Yaku::Chinitsu => &["chinitsu", "full flush"],
+        }
+    }
+
+    pub fn get_name(&self) -> String {
+        let names = self.get_names();
+        format!("{} ({})", names[0], names[1])
+    }
+
+    // ignores case, spaces and dashes, e.g. "Menzen-Tsumo" and "menzentsumo" are the same
+    pub fn from_name(name: &str) -> Option<Yaku> {
+        let simplify = |text: &str| -> String {
+            text.chars()
+                .filter(|c| !c.is_whitespace() && *c != '-' && *c != '_')
+                .collect::<String>()
+                .to_lowercase()
+        };
+        let name = simplify(name);
+        ALL_YAKU.iter().copied().find(|yaku| {
+            yaku.get_names()
+                .iter()
+                .any(|yaku_name| simplify(yaku_name) == name)
+        })
+    }
+
+    pub fn get_han(&self, is_closed: bool, settings: ScoringSettings) -> u8 {
+        let (closed_han, open_han) = match self {
+            Yaku::Riichi | Yaku::Ippatsu | Yaku::MenzenTsumo | Yaku::Pinfu | Yaku::Iipeikou => {
+                (1, 0)
+            }
+            Yaku::Tanyao
+            | Yaku::Haku
+            | Yaku::Hatsu
+            | Yaku::Chun
+            | Yaku::SeatWind
+            | Yaku::RoundWind
+            | Yaku::RinshanKaihou => (1, 1),
+            Yaku::Chanta | Yaku::Ittsu | Yaku::SanshokuDoujun => (2, 1),
+            Yaku::SanshokuDoukou
+            | Yaku::Toitoi
+            | Yaku::Sanankou
+            | Yaku::Sankantsu
+            | Yaku::Honroutou
+            | Yaku::Shousangen => (2, 2),
+            Yaku::Chiitoitsu => (if settings.use_legacy_chiitoitsu { 1 } else { 2 }, 0),
+            Yaku::Honitsu | Yaku::Junchan => (3, 2),
+            Yaku::Ryanpeikou => (3, 0),
+            Yaku::Chinitsu => (6, 5),
+        };
+
+        if is_closed {
+            closed_han
+        } else {
+            open_han
+        }
+    }
+}
+
+fn count_meld_kinds(melds: &[HandMeld], kind: MeldKind) -> usize {
+    melds.iter().filter(|meld| meld.kind == kind).count()
+}
+
+fn is_triplet_or_quad(meld: &HandMeld) -> bool {
+    meld.kind != MeldKind::Sequence
+}
+
+fn has_meld_in_all_suits(
+    melds: &[HandMeld],
+    value: u8,
+    is_matching: fn(&HandMeld) -> bool,
+) -> bool {
+    [Suit::Man, Suit::Pin, Suit::Sou].iter().all(|suit| {
+        melds
+            .iter()
+            .any(|meld| is_matching(meld) && meld.tile == Tile::new(*suit, value))
+    })
+}
+
+fn get_concealed_triplets_count(hand: &TileHand, interpretation: &HandInterpretation) -> usize {
+    interpretation
+        .melds
+        .iter()
+        .enumerate()
+        .filter(|(index, meld)| {
+            // a triplet completed by ron is considered open
+            is_triplet_or_quad(meld)
+                && !meld.is_open
+                && !(hand.is_ron && interpretation.winning_meld_index == Some(*index))
+        })
+        .count()
+}
+
+// yaku that depend only on the tiles and not on how they are split into melds
+fn get_tile_yaku(tiles: &[Tile]) -> Vec<Yaku> {
+    let mut yaku = Vec::new();
+
+    if tiles.iter().all(|tile| !tile.is_terminal_or_honor()) {
+        yaku.push(Yaku::Tanyao);
+    }
+    if tiles.iter().all(|tile| tile.is_terminal_or_honor()) {
+        yaku.push(Yaku::Honroutou);
+    }
+
+    let number_tile_suits: Vec<Suit> = tiles
+        .iter()
+        .filter(|tile| !tile.is_honor())
+        .map(|tile| tile.suit)
+        .collect();
+    if let Some(first_suit) = number_tile_suits.first() {
+        if number_tile_suits.iter().all(|suit| suit == first_suit) {
+            if tiles.iter().any(|tile| tile.is_honor()) {
+                yaku.push(Yaku::Honitsu);
+            } else {
+                yaku.push(Yaku::Chinitsu);
+            }
+        }
+    }
+
+    yaku
+}
+
+fn get_meld_yaku(
+    hand: &TileHand,
+    interpretation: &HandInterpretation,
+    is_closed: bool,
+) -> Vec<Yaku> {
+    let melds = &interpretation.melds;
+    let pair = interpretation.pairs[0];
+    let mut yaku = Vec::new();
+
+    let is_pinfu = is_closed
+        && count_meld_kinds(melds, MeldKind::Sequence) == 4
+        && !pair.is_dragon()
+        && pair != hand.seat_wind
+        && pair != hand.round_wind
+        && interpretation.wait == WaitKind::Ryanmen;
+    if is_pinfu {
+        yaku.push(Yaku::Pinfu);
+    }
+
+    if is_closed {
+        let mut sequences: Vec<Tile> = melds
+            .iter()
+            .filter(|meld| meld.kind == MeldKind::Sequence)
+            .map(|meld| meld.tile)
+            .collect();
+        sequences.sort();
+        let mut identical_sequence_pairs = 0;
+        let mut index = 0;
+        while index + 1 < sequences.len() {
+            if sequences[index] == sequences[index + 1] {
+                identical_sequence_pairs += 1;
+                index += 2;
+            } else {
+                index += 1;
+            }
+        }
+        match identical_sequence_pairs {
+            0 => {}
+            1 => yaku.push(Yaku::Iipeikou),
+            _ => yaku.push(Yaku::Ryanpeikou),
+        }
+    }
+
+    for meld in melds.iter().filter(|meld| is_triplet_or_quad(meld)) {
+        match (meld.tile.suit, meld.tile.value) {
+            (Suit::Honor, 5) => yaku.push(Yaku::Haku),
+            (Suit::Honor, 6) => yaku.push(Yaku::Hatsu),
+            (Suit::Honor, 7) => yaku.push(Yaku::Chun),
+            _ => {}
+        }
+        if meld.tile == hand.seat_wind {
+            yaku.push(Yaku::SeatWind);
+        }
+        if meld.tile == hand.round_wind {
+            yaku.push(Yaku::RoundWind);
+        }
+    }
+
+    if [Suit::Man, Suit::Pin, Suit::Sou].iter().any(|suit| {
+        [1, 4, 7].iter().all(|value| {
+            melds.iter().any(|meld| {
+                meld.kind == MeldKind::Sequence && meld.tile == Tile::new(*suit, *value)
+            })
+        })
+    }) {
+        yaku.push(Yaku::Ittsu);
+    }
+
+    if (1..=7)
+        .any(|value| has_meld_in_all_suits(melds, value, |meld| meld.kind == MeldKind::Sequence))
+    {
+        yaku.push(Yaku::SanshokuDoujun);
+    }
+    if (1..=9).any(|value| has_meld_in_all_suits(melds, value, is_triplet_or_quad)) {
+        yaku.push(Yaku::SanshokuDoukou);
+    }
+
+    let sequences_count = count_meld_kinds(melds, MeldKind::Sequence);
+    if sequences_count == 0 {
+        yaku.push(Yaku::Toitoi);
+    }
+    if get_concealed_triplets_count(hand, interpretation) == 3 {
+        yaku.push(Yaku::Sanankou);
+    }
+    if count_meld_kinds(melds, MeldKind::Quad) == 3 {
+        yaku.push(Yaku::Sankantsu);
+    }
+
+    let dragon_triplets_count = melds
+        .iter()
+        .filter(|meld| is_triplet_or_quad(meld) && meld.tile.is_dragon())
+        .count();
+    if dragon_triplets_count == 2 && pair.is_dragon() {
+        yaku.push(Yaku::Shousangen);
+    }
+
+    // with no sequences it is honroutou instead
+    let every_group_has_terminal = melds.iter().all(|meld| {
+        meld.get_tiles()
+            .iter()
+            .any(|tile| tile.is_terminal_or_honor())
+    }) && pair.is_terminal_or_honor();
+    if every_group_has_terminal && sequences_count > 0 {
+        if hand.get_all_tiles().iter().any(|tile| tile.is_honor()) {
+            yaku.push(Yaku::Chanta);
+        } else {
+            yaku.push(Yaku::Junchan);
+        }
+    }
+
+    yaku
+}
+
+fn get_interpretation_yaku(
+    hand: &TileHand,
+    interpretation: &HandInterpretation,
+    context: &WinContext,
+) -> Vec<Yaku> {
+    let is_closed = hand.is_closed();
+    let mut yaku = Vec::new();
+
+    if context.is_riichi {
+        yaku.push(Yaku::Riichi);
+    }
+    if context.is_ippatsu {
+        yaku.push(Yaku::Ippatsu);
+    }
+    if is_closed && !hand.is_ron {
+        yaku.push(Yaku::MenzenTsumo);
+    }
+    if hand.is_rinshan {
+        yaku.push(Yaku::RinshanKaihou);
+    }
+
+    yaku.extend(get_tile_yaku(&hand.get_all_tiles()));
+
+    if interpretation.is_chiitoitsu() {
+        yaku.push(Yaku::Chiitoitsu);
+    } else {
+        yaku.extend(get_meld_yaku(hand, interpretation, is_closed));
+    }
+
+    yaku.sort();
+    yaku
+}
+
+pub fn count_dora(hand: &TileHand, context: &WinContext) -> u8 {
+    let tiles = hand.get_all_tiles();
+    context
+        .dora_indicators
+        .iter()
+        .map(|indicator| {
+            let dora = indicator.get_dora_from_indicator();
+            tiles.iter().filter(|tile| **tile == dora).count() as u8
+        })
+        .sum()
+}
+
+// picks the interpretation of the hand that gives the most han, and then the most fu
+pub fn evaluate_hand(
+    hand: &TileHand,
+    context: &WinContext,
+    settings: ScoringSettings,
+) -> Option<HandEvaluation> {
+    let is_closed = hand.is_closed();
+    let dora = count_dora(hand, context);
+
+    hand.get_interpretations()
+        .iter()
+        .map(|interpretation| {
+            let yaku = get_interpretation_yaku(hand, interpretation, context);
+            let yaku_han = yaku
+                .iter()
+                .map(|yaku| yaku.get_han(is_closed, settings))
+                .sum();
+            let fu = calculate_fu(&hand.get_fu_composition(interpretation), settings).total;
+            HandEvaluation {
+                yaku,
+                yaku_han,
+                dora,
+                fu,
+            }
+        })
+        .max_by_key(|evaluation| (evaluation.yaku_han, evaluation.fu))
+}
+
+fn get_tile_counts(tiles: &[Tile]) -> [u8; TILE_KINDS_COUNT] {
+    let mut counts = [0; TILE_KINDS_COUNT];
+    for tile in tiles {
+        counts[tile.get_index()] += 1;
+    }
+    counts
+}
+
+// yakuman are not covered by the quiz, so hands that have one are skipped
+pub fn has_yakuman(hand: &TileHand) -> bool {
+    let tiles = hand.get_all_tiles();
+
+    let is_tsuuiisou = tiles.iter().all(|tile| tile.is_honor());
+    let is_chinroutou = tiles
+        .iter()
+        .all(|tile| !tile.is_honor() && tile.is_terminal_or_honor());
+    let is_ryuuiisou = tiles.iter().all(|tile| {
+        *tile == Tile::new(Suit::Honor, 6)
+            || tile.suit == Suit::Sou && [2, 3, 4, 6, 8].contains(&tile.value)
+    });
+    let is_chuuren = hand.declared_melds.is_empty() && {
+        let counts = get_tile_counts(&tiles);
+        let first_suit = tiles[0].suit;
+        first_suit != Suit::Honor
+            && tiles.iter().all(|tile| tile.suit == first_suit)
+            && (1..=9).all(|value| {
+                let count = counts[Tile::new(first_suit, value).get_index()];
+                count >= if value == 1 || value == 9 { 3 } else { 1 }
+            })
+    };
+    if is_tsuuiisou || is_chinroutou || is_ryuuiisou || is_chuuren {
+        return true;
+    }
+
+    hand.get_interpretations().iter().any(|interpretation| {
+        if interpretation.is_chiitoitsu() {
+            return false;
+        }
+        let melds = &interpretation.melds;
+        let pair = interpretation.pairs[0];
+        let dragon_triplets_count = melds
+            .iter()
+            .filter(|meld| is_triplet_or_quad(meld) && meld.tile.is_dragon())
+            .count();
+        let wind_triplets_count = melds
+            .iter()
+            .filter(|meld| is_triplet_or_quad(meld) && meld.tile.is_wind())
+            .count();
+
+        dragon_triplets_count == 3
+            || wind_triplets_count == 4
+            || wind_triplets_count == 3 && pair.is_wind()
+            || count_meld_kinds(melds, MeldKind::Quad) == 4
+            || get_concealed_triplets_count(hand, interpretation) == 4
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user_settings::get_default_settings;
+
+    fn closed_hand(concealed: &str, winning_tile: &str, is_ron: bool) -> TileHand {
+        TileHand {
+            declared_melds: Vec::new(),
+            concealed_tiles: parse_tiles(concealed).unwrap(),
+            winning_tile: parse_tiles(winning_tile).unwrap()[0],
+            is_ron,
+            is_rinshan: false,
+            seat_wind: Tile::new(Suit::Honor, 2),
+            round_wind: Tile::new(Suit::Honor, 1),
+        }
+    }
+
+    fn no_context() -> WinContext {
+        WinContext {
+            is_riichi: false,
+            is_ippatsu: false,
+            dora_indicators: Vec::new(),
+        }
+    }
+
+    fn evaluate(hand: &TileHand, context: &WinContext) -> HandEvaluation {
+        evaluate_hand(hand, context, get_default_settings().scoring_settings).unwrap()
+    }
+
+    #[test]
+    fn test_riichi_pinfu_tanyao_with_dora() {
+        let hand = closed_hand("23m456p678s234s55p", "4m", true);
+        let context = WinContext {
+            is_riichi: true,
+            is_ippatsu: false,
+            dora_indicators: parse_tiles("4p").unwrap(),
+        };
+        let evaluation = evaluate(&hand, &context);
+        assert_eq!(
+            evaluation.yaku,
+            vec![Yaku::Riichi, Yaku::Pinfu, Yaku::Tanyao]
+        );
+        assert_eq!(evaluation.dora, 3);
+        assert_eq!(evaluation.get_total_han(), 6);
+        assert_eq!(evaluation.fu, 30);
+    }
+
+    #[test]
+    fn test_interpretation_with_most_han_is_chosen() {
+        // can be read as ryanpeikou or as chiitoitsu
+        let hand = closed_hand("223344m556677s9p", "9p", true);
+        let evaluation = evaluate(&hand, &no_context());
+        assert_eq!(evaluation.yaku, vec![Yaku::Ryanpeikou]);
+        assert_eq!(evaluation.yaku_han, 3);
+    }
+
+    #[test]
+    fn test_open_hand_han_is_reduced() {
+        let mut hand = closed_hand("123m789m1z", "1z", false);
+        hand.declared_melds = vec![
+            HandMeld {
+                kind: MeldKind::Sequence,
+                tile: Tile::new(Suit::Man, 4),
+                is_open: true,
+            },
+            HandMeld {
+                kind: MeldKind::Triplet,
+                tile: Tile::new(Suit::Honor, 7),
+                is_open: true,
+            },
+        ];
+        let evaluation = evaluate(&hand, &no_context());
+        // chun 1, ittsu 1, honitsu 2
+        assert_eq!(
+            evaluation.yaku,
+            vec![Yaku::Chun, Yaku::Ittsu, Yaku::Honitsu]
+        );
+        assert_eq!(evaluation.yaku_han, 4);
+    }
+
+    #[test]
+    fn test_yaku_names() {
+        assert_eq!(Yaku::from_name("Menzen-Tsumo"), Some(Yaku::MenzenTsumo));
+        assert_eq!(Yaku::from_name("all simples"), Some(Yaku::Tanyao));
+        assert_eq!(Yaku::from_name("dora"), None);
+    }
+
+    #[test]
+    fn test_yakuman_hands_are_detected() {
+        assert!(has_yakuman(&closed_hand("555666777z123m9p", "9p", true)));
+        assert!(has_yakuman(&closed_hand("111222m333p444s5z", "5z", false)));
+        // the triplet completed by ron is not concealed, so it's only sanankou
+        assert!(!has_yakuman(&closed_hand("111222m333p44s55z", "4s", true)));
+        assert!(!has_yakuman(&closed_hand("23m456p678s234s55p", "4m", true)));
+    }
+}
@@ -0,0 +1,255 @@
+// Copyright (C) Pavel Grebnev 2024
+// Distributed under the MIT License (license terms are at http://opensource.org/licenses/MIT).
+
+use crate::answer_parser::normalize_char;
+use crate::fu_calculator::MeldKind;
+use crate::hand_score::{HandScoreData, ScoringSettings};
+use crate::tile_hand::{generate_random_tile_hand, TileHand};
+use crate::tiles::*;
+use crate::yaku::*;
+use rand::Rng;
+
+#[derive(Debug, Clone)]
+pub struct YakuQuestion {
+    pub hand: TileHand,
+    pub context: WinContext,
+    pub evaluation: HandEvaluation,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum YakuAnswer {
+    Yaku(Vec<Yaku>),
+    Han(u8),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum YakuAnswerParseError {
+    Empty,
+    UnknownYaku { name: String },
+}
+
+impl YakuQuestion {
+    pub fn is_dealer(&self) -> bool {
+        self.hand.seat_wind == Tile::new(Suit::Honor, 1)
+    }
+
+    // the score question that follows the yaku question
+    pub fn get_hand_score(&self) -> HandScoreData {
+        HandScoreData {
+            han: self.evaluation.get_total_han(),
+            fu: self.evaluation.fu,
+            honba: 0,
+            ron: self.hand.is_ron,
+            is_dealer: self.is_dealer(),
+        }
+    }
+}
+
+fn generate_dora_indicators<R: Rng>(rng: &mut R, hand: &TileHand) -> Option<Vec<Tile>> {
+    let quads_count = hand
+        .declared_melds
+        .iter()
+        .filter(|meld| meld.kind == MeldKind::Quad)
+        .count();
+    let indicators: Vec<Tile> = (0..=quads_count)
+        .map(|_| Tile::from_index(rng.gen_range(0..TILE_KINDS_COUNT)))
+        .collect();
+
+    // indicators are taken from the same set of tiles as the hand
+    let mut tiles = hand.get_all_tiles();
+    tiles.extend(indicators.iter().copied());
+    let is_possible = indicators
+        .iter()
+        .all(|indicator| tiles.iter().filter(|tile| *tile == indicator).count() <= 4);
+    is_possible.then_some(indicators)
+}
+
+// generates a hand that has at least one yaku, but no yakuman
+pub fn generate_yaku_question<R: Rng>(rng: &mut R, settings: ScoringSettings) -> YakuQuestion {
+    loop {
+        let hand = generate_random_tile_hand(rng, settings);
+        if has_yakuman(&hand) {
+            continue;
+        }
+
+        let Some(dora_indicators) = generate_dora_indicators(rng, &hand) else {
+            continue;
+        };
+        let is_riichi = hand.is_closed() && rng.gen_bool(0.6);
+        let context = WinContext {
+            is_riichi,
+            is_ippatsu: is_riichi && rng.gen_bool(0.15),
+            dora_indicators,
+        };
+
+        let Some(evaluation) = evaluate_hand(&hand, &context, settings) else {
+            continue;
+        };
+        if evaluation.yaku.is_empty() {
+            continue;
+        }
+
+        return YakuQuestion {
+            hand,
+            context,
+            evaluation,
+        };
+    }
+}
+
+pub fn get_yaku_question_text(question: &YakuQuestion) -> String {
+    let hand = &question.hand;
+    let mut tiles = hand.get_all_tiles();
+    tiles.extend([hand.seat_wind, hand.round_wind]);
+    tiles.extend(question.context.dora_indicators.iter().copied());
+
+    let riichi_text = match (question.context.is_riichi, question.context.is_ippatsu) {
+        (true, true) => "\nRiichi, ippatsu",
+        (true, false) => "\nRiichi",
+        _ => "",
+    };
+
+    format!(
+        "{}{}\nDora indicators: {}\nSeat wind: {}, round wind: {}\n\n{}\n\nList the yaku (e.g. riichi, tanyao) or send the total han including dora",
+        hand.format_hand(),
+        riichi_text,
+        format_tiles(&question.context.dora_indicators),
+        hand.seat_wind.get_honor_name(),
+        hand.round_wind.get_honor_name(),
+        get_honor_legend_text(&tiles),
+    )
+}
+
+// accepts a total han number like "4" or "4 han", or a list of yaku like "riichi, tanyao"
+pub fn parse_yaku_answer(text: &str) -> Result<YakuAnswer, YakuAnswerParseError> {
+    let text: String = text
+        .chars()
+        .map(normalize_char)
+        .collect::<String>()
+        .to_lowercase();
+    let text = text.trim();
+
+    if text.is_empty() {
+        return Err(YakuAnswerParseError::Empty);
+    }
+
+    if let Ok(han) = text
+        .strip_suffix("han")
+        .unwrap_or(text)
+        .trim()
+        .parse::<u8>()
+    {
+        return Ok(YakuAnswer::Han(han));
+    }
+
+    let mut yaku = Vec::new();
+    for name in text.split([',', ';', '+', '\n']).map(|name| name.trim()) {
+        // dora is not a yaku, but people often list it together with the yaku
+        if name.is_empty() || name.starts_with("dora") {
+            continue;
+        }
+        let Some(parsed_yaku) = Yaku::from_name(name) else {
+            return Err(YakuAnswerParseError::UnknownYaku {
+                name: name.to_string(),
+            });
+        };
+        if !yaku.contains(&parsed_yaku) {
+            yaku.push(parsed_yaku);
+        }
+    }
+
+    if yaku.is_empty() {
+        return Err(YakuAnswerParseError::Empty);
+    }
+    Ok(YakuAnswer::Yaku(yaku))
+}
+
+// returns the yaku that were missed and the yaku that were listed but the hand doesn't have
+pub fn compare_yaku(correct: &[Yaku], answered: &[Yaku]) -> (Vec<Yaku>, Vec<Yaku>) {
+    let missed = correct
+        .iter()
+        .copied()
+        .filter(|yaku| !answered.contains(yaku))
+        .collect();
+    let extra = answered
+        .iter()
+        .copied()
+        .filter(|yaku| !correct.contains(yaku))
+        .collect();
+    (missed, extra)
+}
+
+pub fn get_yaku_list_text(question: &YakuQuestion, settings: ScoringSettings) -> String {
+    let is_closed = question.hand.is_closed();
+    let evaluation = &question.evaluation;
+
+    let mut lines: Vec<String> = evaluation
+        .yaku
+        .iter()
+        .map(|yaku| {
+            format!(
+                "{} - {} han",
+                yaku.get_name(),
+                yaku.get_han(is_closed, settings)
+            )
+        })
+        .collect();
+    if evaluation.dora > 0 {
+        lines.push(format!("dora - {} han", evaluation.dora));
+    }
+    lines.push(format!("Total: {} han", evaluation.get_total_han()));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user_settings::get_default_settings;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_parse_yaku_answer() {
+        assert_eq!(parse_yaku_answer("4"), Ok(YakuAnswer::Han(4)));
+        assert_eq!(parse_yaku_answer("３ han"), Ok(YakuAnswer::Han(3)));
+        assert_eq!(
+            parse_yaku_answer("Riichi, all simples, tsumo, dora 2, riichi"),
+            Ok(YakuAnswer::Yaku(vec![
+                Yaku::Riichi,
+                Yaku::Tanyao,
+                Yaku::MenzenTsumo
+            ]))
+        );
+        assert_eq!(parse_yaku_answer("dora"), Err(YakuAnswerParseError::Empty));
+        assert_eq!(
+            parse_yaku_answer("riichi, yakuman"),
+            Err(YakuAnswerParseError::UnknownYaku {
+                name: "yakuman".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_compare_yaku() {
+        let (missed, extra) = compare_yaku(
+            &[Yaku::Riichi, Yaku::Pinfu, Yaku::Tanyao],
+            &[Yaku::Tanyao, Yaku::Riichi, Yaku::Iipeikou],
+        );
+        assert_eq!(missed, vec![Yaku::Pinfu]);
+        assert_eq!(extra, vec![Yaku::Iipeikou]);
+    }
+
+    #[test]
+    fn test_generated_questions_have_yaku() {
+        let settings = get_default_settings().scoring_settings;
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..300 {
+            let question = generate_yaku_question(&mut rng, settings);
+            assert!(!question.evaluation.yaku.is_empty());
+            assert!(!has_yakuman(&question.hand));
+            if !question.hand.is_closed() {
+                assert!(!question.context.is_riichi);
+            }
+        }
+    }
+}